@@ -2,24 +2,29 @@
 //!
 use crate::{
     content_types::{Content, ContentInfo},
+    fetcher::Fetcher,
     header::xchina_headers,
+    limiter::limiter,
+    manifest::{now, FileEntry, Manifest, Status, WorkEntry},
     opt_parse::DownloadType,
     page_parse::PageParser,
+    resume::{download_to, DownloadError},
+    retry::{check_status, policy, FetchError},
     splash_client::SplashClient,
+    verify::verify_or_quarantine,
     DOWNLOAD_TYPE, PROXY, SAVE_DIR,
 };
-use bytes::Bytes;
-use std::{path::PathBuf, sync::Arc};
-use tokio::sync::{mpsc, Semaphore};
+use reqwest::header;
+use std::{collections::HashMap, path::Path, sync::Arc};
 use tracing::{debug, error, info, warn};
 
-type UUrl = String;
-
 #[derive(Clone)]
 pub struct XchaClient {
     pub conn: reqwest::Client,
     pub splash_conn: SplashClient,
-    pub page_parser: PageParser,
+    pub page_parser: PageParser<Fetcher>,
+    /// SAVE_DIR中的下载清单，每下载完一个作品记录一次
+    pub manifest: Option<Arc<Manifest>>,
 }
 
 impl XchaClient {
@@ -34,28 +39,65 @@ impl XchaClient {
         let conn = builder.build().unwrap();
         let splash_conn = SplashClient::new();
 
-        let page_parser = PageParser::new(splash_conn.clone());
+        let page_parser = PageParser::new(Fetcher::new());
+
+        let manifest = SAVE_DIR.get().and_then(|dir| match Manifest::open(dir) {
+            Ok(m) => Some(Arc::new(m)),
+            Err(e) => {
+                error!("读取 {} 中的下载清单失败: {}", dir.display(), e);
+                None
+            }
+        });
 
         Self {
             conn,
             splash_conn,
             page_parser,
+            manifest,
         }
     }
 
+    /// 记录作品的下载情况到清单中。files为None表示无法解析出该作品的文件列表
+    fn record(&self, info: ContentInfo, files: Option<Vec<FileEntry>>) -> WorkEntry {
+        let status = match &files {
+            Some(fs) if fs.iter().all(|f| f.status == Status::Done) => Status::Done,
+            _ => Status::Failed,
+        };
+        let entry = WorkEntry {
+            info,
+            files: files.unwrap_or_default(),
+            status,
+            time: now(),
+        };
+        if let Some(m) = &self.manifest {
+            if let Err(e) = m.record_work(entry.clone()) {
+                error!("写入下载清单失败: {}", e);
+            }
+        }
+        entry
+    }
+
     /// 下载作品中的内容
-    pub async fn download_content(&self, content: Content) {
+    pub async fn download_content(&self, content: Content) -> WorkEntry {
+        let content_info = content.content_info().clone();
+        let files = self.download_files(content).await;
+        self.record(content_info, files)
+    }
+
+    /// 下载作品中的各个文件，返回各文件的下载结果，无法得到文件列表时返回None
+    async fn download_files(&self, content: Content) -> Option<Vec<FileEntry>> {
         let content_info = content.content_info().clone();
         let mut urls = content.urls();
+        let mut videos = content.videos;
 
         if urls.is_empty() {
             warn!("{}页没有内容可下载", content_info.page_url);
-            return;
+            return Some(vec![]);
         }
 
         // 先拿一个url进行探测该url是否正确，如果正确，则继续，否则解析作品页获得正确的url
         let first_url = urls.first().unwrap();
-        if self.download_one_retry(&first_url).await.is_err() {
+        if self.probe_retry(first_url).await.is_err() {
             let all_content_urls = self
                 .page_parser
                 .all_content_urls(&content_info.page_url)
@@ -63,8 +105,9 @@ impl XchaClient {
             match all_content_urls {
                 Some(c) => {
                     urls = c.urls();
+                    videos = c.videos;
                 }
-                None => return,
+                None => return None,
             }
         }
 
@@ -78,57 +121,91 @@ impl XchaClient {
 
         debug!("等待被下载的url列表: {:#?}", urls);
 
-        let (tx, rx) = mpsc::channel::<(Bytes, UUrl, PathBuf)>(1000);
+        // 视频文件名对应的大小，例如"0003.mp4" => "29M"
+        let filesizes: HashMap<String, String> = videos
+            .into_iter()
+            .map(|v| (v.filename, v.filesize))
+            .collect();
 
-        let save_dir = SAVE_DIR.get().unwrap();
-        let save_dir = content_info.file_dir(save_dir);
+        let root_dir = SAVE_DIR.get().unwrap();
+        let save_dir = content_info.file_dir(root_dir);
         if let Err(e) = tokio::fs::create_dir_all(&save_dir).await {
             error!("创建目录 {} 失败, 错误信息: {}", save_dir.display(), e);
-            return;
+            return None;
         }
 
-        let s_self = self.clone();
-        tokio::spawn(async move {
-            let semaphore = Arc::new(Semaphore::new(20));
-            let mut tasks = vec![];
-            for url in urls {
-                let filename = url.rsplit_once('/').unwrap().1;
-                let file_path = save_dir.join(filename);
-                if file_path.exists() {
+        let mut files = vec![];
+        let mut tasks = vec![];
+        for url in urls {
+            let filename = url.rsplit_once('/').unwrap().1;
+            let file_path = save_dir.join(filename);
+            let filesize = filesizes.get(filename).cloned();
+            // 只有完整下载的文件才会从.part重命名为最终文件名，
+            // 但可能是之前保存的错误页面等，校验失败的文件会被移走并重新下载
+            if let Ok(meta) = std::fs::metadata(&file_path) {
+                if verify_or_quarantine(root_dir, &file_path, filesize.as_deref()).is_ok() {
                     info!("文件已存在, {}", file_path.display());
+                    files.push(FileEntry {
+                        url,
+                        path: file_path,
+                        size: Some(meta.len()),
+                        filesize,
+                        status: Status::Done,
+                        error: None,
+                    });
                     continue;
                 }
-
-                let s_self = s_self.clone();
-                let sem = semaphore.clone();
-                let tx = tx.clone();
-                let task = tokio::spawn(async move {
-                    let _permit = sem.acquire().await.unwrap();
-                    debug!("下载 {}", url);
-                    match s_self.download_one_retry(&url).await {
-                        Ok(bs) => {
-                            debug!("下载 {} 长度: {}", url, bs.len());
-                            tx.send((bs, url, file_path)).await.unwrap();
-                        }
-                        Err(e) => {
-                            error!("下载({})失败: {}", url, e);
-                        }
-                    }
-                });
-                tasks.push(task);
             }
 
-            for task in tasks {
-                task.await.unwrap();
-            }
-            drop(tx);
-        });
+            let s_self = self.clone();
+            let root_dir = root_dir.clone();
+            let task = tokio::spawn(async move {
+                let _permit = limiter().file_permit().await;
+                debug!("下载 {}", url);
+                let res = s_self
+                    .download_file_retry(&url, &file_path)
+                    .await
+                    .map_err(|e| e.to_string())
+                    .and_then(|len| {
+                        verify_or_quarantine(&root_dir, &file_path, filesize.as_deref())
+                            .map(|_| len)
+                            .map_err(|p| format!("校验失败: {}", p))
+                    });
+                let (size, status, error) = match res {
+                    Ok(len) => {
+                        info!(
+                            "下载成功: {}, 长度: {}, 保存在: {}",
+                            url,
+                            len,
+                            file_path.display()
+                        );
+                        (Some(len), Status::Done, None)
+                    }
+                    Err(e) => {
+                        error!("下载({})失败: {}", url, e);
+                        (None, Status::Failed, Some(e))
+                    }
+                };
+                FileEntry {
+                    url,
+                    path: file_path,
+                    size,
+                    filesize,
+                    status,
+                    error,
+                }
+            });
+            tasks.push(task);
+        }
 
-        self.write_file(rx).await;
+        for task in tasks {
+            files.push(task.await.unwrap());
+        }
+        Some(files)
     }
 
     /// 给定一个作品基本信息，下载该作品中的所有内容(将先解析页面)
-    pub async fn download_from_content_info(&self, content_info: ContentInfo) {
+    pub async fn download_from_content_info(&self, content_info: ContentInfo) -> WorkEntry {
         let page_url = &content_info.page_url;
         // 解析页面中的所有内容列表
         let all_content_urls = self.page_parser.all_content_urls(&page_url).await;
@@ -136,58 +213,57 @@ impl XchaClient {
             Some(c) => c,
             None => {
                 error!("无法解析该页: {}", page_url);
-                return;
+                return self.record(content_info, None);
             }
         };
         debug!("解析页({})获得信息: {:?}", page_url, content);
 
-        self.download_content(content).await;
+        self.download_content(content).await
     }
 
-    /// 并发下载多个作品
-    pub async fn download_multi_content_infos(&self, content_infos: Vec<ContentInfo>) {
-        let semaphore = Arc::new(Semaphore::new(10));
+    /// 并发下载多个作品，返回各作品的下载情况
+    pub async fn download_multi_content_infos(
+        &self,
+        content_infos: Vec<ContentInfo>,
+    ) -> Vec<WorkEntry> {
         let mut tasks = vec![];
 
         for content_info in content_infos {
             let c_self = self.clone();
-            let sem = semaphore.clone();
             let task = tokio::spawn(async move {
-                let _ = sem.acquire().await.unwrap();
-                c_self.download_from_content_info(content_info).await;
+                let _permit = limiter().work_permit().await;
+                c_self.download_from_content_info(content_info).await
             });
             tasks.push(task);
         }
 
+        let mut works = vec![];
         for task in tasks {
-            let _ = task.await;
+            if let Ok(w) = task.await {
+                works.push(w);
+            }
         }
+        works
     }
 
-    async fn download_one(&self, url: &str) -> Result<Bytes, reqwest::Error> {
-        self.conn.get(url).send().await?.bytes().await
+    /// 只请求第一个字节来检查url是否可用，不读取响应体
+    async fn probe(&self, url: &str) -> Result<(), FetchError> {
+        limiter().request(url).await;
+        let req = self.conn.get(url).header(header::RANGE, "bytes=0-0");
+        check_status(req.send().await?)?;
+        Ok(())
     }
 
-    /// 重试3次的下载
-    async fn download_one_retry(&self, url: &str) -> Result<Bytes, reqwest::Error> {
-        for _ in 1..=2 {
-            if let Ok(data) = self.download_one(url).await {
-                return Ok(data);
-            }
-            tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-        }
-        self.download_one(url).await
+    /// 按RetryPolicy重试的探测
+    async fn probe_retry(&self, url: &str) -> Result<(), FetchError> {
+        policy().run(url, || self.probe(url)).await
     }
 
-    /// 阻塞等待接收下载的数据，并写入文件
-    async fn write_file(&self, mut rx: mpsc::Receiver<(Bytes, UUrl, PathBuf)>) {
-        while let Some((data, url, file_path)) = rx.recv().await {
-            debug!("接收 {} 字节数据长度: {}", data.len(), url);
-            if let Err(e) = tokio::fs::write(&file_path, data).await {
-                error!("数据写入 {} 文件失败, 错误信息: {}", file_path.display(), e);
-            }
-            info!("下载成功: {}, 保存在: {}", url, file_path.display());
-        }
+    /// 按RetryPolicy重试的下载，数据写入`file_path`，每次重试都从上次中断的位置继续
+    async fn download_file_retry(&self, url: &str, file_path: &Path) -> Result<u64, DownloadError> {
+        policy()
+            .run(url, || download_to(&self.conn, url, file_path))
+            .await
     }
 }
 
@@ -199,13 +275,15 @@ impl XchaClient {
         let filename = url.rsplit_once('/').unwrap().1;
         let path = SAVE_DIR.get().unwrap().join(filename);
 
-        match client.download_one_retry(url).await {
-            Ok(data) => tokio::fs::write(&path, data).await.unwrap(),
+        match client.download_file_retry(url, &path).await {
+            Ok(_) => match verify_or_quarantine(SAVE_DIR.get().unwrap(), &path, None) {
+                Ok(()) => info!("下载成功: {}，保存在 {}", url, path.display()),
+                Err(p) => error!("下载的文件校验失败({})，错误信息: {}", url, p),
+            },
             Err(e) => {
                 error!("下载失败({})，错误信息: {}", url, e);
             }
         }
-        info!("下载成功: {}，保存在 {}", url, path.display());
     }
 
     /// 只下载一个作品页面中的所有内容，例如：https://xchina.co/photo/id-64c4abcd9026b/1.html
@@ -0,0 +1,100 @@
+//! 获取页面html的方式：通过Splash渲染，或者直接向xchina请求
+//!
+
+use crate::{
+    header::xchina_headers,
+    limiter::limiter,
+    opt_parse::FetcherKind,
+    retry::{check_status, policy, FetchError},
+    splash_client::SplashClient,
+    FETCHER, PROXY,
+};
+use async_trait::async_trait;
+use tracing::{debug, instrument};
+
+/// 获取页面html。实现者应自行处理重试(通常按`retry::policy()`)
+#[async_trait]
+pub trait PageFetcher: Clone + Send + Sync + 'static {
+    async fn fetch_html(&self, url: &str) -> Result<String, FetchError>;
+}
+
+#[async_trait]
+impl PageFetcher for SplashClient {
+    async fn fetch_html(&self, url: &str) -> Result<String, FetchError> {
+        self.get_html_retry(url).await
+    }
+}
+
+/// 不经过Splash，直接请求页面，使用和下载时相同的请求头和代理
+#[derive(Clone)]
+pub struct HttpFetcher {
+    conn: reqwest::Client,
+}
+
+impl HttpFetcher {
+    pub fn new() -> Self {
+        let mut builder = reqwest::Client::builder().default_headers(xchina_headers());
+        if let Some(Some(p)) = PROXY.get() {
+            builder = builder.proxy(reqwest::Proxy::all(p).unwrap());
+        }
+
+        Self {
+            conn: builder.build().unwrap(),
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub async fn get_html(&self, url: &str) -> Result<String, FetchError> {
+        debug!("send request: {}", url);
+        limiter().request(url).await;
+        let resp = check_status(self.conn.get(url).send().await?)?;
+        Ok(resp.text().await?)
+    }
+}
+
+impl Default for HttpFetcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl PageFetcher for HttpFetcher {
+    /// 请求页面，按RetryPolicy重试
+    async fn fetch_html(&self, url: &str) -> Result<String, FetchError> {
+        policy().run(url, || self.get_html(url)).await
+    }
+}
+
+/// 根据`--fetcher`选项在运行时选择的PageFetcher
+#[derive(Clone)]
+pub enum Fetcher {
+    Splash(SplashClient),
+    Http(HttpFetcher),
+}
+
+impl Fetcher {
+    /// 按`--fetcher`选项创建，未设置时使用Splash
+    pub fn new() -> Self {
+        match FETCHER.get().copied().unwrap_or_default() {
+            FetcherKind::Splash => Self::Splash(SplashClient::new()),
+            FetcherKind::Http => Self::Http(HttpFetcher::new()),
+        }
+    }
+}
+
+impl Default for Fetcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl PageFetcher for Fetcher {
+    async fn fetch_html(&self, url: &str) -> Result<String, FetchError> {
+        match self {
+            Fetcher::Splash(f) => f.fetch_html(url).await,
+            Fetcher::Http(f) => f.fetch_html(url).await,
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! page_parse的离线测试数据：保存的页面html，以及各页面解析结果的golden文件
+//!
+//! 目录结构：
+//! - `index.json`: 各页面的名称、url和页面类型
+//! - `<name>.html`: 页面html
+//! - `<name>.golden.json`: 页面的解析结果，测试时与解析结果比较。
+//!   设置环境变量`UPDATE_GOLDEN=1`运行测试，会用当前的解析结果重写golden文件
+//!
+//! 页面只能用`record-fixture`命令从网站保存，不要手写：测试是为了发现网站布局的变化，
+//! 按当前选择器写出的页面发现不了。生成的golden文件只是当前解析器的输出，
+//! 提交前要对照网页逐项核对(分类、演员、发布日期、图片和视频url等)
+//!
+
+use crate::{fetcher::PageFetcher, opt_parse::UrlType};
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// 仓库中的测试数据目录
+pub const FIXTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/pages");
+
+const INDEX_FILE: &str = "index.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FixtureKind {
+    /// 主页，解析为MainPageFenLei
+    Main,
+    /// 分类页，解析为Vec<ContentInfo>
+    Series,
+    /// 作品页，解析为Content
+    Work,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+    pub name: String,
+    pub url: String,
+    pub kind: FixtureKind,
+}
+
+impl Fixture {
+    pub fn html_path(&self, dir: &Path) -> PathBuf {
+        dir.join(format!("{}.html", self.name))
+    }
+
+    pub fn golden_path(&self, dir: &Path) -> PathBuf {
+        dir.join(format!("{}.golden.json", self.name))
+    }
+}
+
+/// 读取目录中的index.json，不存在时为空
+pub fn load_index(dir: &Path) -> io::Result<Vec<Fixture>> {
+    match std::fs::read_to_string(dir.join(INDEX_FILE)) {
+        Ok(s) => Ok(serde_json::from_str(&s)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e),
+    }
+}
+
+fn save_index(dir: &Path, fixtures: &[Fixture]) -> io::Result<()> {
+    let mut s = serde_json::to_string_pretty(fixtures)?;
+    s.push('\n');
+    std::fs::write(dir.join(INDEX_FILE), s)
+}
+
+/// 获取url的页面并保存到dir中，名称相同的页面会被覆盖
+pub async fn record<F: PageFetcher>(
+    fetcher: &F,
+    dir: &Path,
+    name: &str,
+    url: &str,
+) -> Result<Fixture, Box<dyn std::error::Error>> {
+    let kind = match UrlType::parse(url) {
+        Some(UrlType::MainPage(_)) => FixtureKind::Main,
+        Some(UrlType::FenLei(_)) => FixtureKind::Series,
+        Some(UrlType::ZuoPing(_)) => FixtureKind::Work,
+        _ => return Err(format!("({})不是可解析页面", url).into()),
+    };
+    let html = fetcher.fetch_html(url).await?;
+
+    let fixture = Fixture {
+        name: name.to_string(),
+        url: url.to_string(),
+        kind,
+    };
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(fixture.html_path(dir), html)?;
+
+    let mut fixtures = load_index(dir)?;
+    fixtures.retain(|f| f.name != name);
+    fixtures.push(fixture.clone());
+    save_index(dir, &fixtures)?;
+
+    Ok(fixture)
+}
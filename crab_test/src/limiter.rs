@@ -0,0 +1,173 @@
+//! 全局限流：限制同时下载的作品数和文件数，每个host每秒的请求数，以及下载的总带宽
+//!
+//! XchaClient、SplashClient和HttpFetcher的每个请求都先经过`limiter()`。
+//! 每秒请求数和带宽使用令牌桶限制，令牌不足时等待，而不是拒绝请求
+//!
+
+use crate::LIMITER;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    sync::{Semaphore, SemaphorePermit},
+    time::{Duration, Instant},
+};
+use url::Url;
+
+/// 令牌桶：每秒补充rate个令牌，最多积攒capacity个
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    /// (当前令牌数, 上次补充的时间)。令牌数可以为负，表示已被预订的令牌
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    /// 桶中初始是满的，允许最多1秒的突发。rate必须大于0
+    pub fn new(rate: f64) -> Self {
+        assert!(rate > 0.0, "令牌桶的速率必须大于0: {}", rate);
+        let capacity = rate.max(1.0);
+        Self {
+            rate,
+            capacity,
+            state: Mutex::new((capacity, Instant::now())),
+        }
+    }
+
+    /// 取走n个令牌，令牌不足时等待到补足为止。n可以大于桶的容量
+    pub async fn acquire(&self, n: f64) {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            let (tokens, last) = *state;
+            let tokens = (tokens + now.duration_since(last).as_secs_f64() * self.rate)
+                .min(self.capacity)
+                - n;
+            *state = (tokens, now);
+            if tokens < 0.0 {
+                Duration::from_secs_f64(-tokens / self.rate)
+            } else {
+                Duration::ZERO
+            }
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Limiter {
+    /// 同时下载的作品数
+    works: Semaphore,
+    /// 同时下载的文件数(所有作品共享)
+    files: Semaphore,
+    /// 每个host每秒的请求数，None表示不限制
+    rps: Option<f64>,
+    hosts: Mutex<HashMap<String, Arc<TokenBucket>>>,
+    /// 下载的总带宽(字节/秒)，None表示不限制
+    bandwidth: Option<TokenBucket>,
+}
+
+impl Limiter {
+    pub fn new(
+        max_works: usize,
+        max_files: usize,
+        rps: Option<f64>,
+        bandwidth: Option<u64>,
+    ) -> Self {
+        Self {
+            works: Semaphore::new(max_works),
+            files: Semaphore::new(max_files),
+            rps,
+            hosts: Mutex::new(HashMap::new()),
+            bandwidth: bandwidth.map(|b| TokenBucket::new(b as f64)),
+        }
+    }
+
+    pub async fn work_permit(&self) -> SemaphorePermit<'_> {
+        self.works.acquire().await.unwrap()
+    }
+
+    pub async fn file_permit(&self) -> SemaphorePermit<'_> {
+        self.files.acquire().await.unwrap()
+    }
+
+    /// 向url所在的host发送请求之前调用
+    pub async fn request(&self, url: &str) {
+        let rps = match self.rps {
+            Some(r) => r,
+            None => return,
+        };
+        let host = Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(|h| h.to_string()))
+            .unwrap_or_default();
+        let bucket = self
+            .hosts
+            .lock()
+            .unwrap()
+            .entry(host)
+            .or_insert_with(|| Arc::new(TokenBucket::new(rps)))
+            .clone();
+        bucket.acquire(1.0).await;
+    }
+
+    /// 收到n字节的数据后调用
+    pub async fn received(&self, n: usize) {
+        if let Some(b) = &self.bandwidth {
+            b.acquire(n as f64).await;
+        }
+    }
+}
+
+impl Default for Limiter {
+    /// 同时下载10个作品、20个文件，不限制请求数和带宽
+    fn default() -> Self {
+        Self::new(10, 20, None, None)
+    }
+}
+
+/// 全局的Limiter，未设置时使用默认值
+pub fn limiter() -> &'static Limiter {
+    LIMITER.get_or_init(Limiter::default)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn token_bucket() {
+        let bucket = TokenBucket::new(10.0);
+        let start = Instant::now();
+        // 初始的10个令牌不需要等待
+        for _ in 0..10 {
+            bucket.acquire(1.0).await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(10));
+        // 之后每个令牌等待0.1秒
+        for _ in 0..10 {
+            bucket.acquire(1.0).await;
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(990) && elapsed < Duration::from_millis(1100));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn per_host() {
+        let limiter = Limiter::new(1, 1, Some(2.0), None);
+        let start = Instant::now();
+        for _ in 0..4 {
+            limiter.request("https://xchina.co/photo/id-1.html").await;
+        }
+        // 不同host的令牌桶互不影响
+        limiter
+            .request("https://img.xchina.biz/photos/1/0001.jpg")
+            .await;
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(990) && elapsed < Duration::from_millis(1100));
+    }
+}
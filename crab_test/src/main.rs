@@ -2,29 +2,45 @@
 
 use crate::{
     content_client::XchaClient,
+    fetcher::Fetcher,
+    limiter::Limiter,
+    manifest::Manifest,
     opt_parse::{args_init, Cmds, UrlType},
     others::enable_log,
+    output::{FenLeiRow, FileRow, PageRow},
     page_parse::PageParser,
-    splash_client::SplashClient,
+    retry::RetryPolicy,
 };
 use once_cell::sync::OnceCell;
-use opt_parse::{Download, DownloadType, Parse};
+use opt_parse::{Download, DownloadType, FetcherKind, Parse, RecordFixture, SyncCmd, VerifyCmd};
 use others::parse_number_range;
 use std::path::PathBuf;
 use tracing::{debug, error};
 
 pub mod content_client;
 pub mod content_types;
+pub mod fetcher;
+pub mod fixture;
 pub mod header;
+pub mod limiter;
+pub mod manifest;
 pub mod opt_parse;
 pub mod others;
+pub mod output;
 pub mod page_parse;
+pub mod resume;
+pub mod retry;
 pub mod splash_client;
+pub mod sync;
+pub mod verify;
 
 pub static PROXY: OnceCell<Option<String>> = OnceCell::new();
 pub static SAVE_DIR: OnceCell<PathBuf> = OnceCell::new();
 pub static SPLASH_ADDR: OnceCell<String> = OnceCell::new();
 pub static DOWNLOAD_TYPE: OnceCell<DownloadType> = OnceCell::new();
+pub static FETCHER: OnceCell<FetcherKind> = OnceCell::new();
+pub static LIMITER: OnceCell<Limiter> = OnceCell::new();
+pub static RETRY: OnceCell<RetryPolicy> = OnceCell::new();
 
 pub const XCHAIN_BASE_URL: &str = "https://xchina.co";
 
@@ -43,6 +59,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         SPLASH_ADDR.set(simple_opts.splash_addr).unwrap();
         SAVE_DIR.set(simple_opts.save_dir).unwrap();
         PROXY.set(simple_opts.proxy).unwrap();
+        FETCHER.set(simple_opts.fetcher).unwrap();
+        LIMITER
+            .set(Limiter::new(
+                simple_opts.max_works,
+                simple_opts.max_files,
+                simple_opts.rps,
+                simple_opts.bandwidth,
+            ))
+            .unwrap();
+        RETRY.set(simple_opts.retry).unwrap();
     }
 
     let start = std::time::Instant::now();
@@ -53,6 +79,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             DOWNLOAD_TYPE.set(p.only).unwrap();
             download(&p).await;
         }
+        Cmds::Sync(p) => {
+            DOWNLOAD_TYPE.set(p.only).unwrap();
+            sync(&p).await;
+        }
+        Cmds::RecordFixture(p) => record_fixture(&p).await,
+        Cmds::Verify(p) => verify(&p),
         Cmds::No => {
             // let url = "https://xchina.co/photos/series-5f1476781eab4.html";
             // let url = "https://xchina.co/photo/id-5f55202b3e808.html";
@@ -69,8 +101,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             // let contents = PageParser::parse_serie_page(&str);
             // println!("{:#?}", contents);
 
-            let splash_client = SplashClient::new();
-            let page_parse = PageParser::new(splash_client);
+            let page_parse = PageParser::new(Fetcher::new());
             // let url = "https://xchina.co/photo/id-64846cdd817b7.html";
             let url = "https://xchina.co/photo/id-6496855837cde.html";
             let content = page_parse.all_content_urls(url).await;
@@ -116,20 +147,21 @@ async fn parse(opts: &Parse) {
         None => panic!("无效的url: {}", opts.url),
     };
 
-    match &url {
+    let output = opts.output.as_deref();
+    let res = match &url {
         UrlType::SingleFile(u) => {
             error!("({})不是可解析页面", u);
+            return;
         }
         UrlType::MainPage(u) => {
             // 获取该页
-            let splash_client = SplashClient::new();
-            let res = PageParser::new(splash_client).parse_main_page(u).await;
-            println!("{:#?}", res);
+            let res = PageParser::new(Fetcher::new()).parse_main_page(u).await;
+            let rows = FenLeiRow::from_main_page(&res);
+            output::output(opts.format, output, &res, &rows)
         }
         UrlType::ZuoPing(u) => {
             // 解析页面中的所有内容列表
-            let splash_client = SplashClient::new();
-            let content = match PageParser::new(splash_client).all_content_urls(u).await {
+            let content = match PageParser::new(Fetcher::new()).all_content_urls(u).await {
                 Some(c) => c,
                 None => {
                     error!("无法解析该页: {}", u);
@@ -137,29 +169,34 @@ async fn parse(opts: &Parse) {
                 }
             };
 
-            println!("{:#?}", content);
+            let rows = FileRow::from_content(&content);
+            output::output(opts.format, output, &content, &rows)
         }
         UrlType::FenLei(url) => {
-            let splash_client = SplashClient::new();
-            let page_parser = PageParser::new(splash_client);
+            let page_parser = PageParser::new(Fetcher::new());
             // 获取最大的页码
             if opts.max_page {
                 let urls = page_parser.parse_pages_urls(&url).await;
-                for (u, _) in urls {
-                    println!("{}", u);
-                }
-                return;
-            }
+                let rows: Vec<_> = urls
+                    .into_iter()
+                    .map(|(url, current)| PageRow { url, current })
+                    .collect();
+                output::output(opts.format, output, &rows, &rows)
+            } else {
+                let urls = match &opts.pages {
+                    None => vec![url.to_string()],
+                    Some(range_str) => make_urls_from_range(url, range_str),
+                };
+                debug!("将要解析的分类页: {:#?}", urls);
 
-            let urls = match &opts.pages {
-                None => vec![url.to_string()],
-                Some(range_str) => make_urls_from_range(url, range_str),
-            };
-            debug!("将要解析的分类页: {:#?}", urls);
-
-            let content_infos = page_parser.parse_multi_serie_pages(urls).await;
-            println!("{:#?}", content_infos);
+                let content_infos = page_parser.parse_multi_serie_pages(urls).await;
+                output::output(opts.format, output, &content_infos, &content_infos)
+            }
         }
+    };
+
+    if let Err(e) = res {
+        error!("输出解析结果失败: {}", e);
     }
 }
 
@@ -193,6 +230,43 @@ async fn download(opts: &Download) {
     }
 }
 
+async fn sync(opts: &SyncCmd) {
+    let url = match UrlType::parse(&opts.url) {
+        Some(UrlType::FenLei(u)) => u,
+        _ => panic!("无效的分类url: {}", opts.url),
+    };
+
+    // 从第1页开始，最多max_pages页
+    let page_urls = make_urls_from_range(&url, &format!("1~{}", opts.max_pages));
+    let client = XchaClient::new();
+    let report = client.sync(page_urls, !opts.no_retry).await;
+    print!("{}", report);
+}
+
+fn verify(opts: &VerifyCmd) {
+    let save_dir = SAVE_DIR.get().unwrap();
+    let manifest = match Manifest::open(save_dir) {
+        Ok(m) => Some(m),
+        Err(e) => {
+            error!("读取 {} 中的下载清单失败: {}", save_dir.display(), e);
+            None
+        }
+    };
+    let report = verify::verify_dir(save_dir, manifest.as_ref(), opts.dry_run);
+    print!("{}", report);
+}
+
+async fn record_fixture(opts: &RecordFixture) {
+    let dir = opts
+        .dir
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(fixture::FIXTURE_DIR));
+    match fixture::record(&Fetcher::new(), &dir, &opts.name, &opts.url).await {
+        Ok(f) => println!("已保存 {}", f.html_path(&dir).display()),
+        Err(e) => error!("保存页面({})失败: {}", opts.url, e),
+    }
+}
+
 // 根据给定url，以及范围字符串，解析出范围内的所有Url
 fn make_urls_from_range(url: &str, range_str: &str) -> Vec<String> {
     // 两种类型的页面，要去除base url: https://xchina.co/photos/series-5f1476781eab4
@@ -0,0 +1,274 @@
+//! 下载清单：记录SAVE_DIR中每个作品的下载情况(作品信息、各文件的url、大小和状态)，
+//! 以及每次sync的时间和已同步到的发布日期
+//!
+//! 清单保存在`<SAVE_DIR>/.crab_manifest.jsonl`中，每行一条JSON记录，只追加不修改，
+//! 读取时同一作品(或同一分类)以最后一条记录为准
+//!
+
+use crate::content_types::ContentInfo;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+
+pub const MANIFEST_FILE: &str = ".crab_manifest.jsonl";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Done,
+    Failed,
+}
+
+/// 作品中的一个文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileEntry {
+    pub url: String,
+    pub path: PathBuf,
+    /// 下载完成时的文件大小
+    pub size: Option<u64>,
+    /// 作品页中给出的视频大小，例如"29M"，用于校验
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filesize: Option<String>,
+    pub status: Status,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 一个作品的下载情况
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkEntry {
+    pub info: ContentInfo,
+    pub files: Vec<FileEntry>,
+    /// 无法解析出文件列表，或者有任何文件下载失败，则为Failed
+    pub status: Status,
+    /// 记录时间，unix时间戳(秒)
+    pub time: u64,
+}
+
+/// 一个分类的sync记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncEntry {
+    /// 分类第一页的url
+    pub url: String,
+    /// 已同步的作品中最新的发布日期，例如"2023-07-29"
+    pub latest_pub_date: String,
+    pub time: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum Record {
+    Work(WorkEntry),
+    Sync(SyncEntry),
+}
+
+#[derive(Default)]
+struct State {
+    works: HashMap<String, WorkEntry>,
+    syncs: HashMap<String, SyncEntry>,
+    /// 文件最后一行没有换行符(写入时被中断)，下次追加前先补上换行
+    broken_tail: bool,
+}
+
+impl State {
+    fn apply(&mut self, record: Record) {
+        match record {
+            Record::Work(w) => {
+                self.works.insert(w.info.page_url.clone(), w);
+            }
+            Record::Sync(s) => {
+                self.syncs.insert(s.url.clone(), s);
+            }
+        }
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+pub struct Manifest {
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+impl Manifest {
+    /// 读取save_dir中的清单，清单不存在时为空清单
+    pub fn open(save_dir: &Path) -> io::Result<Self> {
+        let path = save_dir.join(MANIFEST_FILE);
+        let mut state = State::default();
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                state.broken_tail = !text.is_empty() && !text.ends_with('\n');
+                for (i, line) in text.lines().enumerate() {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    // 写入时被中断，最后一行可能不完整，跳过
+                    match serde_json::from_str::<Record>(line) {
+                        Ok(r) => state.apply(r),
+                        Err(e) => warn!("清单{}第{}行无效: {}", path.display(), i + 1, e),
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(Self {
+            path,
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn work(&self, page_url: &str) -> Option<WorkEntry> {
+        self.state.lock().unwrap().works.get(page_url).cloned()
+    }
+
+    /// 清单中的所有作品
+    pub fn works(&self) -> Vec<WorkEntry> {
+        let state = self.state.lock().unwrap();
+        let mut works: Vec<_> = state.works.values().cloned().collect();
+        works.sort_by(|a, b| a.info.page_url.cmp(&b.info.page_url));
+        works
+    }
+
+    /// 所有下载失败的作品
+    pub fn failed_works(&self) -> Vec<WorkEntry> {
+        let state = self.state.lock().unwrap();
+        let mut works: Vec<_> = state
+            .works
+            .values()
+            .filter(|w| w.status == Status::Failed)
+            .cloned()
+            .collect();
+        works.sort_by(|a, b| a.info.page_url.cmp(&b.info.page_url));
+        works
+    }
+
+    pub fn last_sync(&self, url: &str) -> Option<SyncEntry> {
+        self.state.lock().unwrap().syncs.get(url).cloned()
+    }
+
+    pub fn record_work(&self, entry: WorkEntry) -> io::Result<()> {
+        self.append(Record::Work(entry))
+    }
+
+    pub fn record_sync(&self, entry: SyncEntry) -> io::Result<()> {
+        self.append(Record::Sync(entry))
+    }
+
+    fn append(&self, record: Record) -> io::Result<()> {
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+
+        // 持有锁写入，避免并发写入的行交错
+        let mut state = self.state.lock().unwrap();
+        if state.broken_tail {
+            line.insert(0, '\n');
+        }
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())?;
+        state.broken_tail = false;
+        state.apply(record);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn info(page_url: &str, pub_date: &str) -> ContentInfo {
+        ContentInfo {
+            fen_lei: "秀仍网".to_string(),
+            actor: "无名".to_string(),
+            title: "无标题".to_string(),
+            pub_date: pub_date.to_string(),
+            page_url: page_url.to_string(),
+            show_url: "https://img.xchina.biz/photos/64c4abcd9026b/0001_600x0.jpg".to_string(),
+            jpg_count: 1,
+            video_count: 0,
+        }
+    }
+
+    fn work(page_url: &str, status: Status) -> WorkEntry {
+        WorkEntry {
+            info: info(page_url, "2023-07-29"),
+            files: vec![FileEntry {
+                url: "https://img.xchina.biz/photos/64c4abcd9026b/0001.jpg".to_string(),
+                path: PathBuf::from("/tmp/0001.jpg"),
+                size: (status == Status::Done).then_some(1024),
+                filesize: None,
+                status,
+                error: None,
+            }],
+            status,
+            time: now(),
+        }
+    }
+
+    #[test]
+    fn replay() {
+        let dir = std::env::temp_dir().join(format!("crab_test-manifest-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let m = Manifest::open(&dir).unwrap();
+        assert!(m.work("https://xchina.co/photo/id-1.html").is_none());
+        m.record_work(work("https://xchina.co/photo/id-1.html", Status::Failed))
+            .unwrap();
+        m.record_work(work("https://xchina.co/photo/id-2.html", Status::Failed))
+            .unwrap();
+        m.record_work(work("https://xchina.co/photo/id-1.html", Status::Done))
+            .unwrap();
+        m.record_sync(SyncEntry {
+            url: "https://xchina.co/photos/series-5f1476781eab4/1.html".to_string(),
+            latest_pub_date: "2023-07-29".to_string(),
+            time: now(),
+        })
+        .unwrap();
+
+        // 模拟写入中断留下的半行
+        OpenOptions::new()
+            .append(true)
+            .open(dir.join(MANIFEST_FILE))
+            .unwrap()
+            .write_all(b"{\"kind\":\"work\",\"info\":")
+            .unwrap();
+
+        // 半行之后追加的记录不受影响
+        let m = Manifest::open(&dir).unwrap();
+        m.record_work(work("https://xchina.co/photo/id-3.html", Status::Done))
+            .unwrap();
+
+        let m = Manifest::open(&dir).unwrap();
+        assert!(m.work("https://xchina.co/photo/id-3.html").is_some());
+        let w = m.work("https://xchina.co/photo/id-1.html").unwrap();
+        assert_eq!(w.status, Status::Done);
+        assert_eq!(w.files[0].size, Some(1024));
+        let failed = m.failed_works();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].info.page_url, "https://xchina.co/photo/id-2.html");
+        let s = m
+            .last_sync("https://xchina.co/photos/series-5f1476781eab4/1.html")
+            .unwrap();
+        assert_eq!(s.latest_pub_date, "2023-07-29");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,6 +1,10 @@
-use crate::XCHAIN_BASE_URL;
-use clap::{ArgGroup, Parser, Subcommand};
-use std::{env, path::PathBuf, str::FromStr};
+use crate::{
+    others::{parse_rate, parse_size},
+    retry::RetryPolicy,
+    XCHAIN_BASE_URL,
+};
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+use std::{env, path::PathBuf, str::FromStr, time::Duration};
 use url::Url;
 
 /// 解析/下载 ×chinα.co 美图/视频
@@ -36,6 +40,50 @@ pub struct Opts {
     #[clap(short = 'o', long, env = "SAVE_DIR")]
     pub save_dir: Option<PathBuf>,
 
+    /// 获取页面html的方式，也可以设置到环境变量 FETCHER
+    ///
+    /// - splash: 通过Splash服务渲染页面(默认)
+    ///
+    /// - http: 直接请求页面，不需要Splash服务
+    #[clap(long, env = "FETCHER", value_enum, default_value_t = FetcherKind::Splash)]
+    pub fetcher: FetcherKind,
+
+    /// 同时下载的作品数，也可以设置到环境变量 MAX_WORKS，默认10
+    #[clap(long, env = "MAX_WORKS", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_works: Option<u32>,
+
+    /// 同时下载的文件数(所有作品共享)，也可以设置到环境变量 MAX_FILES，默认20
+    #[clap(long, env = "MAX_FILES", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_files: Option<u32>,
+
+    /// 每个host每秒最多发送的请求数(包括请求页面和下载文件)，可以是小数，例如0.5表示每2秒一个请求
+    ///
+    /// 也可以设置到环境变量 RPS，默认不限制
+    #[clap(long, env = "RPS", value_parser = parse_rate)]
+    pub rps: Option<f64>,
+
+    /// 下载的总带宽上限(字节/秒)，可以带后缀K/M/G，例如`512K`、`2M`
+    ///
+    /// 也可以设置到环境变量 BANDWIDTH，默认不限制
+    #[clap(long, env = "BANDWIDTH", value_parser = parse_size)]
+    pub bandwidth: Option<u64>,
+
+    /// 请求页面或下载文件时最多尝试的次数(包括第一次)，也可以设置到环境变量 RETRIES，默认3
+    ///
+    /// 只重试超时、连接中断、5xx、429等可能恢复的错误，404等不重试
+    #[clap(long, env = "RETRIES", value_parser = clap::value_parser!(u32).range(1..))]
+    pub retries: Option<u32>,
+
+    /// 第一次重试前等待的毫秒数，之后每次翻倍(并随机减少最多一半)，
+    /// 也可以设置到环境变量 RETRY_DELAY，默认500
+    #[clap(long, env = "RETRY_DELAY")]
+    pub retry_delay: Option<u64>,
+
+    /// 重试前等待的最大毫秒数(服务端返回的`Retry-After`不受此限制)，
+    /// 也可以设置到环境变量 RETRY_MAX_DELAY，默认30000
+    #[clap(long, env = "RETRY_MAX_DELAY")]
+    pub retry_max_delay: Option<u64>,
+
     /// 使用 debug 模式
     #[clap(long)]
     pub debug: bool,
@@ -46,6 +94,9 @@ pub enum Cmds {
     // Parse(Parse),
     Parse(Parse),
     Download(Download),
+    Sync(SyncCmd),
+    RecordFixture(RecordFixture),
+    Verify(VerifyCmd),
     /// 无视该子命令，我用来调试功能的选项
     #[clap(subcommand, hide(true))]
     No,
@@ -106,6 +157,19 @@ pub struct Parse {
     /// 和 --pages 选项冲突
     #[clap(short, long)]
     pub max_page: bool,
+
+    /// 输出格式
+    ///
+    /// - json: 整个解析结果
+    ///
+    /// - jsonl/csv/table: 每条记录一行，记录为主页中的分类、分类页中的作品、作品中的文件，
+    ///   或者(指定 --max-page 时)各分页的url
+    #[clap(short, long, value_enum, default_value_t = OutputFormat::Json)]
+    pub format: OutputFormat,
+
+    /// 将解析结果写入该文件，而不是输出到标准输出
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
 }
 
 /// 下载操作
@@ -140,11 +204,71 @@ pub struct Download {
     pub only: DownloadType,
 }
 
+/// 增量同步分类页：只下载上次同步之后发布的作品，并重试之前下载失败的作品
+///
+/// 同步记录和下载情况保存在下载目录的清单文件`.crab_manifest.jsonl`中
+#[derive(Debug, Parser)]
+pub struct SyncCmd {
+    /// 要同步的分类页url，例如`https://xchina.co/photos/series-5f1476781eab4.html`
+    #[clap(short, long)]
+    pub url: String,
+
+    /// 最多解析的分页数(从第1页开始)
+    ///
+    /// 首次同步时解析这么多页，之后遇到上次同步之前发布的作品即停止
+    #[clap(long, default_value_t = 10)]
+    pub max_pages: u16,
+
+    /// 不重试清单中下载失败的作品
+    #[clap(long)]
+    pub no_retry: bool,
+
+    /// 参考 download 子命令的 `--only` 选项的解释说明
+    #[clap(long, default_value = "a")]
+    pub only: DownloadType,
+}
+
+/// 校验下载目录中的所有文件：检查jpg/png/webp/mp4的文件头和是否完整，以及视频大小
+///
+/// 校验失败的文件被移动到下载目录的`.quarantine`目录中，清单中所属作品标记为下载失败，
+/// 下次sync时会重新下载
+#[derive(Debug, Parser)]
+pub struct VerifyCmd {
+    /// 只列出校验失败的文件，不移动文件也不修改清单
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+/// 调试用：保存页面html作为page_parse的测试数据
+///
+/// 保存后设置环境变量`UPDATE_GOLDEN=1`运行`cargo test`，生成该页面的golden文件，
+/// 并对照网页核对golden文件中的每一项
+#[derive(Debug, Parser)]
+pub struct RecordFixture {
+    /// 要保存的页面url，可以是主页、分类页或作品页
+    #[clap(short, long)]
+    pub url: String,
+
+    /// 测试数据的名称，保存为`<name>.html`，名称相同的测试数据会被覆盖
+    #[clap(short, long)]
+    pub name: String,
+
+    /// 测试数据目录，默认为源码中的`fixtures/pages`目录
+    #[clap(long)]
+    pub dir: Option<PathBuf>,
+}
+
 #[derive(Debug)]
 pub struct SimleOpts {
     pub splash_addr: String,
     pub proxy: Option<String>,
     pub save_dir: PathBuf,
+    pub fetcher: FetcherKind,
+    pub max_works: usize,
+    pub max_files: usize,
+    pub rps: Option<f64>,
+    pub bandwidth: Option<u64>,
+    pub retry: RetryPolicy,
 }
 
 pub fn args_init() -> (SimleOpts, Opts) {
@@ -154,6 +278,9 @@ pub fn args_init() -> (SimleOpts, Opts) {
     match &opts.cmds {
         Cmds::Parse(c) => valid_parse_cmd(c),
         Cmds::Download(d) => valid_download_cmd(d),
+        Cmds::Sync(s) => valid_sync_cmd(s),
+        Cmds::RecordFixture(_) => {}
+        Cmds::Verify(_) => {}
         Cmds::No => {}
     }
 
@@ -182,6 +309,39 @@ pub fn args_init() -> (SimleOpts, Opts) {
         )
     });
 
+    // 限流选项：先读选项，再读环境变量(.env文件中的)，最后设置默认
+    let max_works = opts
+        .max_works
+        .or_else(|| env_parse("MAX_WORKS", |x| x.parse().ok().filter(|&n| n > 0)))
+        .unwrap_or(10) as usize;
+    let max_files = opts
+        .max_files
+        .or_else(|| env_parse("MAX_FILES", |x| x.parse().ok().filter(|&n| n > 0)))
+        .unwrap_or(20) as usize;
+    let rps = opts.rps.or_else(|| env_parse("RPS", |x| x.parse().ok()));
+    if rps.is_some_and(|r| r <= 0.0 || !r.is_finite()) {
+        panic!("`--rps` 选项的参数必须大于0")
+    }
+    let bandwidth = opts
+        .bandwidth
+        .or_else(|| env_parse("BANDWIDTH", |x| parse_size(x).ok()));
+
+    let default_retry = RetryPolicy::default();
+    let retry = RetryPolicy {
+        attempts: opts
+            .retries
+            .or_else(|| env_parse("RETRIES", |x| x.parse().ok().filter(|&n| n > 0)))
+            .unwrap_or(default_retry.attempts),
+        base_delay: opts
+            .retry_delay
+            .or_else(|| env_parse("RETRY_DELAY", |x| x.parse().ok()))
+            .map_or(default_retry.base_delay, Duration::from_millis),
+        max_delay: opts
+            .retry_max_delay
+            .or_else(|| env_parse("RETRY_MAX_DELAY", |x| x.parse().ok()))
+            .map_or(default_retry.max_delay, Duration::from_millis),
+    };
+
     if opts.debug {
         std::env::set_var("RUST_LOG", "info,crab_test=debug");
     }
@@ -190,11 +350,27 @@ pub fn args_init() -> (SimleOpts, Opts) {
         splash_addr,
         proxy,
         save_dir,
+        fetcher: opts.fetcher,
+        max_works,
+        max_files,
+        rps,
+        bandwidth,
+        retry,
     };
 
     (simple_opts, opts)
 }
 
+/// 读取并解析环境变量，无法解析时忽略该环境变量
+fn env_parse<T>(key: &str, parse: impl FnOnce(&str) -> Option<T>) -> Option<T> {
+    let value = env::var(key).ok()?;
+    let res = parse(&value);
+    if res.is_none() {
+        eprintln!("忽略无效的环境变量 {}={}", key, value);
+    }
+    res
+}
+
 /// 检查 parse 子命令的选项
 fn valid_parse_cmd(cmd: &Parse) {
     let url_type = UrlType::parse(&cmd.url).expect(&format!("无效的url: {}", cmd.url));
@@ -221,6 +397,17 @@ fn valid_download_cmd(cmd: &Download) {
     // }
 }
 
+/// 检查 sync 子命令的选项
+fn valid_sync_cmd(cmd: &SyncCmd) {
+    match UrlType::parse(&cmd.url) {
+        Some(u) if u.is_fenlei() => {}
+        _ => panic!("`--url` 选项的参数必须是分类url: {}", cmd.url),
+    }
+    if cmd.max_pages == 0 {
+        panic!("`--max-pages` 选项的参数必须大于0")
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum UrlType {
     /// 给定url是主页，固定值"https://xchina.co"
@@ -326,3 +513,27 @@ impl FromStr for DownloadType {
         }
     }
 }
+
+/// parse 子命令的输出格式
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// 格式化的JSON
+    #[default]
+    Json,
+    /// 每行一条JSON记录
+    Jsonl,
+    /// 带表头的CSV
+    Csv,
+    /// 按列对齐的文本表格
+    Table,
+}
+
+/// 获取页面html的方式
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum FetcherKind {
+    /// 通过Splash服务渲染页面
+    #[default]
+    Splash,
+    /// 直接请求页面
+    Http,
+}
@@ -29,6 +29,35 @@ pub fn enable_log() {
     log_builder.init();
 }
 
+/// 解析字节数，可以带后缀K/M/G(1024进制，不区分大小写)，例如`512K`、`2M`、`1.5m`
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (num, unit) = match s.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&s[..i], c.to_ascii_uppercase()),
+        _ => (s, 'B'),
+    };
+    let unit = match unit {
+        'B' => 1u64,
+        'K' => 1 << 10,
+        'M' => 1 << 20,
+        'G' => 1 << 30,
+        _ => return Err(format!("无效的单位: {}", s)),
+    };
+    // 不足1字节的会截断为0
+    match num.trim().parse::<f64>() {
+        Ok(n) if n.is_finite() && n * unit as f64 >= 1.0 => Ok((n * unit as f64) as u64),
+        _ => Err(format!("无效的大小: {}", s)),
+    }
+}
+
+/// 解析每秒的次数，必须是大于0的有限小数，例如`0.5`
+pub fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(n) if n.is_finite() && n > 0.0 => Ok(n),
+        _ => Err(format!("无效的速率: {}", s)),
+    }
+}
+
 /// 解析范围字符串。规则(假设 given_num 参数给定值为15)：
 ///
 /// - 离散值: `1,3,5,7,9`，表示第1 3 5 7 9
@@ -119,7 +148,30 @@ pub fn parse_number_range(range_str: &str, given_num: i16) -> Vec<u16> {
 
 #[cfg(test)]
 mod test {
-    use super::parse_number_range;
+    use super::{parse_number_range, parse_rate, parse_size};
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("512K"), Ok(512 * 1024));
+        assert_eq!(parse_size("1.5m"), Ok(1536 * 1024));
+        assert_eq!(parse_size(" 2 G"), Ok(2 << 30));
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("0.5").is_err());
+        assert!(parse_size("0.0001K").is_err());
+        assert!(parse_size("inf").is_err());
+        assert!(parse_size("10T").is_err());
+        assert!(parse_size("M").is_err());
+    }
+
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!(parse_rate("0.5"), Ok(0.5));
+        assert_eq!(parse_rate(" 10 "), Ok(10.0));
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("-1").is_err());
+        assert!(parse_rate("NaN").is_err());
+    }
 
     #[test]
     fn test_parse_range_str() {
@@ -0,0 +1,332 @@
+//! parse子命令的输出：把解析结果按`--format`指定的格式写到标准输出或`--output`文件
+//!
+//! - json: 整个解析结果，map的key按字典序排列，便于对比不同时间的解析结果
+//! - jsonl/csv/table: 每条记录一行，记录为主页中的分类、分类页中的作品、作品中的文件或分页url
+//!
+
+use crate::{
+    content_types::{Content, ContentInfo, MainPageFenLei},
+    opt_parse::OutputFormat,
+};
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// 可以输出为一行jsonl/csv/table的记录
+pub trait Row: Serialize {
+    /// csv和table格式的表头
+    const HEADER: &'static [&'static str];
+
+    /// 与HEADER一一对应的各列的值
+    fn row(&self) -> Vec<String>;
+}
+
+/// 主页中的一个分类
+#[derive(Debug, Serialize)]
+pub struct FenLeiRow {
+    /// 所属的大类，例如`xiezhen`、`renti_sheying`
+    pub group: &'static str,
+    pub name: String,
+    pub url: String,
+    pub count: u16,
+}
+
+impl FenLeiRow {
+    /// 按大类、分类名称排序
+    pub fn from_main_page(fen_lei: &MainPageFenLei) -> Vec<Self> {
+        let mut rows = vec![];
+        for (group, map) in [
+            ("xiezhen", &fen_lei.xiezhen),
+            ("renti_sheying", &fen_lei.renti_sheying),
+        ] {
+            let mut names: Vec<_> = map.iter().collect();
+            names.sort_by(|a, b| a.0.cmp(b.0));
+            for (name, (url, count)) in names {
+                rows.push(Self {
+                    group,
+                    name: name.clone(),
+                    url: url.clone(),
+                    count: *count,
+                });
+            }
+        }
+        rows
+    }
+}
+
+impl Row for FenLeiRow {
+    const HEADER: &'static [&'static str] = &["group", "name", "url", "count"];
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.group.to_string(),
+            self.name.clone(),
+            self.url.clone(),
+            self.count.to_string(),
+        ]
+    }
+}
+
+/// 作品中的一个文件
+#[derive(Debug, Serialize)]
+pub struct FileRow {
+    pub page_url: String,
+    /// `img`或`video`
+    pub kind: &'static str,
+    pub url: String,
+    /// 视频的大小，例如`29M`，图片没有该值
+    pub filesize: Option<String>,
+}
+
+impl FileRow {
+    pub fn from_content(content: &Content) -> Vec<Self> {
+        let page_url = &content.info.page_url;
+        let imgs = content.img_urls.iter().map(|u| Self {
+            page_url: page_url.clone(),
+            kind: "img",
+            url: u.clone(),
+            filesize: None,
+        });
+        let videos = content.videos.iter().map(|v| Self {
+            page_url: page_url.clone(),
+            kind: "video",
+            url: v.url.clone(),
+            filesize: Some(v.filesize.clone()),
+        });
+        imgs.chain(videos).collect()
+    }
+}
+
+impl Row for FileRow {
+    const HEADER: &'static [&'static str] = &["page_url", "kind", "url", "filesize"];
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.page_url.clone(),
+            self.kind.to_string(),
+            self.url.clone(),
+            self.filesize.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+/// 分类的一个分页
+#[derive(Debug, Serialize)]
+pub struct PageRow {
+    pub url: String,
+    /// 是否是`--url`选项给定的页
+    pub current: bool,
+}
+
+impl Row for PageRow {
+    const HEADER: &'static [&'static str] = &["url", "current"];
+
+    fn row(&self) -> Vec<String> {
+        vec![self.url.clone(), self.current.to_string()]
+    }
+}
+
+impl Row for ContentInfo {
+    const HEADER: &'static [&'static str] = &[
+        "fen_lei",
+        "actor",
+        "title",
+        "pub_date",
+        "page_url",
+        "show_url",
+        "jpg_count",
+        "video_count",
+    ];
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.fen_lei.clone(),
+            self.actor.clone(),
+            self.title.clone(),
+            self.pub_date.clone(),
+            self.page_url.clone(),
+            self.show_url.clone(),
+            self.jpg_count.to_string(),
+            self.video_count.to_string(),
+        ]
+    }
+}
+
+/// 输出解析结果到`path`，`path`为None时输出到标准输出。
+///
+/// json格式输出`whole`，其它格式逐行输出`rows`
+pub fn output<S: Serialize, R: Row>(
+    format: OutputFormat,
+    path: Option<&Path>,
+    whole: &S,
+    rows: &[R],
+) -> io::Result<()> {
+    match path {
+        Some(p) => {
+            let mut w = BufWriter::new(File::create(p)?);
+            write_to(&mut w, format, whole, rows)?;
+            w.flush()
+        }
+        None => {
+            let mut w = io::stdout().lock();
+            write_to(&mut w, format, whole, rows)?;
+            w.flush()
+        }
+    }
+}
+
+pub fn write_to<W: Write, S: Serialize, R: Row>(
+    w: &mut W,
+    format: OutputFormat,
+    whole: &S,
+    rows: &[R],
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Json => {
+            // 先转为Value，HashMap的key会按字典序排列
+            let value = serde_json::to_value(whole)?;
+            serde_json::to_writer_pretty(&mut *w, &value)?;
+            writeln!(w)
+        }
+        OutputFormat::Jsonl => {
+            for r in rows {
+                serde_json::to_writer(&mut *w, r)?;
+                writeln!(w)?;
+            }
+            Ok(())
+        }
+        OutputFormat::Csv => {
+            writeln!(w, "{}", csv_line(R::HEADER.iter().copied()))?;
+            for r in rows {
+                writeln!(w, "{}", csv_line(r.row().iter().map(String::as_str)))?;
+            }
+            Ok(())
+        }
+        OutputFormat::Table => {
+            let rows: Vec<_> = rows.iter().map(Row::row).collect();
+            let mut widths: Vec<_> = R::HEADER.iter().map(|h| display_width(h)).collect();
+            for r in &rows {
+                for (w, cell) in widths.iter_mut().zip(r) {
+                    *w = (*w).max(display_width(cell));
+                }
+            }
+
+            let header: Vec<_> = R::HEADER.iter().map(|h| h.to_string()).collect();
+            let sep: Vec<_> = widths.iter().map(|&n| "-".repeat(n)).collect();
+            for r in [&header, &sep].into_iter().chain(&rows) {
+                writeln!(w, "{}", table_line(r, &widths))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// 含有`,`、`"`或换行的字段用双引号括起来，字段中的`"`写为`""`
+fn csv_line<'a>(fields: impl Iterator<Item = &'a str>) -> String {
+    fields
+        .map(|f| {
+            if f.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", f.replace('"', "\"\""))
+            } else {
+                f.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn table_line(cells: &[String], widths: &[usize]) -> String {
+    let mut line = String::new();
+    for (i, (cell, w)) in cells.iter().zip(widths).enumerate() {
+        if i > 0 {
+            line.push_str("  ");
+        }
+        line.push_str(cell);
+        // 最后一列不补空格
+        if i + 1 < cells.len() {
+            line.push_str(&" ".repeat(w - display_width(cell)));
+        }
+    }
+    line
+}
+
+/// 字符串在终端中的显示宽度，中日韩文字及全角符号按2计算
+fn display_width(s: &str) -> usize {
+    s.chars()
+        .map(|c| match c as u32 {
+            0x1100..=0x115F
+            | 0x2E80..=0xA4CF
+            | 0xAC00..=0xD7A3
+            | 0xF900..=0xFAFF
+            | 0xFE30..=0xFE4F
+            | 0xFF00..=0xFF60
+            | 0xFFE0..=0xFFE6
+            | 0x20000..=0x3FFFD => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rows() -> Vec<PageRow> {
+        vec![
+            PageRow {
+                url: "https://xchina.co/photos/series-5f1476781eab4/1.html".to_string(),
+                current: true,
+            },
+            PageRow {
+                url: "https://xchina.co/photos/series-5f1476781eab4/2.html".to_string(),
+                current: false,
+            },
+        ]
+    }
+
+    fn write(format: OutputFormat) -> String {
+        let rows = rows();
+        let mut buf = vec![];
+        write_to(&mut buf, format, &rows, &rows).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(
+            write(OutputFormat::Jsonl),
+            "{\"url\":\"https://xchina.co/photos/series-5f1476781eab4/1.html\",\"current\":true}\n\
+             {\"url\":\"https://xchina.co/photos/series-5f1476781eab4/2.html\",\"current\":false}\n"
+        );
+        assert_eq!(
+            write(OutputFormat::Csv),
+            "url,current\n\
+             https://xchina.co/photos/series-5f1476781eab4/1.html,true\n\
+             https://xchina.co/photos/series-5f1476781eab4/2.html,false\n"
+        );
+        let json: serde_json::Value = serde_json::from_str(&write(OutputFormat::Json)).unwrap();
+        assert_eq!(json[1]["current"], false);
+    }
+
+    #[test]
+    fn csv_quote() {
+        let line = csv_line(["秀人网", "a,b", "say \"hi\""].into_iter());
+        assert_eq!(line, "秀人网,\"a,b\",\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn table_align() {
+        let widths = [6, 3];
+        assert_eq!(
+            table_line(&["秀人网".to_string(), "1".to_string()], &widths),
+            "秀人网  1"
+        );
+        assert_eq!(
+            table_line(&["Pure".to_string(), "79".to_string()], &widths),
+            "Pure    79"
+        );
+    }
+}
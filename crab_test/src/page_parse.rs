@@ -1,6 +1,7 @@
 use crate::{
     content_types::{Content, ContentInfo, MainPageFenLei, Video},
-    splash_client::SplashClient,
+    fetcher::PageFetcher,
+    retry::FetchError,
 };
 use scraper::{ElementRef, Html, Selector};
 use std::{collections::HashMap, sync::Arc};
@@ -8,18 +9,19 @@ use tokio::sync::{RwLock, Semaphore};
 use tracing::{debug, error};
 use url::Url;
 
+/// 页面解析，页面html通过给定的PageFetcher获取
 #[derive(Clone)]
-pub struct PageParser {
-    splash_client: SplashClient,
+pub struct PageParser<F> {
+    fetcher: F,
 }
 
-impl PageParser {
-    pub fn new(splash_client: SplashClient) -> Self {
-        Self { splash_client }
+impl<F: PageFetcher> PageParser<F> {
+    pub fn new(fetcher: F) -> Self {
+        Self { fetcher }
     }
 
     pub async fn get_html(&self, url: &str) -> Option<String> {
-        match self.splash_client.get_html_retry(url).await {
+        match self.fetcher.fetch_html(url).await {
             Ok(s) => Some(s),
             Err(e) => {
                 error!("请求({})失败, 错误信息: {}", url, e);
@@ -211,13 +213,23 @@ impl PageParser {
 
     /// 解析每个分类系列的页面，获取分类的所有作品列表(即该页中的作品列表)，以及每个作品对应的所有url页面
     /// 比如，解析某个photo汇总页`/photos/series-5f1476781eab4.html`中的所有作品列表信息
+    ///
+    /// 请求失败时返回空列表，需要区分请求失败和超出最大页码时用`try_parse_serie_page()`
     pub async fn parse_serie_page(&self, url: &str) -> Vec<ContentInfo> {
+        match self.try_parse_serie_page(url).await {
+            Ok(contents) => contents,
+            Err(e) => {
+                error!("请求({})失败, 错误信息: {}", url, e);
+                vec![]
+            }
+        }
+    }
+
+    /// 同`parse_serie_page()`，但请求失败时返回错误。超出最大页码的分页返回空列表
+    pub async fn try_parse_serie_page(&self, url: &str) -> Result<Vec<ContentInfo>, FetchError> {
         let mut contents = Vec::new();
 
-        let html_str = match self.get_html(url).await {
-            Some(s) => s,
-            None => return contents,
-        };
+        let html_str = self.fetcher.fetch_html(url).await?;
 
         let doc = Html::parse_document(&html_str);
 
@@ -256,7 +268,7 @@ impl PageParser {
             contents.push(content.unwrap());
         }
 
-        contents
+        Ok(contents)
     }
 
     /// 解析单个内容页面，获取该页面中所有图片和视频的url。该方法已经将获取到的图片url存入content中
@@ -330,7 +342,7 @@ impl PageParser {
     }
 }
 
-impl PageParser {
+impl<F: PageFetcher> PageParser<F> {
     /// 给定一个分类url，解析分页，并获取所有分页中的作品信息。
     ///
     /// 注意，有些分类中，有非常多的分页，几百页甚至接近上千页，因此并发多任务解析，并且通过通道来发送已经解析的页面
@@ -1007,3 +1019,106 @@ fn get_parent_text(elem: ElementRef) -> Vec<String> {
         .filter(|x| !x.is_empty())
         .collect::<Vec<String>>()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture::{load_index, FixtureKind, FIXTURE_DIR};
+    use async_trait::async_trait;
+    use std::path::Path;
+
+    /// 从内存中返回页面html的PageFetcher，不在其中的页面返回503
+    #[derive(Clone)]
+    struct StaticFetcher(Arc<HashMap<String, String>>);
+
+    #[async_trait]
+    impl PageFetcher for StaticFetcher {
+        async fn fetch_html(&self, url: &str) -> Result<String, FetchError> {
+            self.0.get(url).cloned().ok_or(FetchError::Status {
+                status: reqwest::StatusCode::SERVICE_UNAVAILABLE,
+                retry_after: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_fetcher() {
+        let url = "https://xchina.co/photos/series-5f1476781eab4.html";
+        let pages = HashMap::from([(url.to_string(), "<html><body></body></html>".to_string())]);
+        let parser = PageParser::new(StaticFetcher(Arc::new(pages)));
+
+        assert_eq!(
+            parser.get_html(url).await.as_deref(),
+            Some("<html><body></body></html>")
+        );
+        // 没有分页时只有当前页
+        assert_eq!(
+            parser.parse_pages_urls(url).await,
+            vec![(url.to_string(), true)]
+        );
+        assert!(parser.parse_serie_page(url).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn serie_page_failure() {
+        let url = "https://xchina.co/photos/series-5f1476781eab4/9.html";
+        let pages = HashMap::from([(url.to_string(), "<html><body></body></html>".to_string())]);
+        let parser = PageParser::new(StaticFetcher(Arc::new(pages)));
+
+        // 超出最大页码的分页没有作品，但不是错误
+        assert!(parser.try_parse_serie_page(url).await.unwrap().is_empty());
+        let failed = "https://xchina.co/photos/series-5f1476781eab4/2.html";
+        assert!(parser.try_parse_serie_page(failed).await.is_err());
+        assert!(parser.parse_serie_page(failed).await.is_empty());
+    }
+
+    /// 解析测试数据目录中的每个页面，与golden文件比较
+    #[tokio::test]
+    async fn fixtures() {
+        let dir = Path::new(FIXTURE_DIR);
+        let fixtures = load_index(dir).unwrap();
+        // 测试数据须是用record-fixture保存的真实页面，还没有保存时跳过
+        if fixtures.is_empty() {
+            eprintln!("{} 中没有测试数据，跳过", dir.display());
+            return;
+        }
+
+        let pages = fixtures
+            .iter()
+            .map(|f| {
+                let html = std::fs::read_to_string(f.html_path(dir)).unwrap();
+                (f.url.clone(), html)
+            })
+            .collect();
+        let parser = PageParser::new(StaticFetcher(Arc::new(pages)));
+        let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+
+        for f in &fixtures {
+            let got = match f.kind {
+                FixtureKind::Main => serde_json::to_value(parser.parse_main_page(&f.url).await),
+                FixtureKind::Series => serde_json::to_value(parser.parse_serie_page(&f.url).await),
+                FixtureKind::Work => {
+                    serde_json::to_value(parser.content_urls_one_page(&f.url).await)
+                }
+            }
+            .unwrap();
+
+            let golden = f.golden_path(dir);
+            if update {
+                let mut s = serde_json::to_string_pretty(&got).unwrap();
+                s.push('\n');
+                std::fs::write(&golden, s).unwrap();
+                continue;
+            }
+            let want: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(&golden).unwrap()).unwrap();
+            assert_eq!(
+                got,
+                want,
+                "{} 的解析结果与 {} 不一致",
+                f.name,
+                golden.display()
+            );
+        }
+    }
+}
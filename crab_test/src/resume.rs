@@ -0,0 +1,347 @@
+//! 可断点续传的下载：数据边下载边写入`<文件名>.part`，连接中断后再次下载时，
+//! 通过`Range`请求从`.part`文件已有的长度处继续，下载完整(与`Content-Length`
+//! 一致)后才重命名为最终的文件名。因此，最终文件存在即表示已完整下载
+//!
+
+use crate::{
+    limiter::limiter,
+    retry::{retry_after, retry_http, retry_status, Retry, Retryable},
+};
+use reqwest::{header, StatusCode};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+use tracing::debug;
+
+#[derive(Debug)]
+pub enum DownloadError {
+    Http(reqwest::Error),
+    Io(std::io::Error),
+    /// 服务端返回了非预期的状态码
+    Status {
+        status: StatusCode,
+        retry_after: Option<Duration>,
+    },
+    /// 服务端返回的`Content-Range`与请求的范围不一致
+    BadRange(String),
+    /// 连接结束时，收到的数据长度与`Content-Length`不一致，已收到的数据保留在`.part`文件中
+    Incomplete {
+        expected: u64,
+        got: u64,
+    },
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DownloadError::Http(e) => write!(f, "{}", e),
+            DownloadError::Io(e) => write!(f, "{}", e),
+            DownloadError::Status { status, .. } => write!(f, "响应状态码: {}", status),
+            DownloadError::BadRange(r) => write!(f, "错误的Content-Range: {}", r),
+            DownloadError::Incomplete { expected, got } => {
+                write!(f, "数据不完整, 应为{}字节, 只收到{}字节", expected, got)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {}
+
+impl Retryable for DownloadError {
+    fn retry(&self) -> Retry {
+        match self {
+            DownloadError::Http(e) => retry_http(e),
+            // 本地文件读写错误，重试也无法恢复
+            DownloadError::Io(_) => Retry::No,
+            DownloadError::Status {
+                status,
+                retry_after,
+            } => retry_status(*status, *retry_after),
+            // 重试时从.part文件的长度处继续，或者从头下载
+            DownloadError::BadRange(_) | DownloadError::Incomplete { .. } => Retry::Backoff,
+        }
+    }
+}
+
+impl From<reqwest::Error> for DownloadError {
+    fn from(e: reqwest::Error) -> Self {
+        DownloadError::Http(e)
+    }
+}
+
+impl From<std::io::Error> for DownloadError {
+    fn from(e: std::io::Error) -> Self {
+        DownloadError::Io(e)
+    }
+}
+
+/// 下载过程中使用的临时文件，例如`0001.jpg`对应`0001.jpg.part`
+pub fn part_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+/// 解析`Content-Range`，例如`bytes 100-199/200`返回`(Some(100), Some(200))`，
+/// `bytes */200`返回`(None, Some(200))`，总长度未知(`*`)时为None
+fn parse_content_range(value: &str) -> Option<(Option<u64>, Option<u64>)> {
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let total = match total {
+        "*" => None,
+        t => Some(t.parse().ok()?),
+    };
+    let start = match range {
+        "*" => None,
+        r => Some(r.split_once('-')?.0.parse().ok()?),
+    };
+    Some((start, total))
+}
+
+fn content_range(resp: &reqwest::Response) -> Option<&str> {
+    resp.headers().get(header::CONTENT_RANGE)?.to_str().ok()
+}
+
+/// 下载`url`并保存到`path`，返回文件的总长度。
+///
+/// 如果存在`.part`文件，则从其末尾继续下载。出错时已收到的数据保留在`.part`文件中，
+/// 再次调用即可续传
+pub async fn download_to(
+    conn: &reqwest::Client,
+    url: &str,
+    path: &Path,
+) -> Result<u64, DownloadError> {
+    let part = part_path(path);
+    let offset = match tokio::fs::metadata(&part).await {
+        Ok(m) => m.len(),
+        Err(_) => 0,
+    };
+
+    limiter().request(url).await;
+    let mut req = conn.get(url);
+    if offset > 0 {
+        debug!("从第{}字节处继续下载 {}", offset, url);
+        req = req.header(header::RANGE, format!("bytes={}-", offset));
+    }
+    let mut resp = req.send().await?;
+
+    // 从哪个位置开始写，以及文件的总长度(未知时为None)
+    let (start, total) = match resp.status() {
+        StatusCode::PARTIAL_CONTENT => {
+            let value = content_range(&resp).unwrap_or_default().to_string();
+            match parse_content_range(&value) {
+                Some((Some(start), total)) if start == offset => (start, total),
+                _ => return Err(DownloadError::BadRange(value)),
+            }
+        }
+        // 不支持Range请求的服务端会返回整个文件，从头开始写
+        StatusCode::OK => (0, resp.content_length()),
+        // `.part`文件已经是完整的文件(上次下载完后未能重命名)
+        StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => {
+            let value = content_range(&resp).unwrap_or_default();
+            match parse_content_range(value) {
+                Some((None, Some(total))) if total == offset => {
+                    tokio::fs::rename(&part, path).await?;
+                    return Ok(total);
+                }
+                // 服务端的文件已变化，丢弃`.part`文件，下次从头下载
+                _ => {
+                    tokio::fs::remove_file(&part).await?;
+                    return Err(DownloadError::BadRange(value.to_string()));
+                }
+            }
+        }
+        status => {
+            return Err(DownloadError::Status {
+                status,
+                retry_after: retry_after(&resp),
+            })
+        }
+    };
+    let expected = match (total, resp.content_length()) {
+        (Some(t), _) => Some(t),
+        (None, Some(len)) => Some(start + len),
+        (None, None) => None,
+    };
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(start > 0)
+        .truncate(start == 0)
+        .open(&part)
+        .await?;
+    let mut got = start;
+    let res = loop {
+        match resp.chunk().await {
+            Ok(Some(bs)) => {
+                if let Err(e) = file.write_all(&bs).await {
+                    break Err(e.into());
+                }
+                got += bs.len() as u64;
+                limiter().received(bs.len()).await;
+            }
+            Ok(None) => break Ok(()),
+            Err(e) => break Err(DownloadError::from(e)),
+        }
+    };
+    // 无论是否出错，都要把已收到的数据写入文件，以便续传
+    file.flush().await?;
+    drop(file);
+    res?;
+
+    if let Some(expected) = expected {
+        if got != expected {
+            return Err(DownloadError::Incomplete { expected, got });
+        }
+    }
+
+    tokio::fs::rename(&part, path).await?;
+    Ok(got)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// 本地的HTTP服务端，提供`body`的下载，支持`Range: bytes=N-`。
+    /// 前`cuts`次请求在发送一半的数据后断开连接，`range`为false时忽略Range请求
+    async fn serve(body: Vec<u8>, cuts: usize, range: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut served = 0;
+            loop {
+                let (mut sock, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0; 4096];
+                let n = sock.read(&mut buf).await.unwrap();
+                let req = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                let start = req
+                    .lines()
+                    .find_map(|l| l.strip_prefix("range: bytes="))
+                    .and_then(|r| r.trim_end_matches('-').parse::<usize>().ok())
+                    .filter(|_| range);
+
+                let (head, data) = match start {
+                    Some(s) if s >= body.len() => (
+                        format!(
+                            "HTTP/1.1 416 Range Not Satisfiable\r\ncontent-range: bytes */{}\r\ncontent-length: 0\r\n\r\n",
+                            body.len()
+                        ),
+                        &body[..0],
+                    ),
+                    Some(s) => (
+                        format!(
+                            "HTTP/1.1 206 Partial Content\r\ncontent-range: bytes {}-{}/{}\r\ncontent-length: {}\r\n\r\n",
+                            s,
+                            body.len() - 1,
+                            body.len(),
+                            body.len() - s
+                        ),
+                        &body[s..],
+                    ),
+                    None => (
+                        format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n", body.len()),
+                        &body[..],
+                    ),
+                };
+                sock.write_all(head.as_bytes()).await.unwrap();
+                served += 1;
+                let data = if served <= cuts {
+                    &data[..data.len() / 2]
+                } else {
+                    data
+                };
+                let _ = sock.write_all(data).await;
+                let _ = sock.shutdown().await;
+            }
+        });
+        format!("http://{}/0001.jpg", addr)
+    }
+
+    fn body() -> Vec<u8> {
+        (0..100_000u32).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("crab_test-resume-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(part_path(&path));
+        path
+    }
+
+    #[test]
+    fn content_range() {
+        assert_eq!(
+            parse_content_range("bytes 100-199/200"),
+            Some((Some(100), Some(200)))
+        );
+        assert_eq!(parse_content_range("bytes 0-9/*"), Some((Some(0), None)));
+        assert_eq!(parse_content_range("bytes */200"), Some((None, Some(200))));
+        assert_eq!(parse_content_range("200"), None);
+    }
+
+    #[tokio::test]
+    async fn resume_after_cut() {
+        let body = body();
+        let url = serve(body.clone(), 2, true).await;
+        let path = temp_path("resume_after_cut.jpg");
+        let conn = reqwest::Client::new();
+
+        // 第一次只收到一半，数据保留在.part文件中，最终文件不存在
+        let err = download_to(&conn, &url, &path).await.unwrap_err();
+        assert!(matches!(
+            err,
+            DownloadError::Http(_) | DownloadError::Incomplete { .. }
+        ));
+        assert!(!path.exists());
+        let half = std::fs::read(part_path(&path)).unwrap();
+        assert_eq!(half, body[..body.len() / 2]);
+
+        // 第二次从一半处继续，又被断开
+        download_to(&conn, &url, &path).await.unwrap_err();
+        assert!(!path.exists());
+
+        // 第三次完成
+        let len = download_to(&conn, &url, &path).await.unwrap();
+        assert_eq!(len, body.len() as u64);
+        assert_eq!(std::fs::read(&path).unwrap(), body);
+        assert!(!part_path(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn server_ignores_range() {
+        let body = body();
+        let url = serve(body.clone(), 1, false).await;
+        let path = temp_path("server_ignores_range.jpg");
+        let conn = reqwest::Client::new();
+
+        download_to(&conn, &url, &path).await.unwrap_err();
+        // 服务端返回整个文件，.part文件从头重写
+        download_to(&conn, &url, &path).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn complete_part_file() {
+        let body = body();
+        let url = serve(body.clone(), 0, true).await;
+        let path = temp_path("complete_part_file.jpg");
+        std::fs::write(part_path(&path), &body).unwrap();
+
+        let len = download_to(&reqwest::Client::new(), &url, &path)
+            .await
+            .unwrap();
+        assert_eq!(len, body.len() as u64);
+        assert_eq!(std::fs::read(&path).unwrap(), body);
+        assert!(!part_path(&path).exists());
+    }
+}
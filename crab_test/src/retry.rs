@@ -0,0 +1,284 @@
+//! 请求失败后的重试：指数退避并加入随机抖动，只重试可能恢复的错误
+//!
+//! - 重试：超时、连接失败、连接中断、5xx、408、429(有`Retry-After`时按其等待)、Splash渲染超时
+//! - 不重试：404等其它4xx、本地文件读写错误
+//!
+
+use crate::RETRY;
+use rand::Rng;
+use reqwest::{header, StatusCode};
+use std::{fmt, future::Future, time::SystemTime};
+use tokio::time::Duration;
+use tracing::warn;
+
+/// 出错后是否重试
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retry {
+    /// 不重试，直接返回错误
+    No,
+    /// 按RetryPolicy的退避时间等待后重试
+    Backoff,
+    /// 等待指定时间后重试(来自`Retry-After`)
+    After(Duration),
+}
+
+pub trait Retryable {
+    fn retry(&self) -> Retry;
+}
+
+/// 请求页面或文件时的错误
+#[derive(Debug)]
+pub enum FetchError {
+    Http(reqwest::Error),
+    /// 服务端返回了非2xx的状态码
+    Status {
+        status: StatusCode,
+        retry_after: Option<Duration>,
+    },
+    /// Splash渲染页面超时，例如：
+    /// {"error": 504, "type": "GlobalTimeoutError", "description": "Timeout exceeded rendering page", ...}
+    SplashTimeout,
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Http(e) => write!(f, "{}", e),
+            FetchError::Status { status, .. } => write!(f, "响应状态码: {}", status),
+            FetchError::SplashTimeout => write!(f, "Splash渲染页面超时"),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(e: reqwest::Error) -> Self {
+        FetchError::Http(e)
+    }
+}
+
+impl Retryable for FetchError {
+    fn retry(&self) -> Retry {
+        match self {
+            FetchError::Http(e) => retry_http(e),
+            FetchError::Status {
+                status,
+                retry_after,
+            } => retry_status(*status, *retry_after),
+            FetchError::SplashTimeout => Retry::Backoff,
+        }
+    }
+}
+
+/// 请求未得到响应，或者接收数据时出错
+pub fn retry_http(e: &reqwest::Error) -> Retry {
+    if let Some(s) = e.status() {
+        return retry_status(s, None);
+    }
+    if e.is_timeout() || e.is_connect() || e.is_body() || (e.is_request() && !e.is_builder()) {
+        Retry::Backoff
+    } else {
+        Retry::No
+    }
+}
+
+pub fn retry_status(status: StatusCode, retry_after: Option<Duration>) -> Retry {
+    match status {
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => {
+            retry_after.map_or(Retry::Backoff, Retry::After)
+        }
+        StatusCode::REQUEST_TIMEOUT => Retry::Backoff,
+        s if s.is_server_error() => Retry::Backoff,
+        _ => Retry::No,
+    }
+}
+
+/// 响应头中的`Retry-After`，可以是秒数或HTTP日期
+pub fn retry_after(resp: &reqwest::Response) -> Option<Duration> {
+    let value = resp
+        .headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    match value.parse::<u64>() {
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => {
+            let at = httpdate::parse_http_date(value).ok()?;
+            // 已经过去的时间视为0
+            Some(at.duration_since(SystemTime::now()).unwrap_or_default())
+        }
+    }
+}
+
+/// 非2xx响应转换为FetchError::Status
+pub fn check_status(resp: reqwest::Response) -> Result<reqwest::Response, FetchError> {
+    let status = resp.status();
+    if status.is_success() {
+        Ok(resp)
+    } else {
+        Err(FetchError::Status {
+            status,
+            retry_after: retry_after(&resp),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// 最多尝试的次数(包括第一次)
+    pub attempts: u32,
+    /// 第一次重试前的等待时间，之后每次翻倍
+    pub base_delay: Duration,
+    /// 退避时间的上限，不限制`Retry-After`
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// 最多3次，等待0.5秒、1秒
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 第n次失败后的等待时间：`base_delay * 2^(n-1)`，不超过max_delay，再随机取其50%~100%
+    pub fn backoff(&self, n: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(n.saturating_sub(1)))
+            .min(self.max_delay);
+        delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+
+    /// 执行f，出错时按错误类型决定是否重试，返回最后一次的结果
+    pub async fn run<T, E, F, Fut>(&self, url: &str, mut f: F) -> Result<T, E>
+    where
+        E: Retryable + fmt::Display,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut n = 1;
+        loop {
+            let e = match f().await {
+                Ok(v) => return Ok(v),
+                Err(e) => e,
+            };
+            let wait = match e.retry() {
+                _ if n >= self.attempts => return Err(e),
+                Retry::No => return Err(e),
+                Retry::Backoff => self.backoff(n),
+                Retry::After(d) => d,
+            };
+            warn!(
+                "请求({})失败, {:.1}秒后第{}次重试: {}",
+                url,
+                wait.as_secs_f64(),
+                n,
+                e
+            );
+            tokio::time::sleep(wait).await;
+            n += 1;
+        }
+    }
+}
+
+/// 全局的RetryPolicy，未设置时使用默认值
+pub fn policy() -> &'static RetryPolicy {
+    RETRY.get_or_init(RetryPolicy::default)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tokio::time::Instant;
+
+    fn status(code: u16) -> FetchError {
+        FetchError::Status {
+            status: StatusCode::from_u16(code).unwrap(),
+            retry_after: None,
+        }
+    }
+
+    #[test]
+    fn classify() {
+        assert_eq!(status(404).retry(), Retry::No);
+        assert_eq!(status(403).retry(), Retry::No);
+        assert_eq!(status(500).retry(), Retry::Backoff);
+        assert_eq!(status(504).retry(), Retry::Backoff);
+        assert_eq!(status(429).retry(), Retry::Backoff);
+        let e = FetchError::Status {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            retry_after: Some(Duration::from_secs(7)),
+        };
+        assert_eq!(e.retry(), Retry::After(Duration::from_secs(7)));
+        assert_eq!(FetchError::SplashTimeout.retry(), Retry::Backoff);
+    }
+
+    #[test]
+    fn backoff() {
+        let p = RetryPolicy {
+            attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+        };
+        for (n, max) in [(1, 100), (2, 200), (3, 400), (4, 800), (5, 1000), (9, 1000)] {
+            let d = p.backoff(n);
+            let max = Duration::from_millis(max);
+            assert!(d >= max / 2 && d <= max, "n: {}, delay: {:?}", n, d);
+        }
+    }
+
+    async fn count_attempts(
+        p: &RetryPolicy,
+        errors: Vec<FetchError>,
+    ) -> (u32, Result<(), FetchError>) {
+        let count = AtomicU32::new(0);
+        let errors = std::sync::Mutex::new(errors);
+        let res = p
+            .run("https://img.xchina.biz/photos/1/0001.jpg", || async {
+                count.fetch_add(1, Ordering::SeqCst);
+                match errors.lock().unwrap().pop() {
+                    Some(e) => Err(e),
+                    None => Ok(()),
+                }
+            })
+            .await;
+        (count.load(Ordering::SeqCst), res)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn run() {
+        let p = RetryPolicy::default();
+
+        // 404不重试
+        let (n, res) = count_attempts(&p, vec![status(404)]).await;
+        assert_eq!((n, res.is_err()), (1, true));
+
+        // 5xx重试后成功
+        let (n, res) = count_attempts(&p, vec![status(502), status(500)]).await;
+        assert_eq!((n, res.is_ok()), (3, true));
+
+        // 超过尝试次数，返回最后一次的错误
+        let (n, res) =
+            count_attempts(&p, vec![status(404), status(503), status(500), status(500)]).await;
+        assert_eq!(n, 3);
+        assert!(matches!(res, Err(FetchError::Status { status, .. }) if status == 503));
+
+        // 按Retry-After等待
+        let start = Instant::now();
+        let e = FetchError::Status {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            retry_after: Some(Duration::from_secs(20)),
+        };
+        let (n, res) = count_attempts(&p, vec![e]).await;
+        assert_eq!((n, res.is_ok()), (2, true));
+        assert!(start.elapsed() >= Duration::from_secs(20));
+    }
+}
@@ -1,7 +1,12 @@
 //! 向Splash发送请求HTML页面
 //!
 
-use crate::{header::xchina_headers_map, SPLASH_ADDR};
+use crate::{
+    header::xchina_headers_map,
+    limiter::limiter,
+    retry::{policy, retry_after, FetchError},
+    SPLASH_ADDR,
+};
 use serde::Serialize;
 use std::{collections::HashMap, sync::Arc};
 use tracing::{debug, instrument};
@@ -61,33 +66,37 @@ impl SplashClient {
         }
     }
 
-    /// 向Splash发送获取html的请求，会重试最多三次
-    pub async fn get_html_retry(&self, url: &str) -> Result<String, reqwest::Error> {
-        for _ in 1..3 {
-            if let Ok(resp) = self.get_html(url).await {
-                // 有可能请求正确，但是返回超时渲染消息 
-                // {"error": 504, "type": "GlobalTimeoutError", "description": "Timeout exceeded rendering page", "info": {"remaining": -0.001005, "timeout": 30}}
-                if !resp.contains(r##"{"error": 504"##) {
-                    return Ok(resp);
-                }
-            }
-
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-        }
-
-        self.get_html(url).await
+    /// 向Splash发送获取html的请求，按RetryPolicy重试
+    pub async fn get_html_retry(&self, url: &str) -> Result<String, FetchError> {
+        policy().run(url, || self.get_html(url)).await
     }
 
     #[instrument(skip(self))]
-    pub async fn get_html(&self, url: &str) -> Result<String, reqwest::Error> {
+    pub async fn get_html(&self, url: &str) -> Result<String, FetchError> {
         // 构建splash要代理请求的地址
         let req_url = format!("{}?url={}", self.splash_url, url);
         debug!(
             "send request: {}, post_data: {:?}",
             req_url, self.splash_data
         );
+        limiter().request(url).await;
         let req = self.conn.post(req_url).json(&*self.splash_data);
-        let res = req.send().await?.text().await?;
+        let resp = req.send().await?;
+        let status = resp.status();
+        let retry_after = retry_after(&resp);
+        let res = resp.text().await?;
+
+        // 渲染超时时返回的消息，有可能状态码是200
+        // {"error": 504, "type": "GlobalTimeoutError", "description": "Timeout exceeded rendering page", "info": {"remaining": -0.001005, "timeout": 30}}
+        if res.contains(r##"{"error": 504"##) {
+            return Err(FetchError::SplashTimeout);
+        }
+        if !status.is_success() {
+            return Err(FetchError::Status {
+                status,
+                retry_after,
+            });
+        }
 
         Ok(res)
     }
@@ -95,6 +104,7 @@ impl SplashClient {
 
 impl SplashClient {
     pub async fn get_html_simple(&self, url: &str) -> Result<String, reqwest::Error> {
+        limiter().request(url).await;
         let url = Self::make_simpl_splash_url(url);
         debug!("send_req: {}", url);
         let res = self.conn.get(url).send().await?.text().await?;
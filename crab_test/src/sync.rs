@@ -0,0 +1,218 @@
+//! 增量同步分类页：只下载上次同步之后发布的作品，同时重试清单中下载失败的作品
+//!
+
+use crate::{
+    content_client::XchaClient,
+    content_types::ContentInfo,
+    manifest::{now, Status, SyncEntry, WorkEntry},
+};
+use std::{collections::HashSet, fmt};
+use tracing::{error, info};
+
+/// 一次sync的结果
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    /// 上次同步到的发布日期，首次同步时为None
+    pub since: Option<String>,
+    /// 本次同步到的发布日期。有分页请求失败时不会记录到清单中
+    pub latest: Option<String>,
+    /// 请求失败的分页，其中的作品没有下载
+    pub failed_pages: Vec<String>,
+    /// 新发布的作品
+    pub new_works: Vec<WorkEntry>,
+    /// 重试的之前下载失败的作品
+    pub retried: Vec<WorkEntry>,
+}
+
+impl XchaClient {
+    /// 依次解析分类的各分页(page_urls须从第1页开始)，直到遇到上次同步之前发布的作品，
+    /// 下载其中新发布的、尚未完整下载的作品。retry_failed为true时，同时重试清单中下载失败的作品
+    pub async fn sync(&self, page_urls: Vec<String>, retry_failed: bool) -> SyncReport {
+        let manifest = match &self.manifest {
+            Some(m) => m.clone(),
+            None => {
+                error!("没有可用的下载清单，无法同步");
+                return SyncReport::default();
+            }
+        };
+        let key = page_urls.first().cloned().unwrap_or_default();
+        let since = manifest.last_sync(&key).map(|s| s.latest_pub_date);
+        info!("同步 {}, 上次同步到: {:?}", key, since);
+
+        let mut latest = since.clone();
+        let mut new_infos: Vec<ContentInfo> = vec![];
+        let mut failed_pages = vec![];
+        for url in &page_urls {
+            let infos = match self.page_parser.try_parse_serie_page(url).await {
+                // 超出最大页码
+                Ok(infos) if infos.is_empty() => break,
+                Ok(infos) => infos,
+                // 继续请求后面的分页，但本次不更新同步进度，下次sync会重新请求这一页
+                Err(e) => {
+                    error!("请求分页({})失败, 错误信息: {}", url, e);
+                    failed_pages.push(url.clone());
+                    continue;
+                }
+            };
+
+            let mut reached_old = false;
+            for info in infos {
+                if latest.as_ref().is_none_or(|l| info.pub_date > *l) {
+                    latest = Some(info.pub_date.clone());
+                }
+                // 同一天发布的作品可能上次没有同步到，因此只跳过更早的
+                if since.as_ref().is_some_and(|s| info.pub_date < *s) {
+                    reached_old = true;
+                    continue;
+                }
+                let done = manifest
+                    .work(&info.page_url)
+                    .is_some_and(|w| w.status == Status::Done);
+                if !done {
+                    new_infos.push(info);
+                }
+            }
+            // 分页按发布时间从新到旧排列，后面的分页都是已同步过的
+            if reached_old {
+                break;
+            }
+        }
+
+        let new_urls: HashSet<String> = new_infos.iter().map(|i| i.page_url.clone()).collect();
+        let retry_infos: Vec<ContentInfo> = if retry_failed {
+            manifest
+                .failed_works()
+                .into_iter()
+                .map(|w| w.info)
+                .filter(|i| !new_urls.contains(&i.page_url))
+                .collect()
+        } else {
+            vec![]
+        };
+        info!(
+            "新作品: {}个, 需重试的作品: {}个",
+            new_infos.len(),
+            retry_infos.len()
+        );
+
+        let mut infos = new_infos;
+        infos.extend(retry_infos);
+        let (new_works, retried) = self
+            .download_multi_content_infos(infos)
+            .await
+            .into_iter()
+            .partition(|w| new_urls.contains(&w.info.page_url));
+
+        if !failed_pages.is_empty() {
+            error!("{}个分页请求失败，不更新同步进度", failed_pages.len());
+        } else if let Some(latest) = &latest {
+            let entry = SyncEntry {
+                url: key,
+                latest_pub_date: latest.clone(),
+                time: now(),
+            };
+            if let Err(e) = manifest.record_sync(entry) {
+                error!("写入下载清单失败: {}", e);
+            }
+        }
+
+        SyncReport {
+            since,
+            latest,
+            failed_pages,
+            new_works,
+            retried,
+        }
+    }
+}
+
+/// 一组作品中下载成功的个数，以及文件数和总字节数
+fn totals(works: &[WorkEntry]) -> (usize, usize, u64) {
+    let done = works.iter().filter(|w| w.status == Status::Done).count();
+    let files = works.iter().map(|w| w.files.len()).sum();
+    let bytes = works
+        .iter()
+        .flat_map(|w| &w.files)
+        .filter_map(|f| f.size)
+        .sum();
+    (done, files, bytes)
+}
+
+fn write_work(f: &mut fmt::Formatter<'_>, tag: &str, w: &WorkEntry) -> fmt::Result {
+    let i = &w.info;
+    let status = match w.status {
+        Status::Done => "完成",
+        Status::Failed => "失败",
+    };
+    writeln!(
+        f,
+        "  [{}{}] {} {}/{}/{} ({}个文件) {}",
+        tag,
+        status,
+        i.pub_date,
+        i.fen_lei,
+        i.actor,
+        i.title,
+        w.files.len(),
+        i.page_url
+    )?;
+    for file in w.files.iter().filter(|x| x.status == Status::Failed) {
+        writeln!(
+            f,
+            "      {}: {}",
+            file.url,
+            file.error.as_deref().unwrap_or_default()
+        )?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for SyncReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "上次同步到: {}, 本次同步到: {}",
+            self.since.as_deref().unwrap_or("无(首次同步)"),
+            self.latest.as_deref().unwrap_or("无")
+        )?;
+        if !self.failed_pages.is_empty() {
+            writeln!(
+                f,
+                "{}个分页请求失败，未更新同步进度: {}",
+                self.failed_pages.len(),
+                self.failed_pages.join(", ")
+            )?;
+        }
+
+        let (done, files, bytes) = totals(&self.new_works);
+        writeln!(
+            f,
+            "新作品: {}个, 成功{}个, 失败{}个, 共{}个文件, {:.1} MB",
+            self.new_works.len(),
+            done,
+            self.new_works.len() - done,
+            files,
+            bytes as f64 / 1024.0 / 1024.0
+        )?;
+        let (done, _, _) = totals(&self.retried);
+        writeln!(
+            f,
+            "重试作品: {}个, 成功{}个, 仍失败{}个",
+            self.retried.len(),
+            done,
+            self.retried.len() - done
+        )?;
+
+        let mut works: Vec<_> = self
+            .new_works
+            .iter()
+            .map(|w| ("新增", w))
+            .chain(self.retried.iter().map(|w| ("重试", w)))
+            .collect();
+        works.sort_by(|a, b| b.1.info.pub_date.cmp(&a.1.info.pub_date));
+        for (tag, w) in works {
+            write_work(f, tag, w)?;
+        }
+        Ok(())
+    }
+}
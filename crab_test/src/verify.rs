@@ -0,0 +1,473 @@
+//! 下载后的文件校验：按扩展名检查jpg/png/webp/mp4文件头的magic bytes，文件是否被截断，
+//! 以及视频大小是否与作品页中给出的大小(例如`29M`)相符
+//!
+//! 校验失败的文件被移动到`<SAVE_DIR>/.quarantine/`下的相同相对路径中，
+//! 因此下次下载时不会因为文件已存在而被跳过
+//!
+
+use crate::{
+    manifest::{now, Manifest, Status},
+    others::parse_size,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+use tracing::{error, warn};
+
+/// 隔离目录，位于SAVE_DIR中
+pub const QUARANTINE_DIR: &str = ".quarantine";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Jpg,
+    Png,
+    Webp,
+    Mp4,
+}
+
+impl Kind {
+    fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "jpg" | "jpeg" => Some(Self::Jpg),
+            "png" => Some(Self::Png),
+            "webp" => Some(Self::Webp),
+            "mp4" => Some(Self::Mp4),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Problem {
+    Io(io::Error),
+    Empty,
+    /// 文件头与扩展名不符，例如保存了html错误页面
+    BadMagic,
+    /// 文件不完整
+    Truncated,
+    /// 与已知的大小不符
+    Size {
+        expected: String,
+        got: u64,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Io(e) => write!(f, "{}", e),
+            Problem::Empty => write!(f, "空文件"),
+            Problem::BadMagic => write!(f, "文件头与扩展名不符"),
+            Problem::Truncated => write!(f, "文件不完整"),
+            Problem::Size { expected, got } => {
+                write!(f, "文件大小应为{}, 实际为{}字节", expected, got)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for Problem {
+    fn from(e: io::Error) -> Self {
+        Problem::Io(e)
+    }
+}
+
+/// 读取文件开头最多n个字节
+fn read_head(file: &mut File, n: usize) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(n);
+    file.by_ref().take(n as u64).read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// 读取文件末尾最多n个字节
+fn read_tail(file: &mut File, len: u64, n: u64) -> io::Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(len.saturating_sub(n)))?;
+    let mut buf = vec![];
+    file.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+/// 依次读取mp4顶层box的头部，最后一个box应恰好在文件末尾结束
+fn mp4_complete(file: &mut File, len: u64) -> io::Result<bool> {
+    let mut offset = 0;
+    while offset < len {
+        file.seek(SeekFrom::Start(offset))?;
+        let head = read_head(file, 16)?;
+        if head.len() < 8 {
+            return Ok(false);
+        }
+        let size = match u32::from_be_bytes(head[..4].try_into().unwrap()) {
+            // 该box一直到文件末尾
+            0 => return Ok(true),
+            // 64位的大小
+            1 if head.len() == 16 => u64::from_be_bytes(head[8..16].try_into().unwrap()),
+            n if n >= 8 => n as u64,
+            _ => return Ok(false),
+        };
+        offset += size;
+    }
+    Ok(offset == len)
+}
+
+/// 视频大小是否与作品页给出的大小相符，例如`29M`允许相差1M
+fn size_matches(expected: &str, got: u64) -> bool {
+    let tolerance = match expected
+        .trim()
+        .chars()
+        .last()
+        .map(|c| c.to_ascii_uppercase())
+    {
+        Some('K') => 1 << 10,
+        Some('M') => 1 << 20,
+        Some('G') => 1 << 30,
+        _ => 0,
+    };
+    match parse_size(expected) {
+        Ok(n) => got.abs_diff(n) <= tolerance,
+        // 无法解析的大小不作比较
+        Err(_) => true,
+    }
+}
+
+/// 校验文件，filesize为作品页中给出的视频大小。不认识的扩展名只检查是否为空文件
+pub fn verify_file(path: &Path, filesize: Option<&str>) -> Result<(), Problem> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    if len == 0 {
+        return Err(Problem::Empty);
+    }
+    if let Some(expected) = filesize {
+        if !size_matches(expected, len) {
+            return Err(Problem::Size {
+                expected: expected.to_string(),
+                got: len,
+            });
+        }
+    }
+    let kind = match Kind::from_path(path) {
+        Some(k) => k,
+        None => return Ok(()),
+    };
+
+    let head = read_head(&mut file, 12)?;
+    let magic = match kind {
+        Kind::Jpg => head.starts_with(&[0xFF, 0xD8, 0xFF]),
+        Kind::Png => head.starts_with(b"\x89PNG\r\n\x1a\n"),
+        Kind::Webp => head.len() == 12 && &head[..4] == b"RIFF" && &head[8..12] == b"WEBP",
+        Kind::Mp4 => {
+            head.len() >= 8
+                && [b"ftyp", b"moov", b"mdat", b"free", b"skip", b"wide"]
+                    .iter()
+                    .any(|t| &head[4..8] == *t)
+        }
+    };
+    if !magic {
+        return Err(Problem::BadMagic);
+    }
+
+    let complete = match kind {
+        // 以EOI标记结束，之后可能有少量填充
+        Kind::Jpg => contains(&read_tail(&mut file, len, 32)?, &[0xFF, 0xD9]),
+        // 以IEND块结束
+        Kind::Png => contains(&read_tail(&mut file, len, 32)?, b"IEND\xAE\x42\x60\x82"),
+        // RIFF头中记录了之后的数据长度
+        Kind::Webp => u32::from_le_bytes(head[4..8].try_into().unwrap()) as u64 + 8 <= len,
+        Kind::Mp4 => mp4_complete(&mut file, len)?,
+    };
+    if !complete {
+        return Err(Problem::Truncated);
+    }
+    Ok(())
+}
+
+/// 把save_dir中的文件移动到隔离目录，返回移动后的路径
+pub fn quarantine(save_dir: &Path, path: &Path) -> io::Result<PathBuf> {
+    let rel = match path.strip_prefix(save_dir) {
+        Ok(r) => r.to_path_buf(),
+        Err(_) => PathBuf::from(path.file_name().unwrap_or_default()),
+    };
+    let to = save_dir.join(QUARANTINE_DIR).join(rel);
+    if let Some(dir) = to.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::rename(path, &to)?;
+    Ok(to)
+}
+
+/// 校验文件，校验失败时移动到隔离目录
+pub fn verify_or_quarantine(
+    save_dir: &Path,
+    path: &Path,
+    filesize: Option<&str>,
+) -> Result<(), Problem> {
+    let problem = match verify_file(path, filesize) {
+        Ok(()) => return Ok(()),
+        Err(p) => p,
+    };
+    match quarantine(save_dir, path) {
+        Ok(to) => warn!(
+            "校验失败({}): {}, 已移动到 {}",
+            problem,
+            path.display(),
+            to.display()
+        ),
+        Err(e) => error!(
+            "校验失败({}): {}, 移动到隔离目录失败: {}",
+            problem,
+            path.display(),
+            e
+        ),
+    }
+    Err(problem)
+}
+
+/// 一次verify的结果
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// 校验过的文件数
+    pub checked: usize,
+    /// 校验失败的文件
+    pub bad: Vec<(PathBuf, Problem)>,
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "校验文件: {}个, 正常{}个, 失败{}个",
+            self.checked,
+            self.checked - self.bad.len(),
+            self.bad.len()
+        )?;
+        for (path, problem) in &self.bad {
+            writeln!(f, "  {}: {}", path.display(), problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// save_dir中所有下载的文件，跳过以`.`开头的文件和目录(清单、隔离目录)以及未下载完的`.part`文件
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with('.') {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            walk(&path, files)?;
+        } else if !name.ends_with(".part") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// 重新校验save_dir中的所有文件。dry_run为false时，把校验失败的文件移动到隔离目录，
+/// 并在清单中把所属作品标记为下载失败，下次sync时会重新下载
+pub fn verify_dir(save_dir: &Path, manifest: Option<&Manifest>, dry_run: bool) -> VerifyReport {
+    let mut files = vec![];
+    if let Err(e) = walk(save_dir, &mut files) {
+        error!("读取目录 {} 失败: {}", save_dir.display(), e);
+    }
+    files.sort();
+
+    // 清单中记录的视频大小，以及文件所属的作品
+    let works = manifest.map(|m| m.works()).unwrap_or_default();
+    let known: HashMap<&Path, (usize, Option<&str>)> = works
+        .iter()
+        .enumerate()
+        .flat_map(|(i, w)| {
+            w.files
+                .iter()
+                .map(move |f| (f.path.as_path(), (i, f.filesize.as_deref())))
+        })
+        .collect();
+
+    let mut report = VerifyReport::default();
+    let mut bad_works: HashMap<usize, Vec<(PathBuf, String)>> = HashMap::new();
+    for path in files {
+        report.checked += 1;
+        let (work, filesize) = match known.get(path.as_path()) {
+            Some((i, s)) => (Some(*i), *s),
+            None => (None, None),
+        };
+        let res = if dry_run {
+            verify_file(&path, filesize)
+        } else {
+            verify_or_quarantine(save_dir, &path, filesize)
+        };
+        if let Err(problem) = res {
+            if let Some(i) = work {
+                bad_works
+                    .entry(i)
+                    .or_default()
+                    .push((path.clone(), problem.to_string()));
+            }
+            report.bad.push((path, problem));
+        }
+    }
+
+    if let (false, Some(m)) = (dry_run, manifest) {
+        for (i, bad) in bad_works {
+            let mut entry = works[i].clone();
+            for f in entry.files.iter_mut() {
+                if let Some((_, problem)) = bad.iter().find(|(p, _)| *p == f.path) {
+                    f.status = Status::Failed;
+                    f.size = None;
+                    f.error = Some(format!("校验失败: {}", problem));
+                }
+            }
+            entry.status = Status::Failed;
+            entry.time = now();
+            if let Err(e) = m.record_work(entry) {
+                error!("写入下载清单失败: {}", e);
+            }
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("crab_test-verify-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn jpg() -> Vec<u8> {
+        let mut data = vec![0xFF, 0xD8, 0xFF, 0xE0];
+        data.extend([0x12; 100]);
+        data.extend([0xFF, 0xD9]);
+        data
+    }
+
+    fn png() -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+        data.extend([0; 100]);
+        data.extend(b"\0\0\0\0IEND\xAE\x42\x60\x82");
+        data
+    }
+
+    fn webp() -> Vec<u8> {
+        let mut data = b"RIFF".to_vec();
+        data.extend(104u32.to_le_bytes());
+        data.extend(b"WEBP");
+        data.extend([0; 100]);
+        data
+    }
+
+    fn mp4() -> Vec<u8> {
+        let mut data = vec![];
+        for (t, len) in [(b"ftyp", 24u32), (b"moov", 100), (b"mdat", 1000)] {
+            data.extend(len.to_be_bytes());
+            data.extend(t);
+            data.extend(vec![0; len as usize - 8]);
+        }
+        data
+    }
+
+    fn check(dir: &Path, name: &str, data: &[u8], filesize: Option<&str>) -> Result<(), Problem> {
+        let path = dir.join(name);
+        std::fs::write(&path, data).unwrap();
+        verify_file(&path, filesize)
+    }
+
+    #[test]
+    fn magic_and_truncation() {
+        let dir = temp_dir("magic");
+        for (name, data) in [
+            ("0001.jpg", jpg()),
+            ("0001.png", png()),
+            ("0001.webp", webp()),
+            ("0001.mp4", mp4()),
+        ] {
+            assert!(check(&dir, name, &data, None).is_ok(), "{}", name);
+            let truncated = &data[..data.len() - 20];
+            assert!(
+                matches!(check(&dir, name, truncated, None), Err(Problem::Truncated)),
+                "{}",
+                name
+            );
+        }
+
+        let html = b"<html><body>404 Not Found</body></html>";
+        assert!(matches!(
+            check(&dir, "0002.jpg", html, None),
+            Err(Problem::BadMagic)
+        ));
+        assert!(matches!(
+            check(&dir, "0002.mp4", html, None),
+            Err(Problem::BadMagic)
+        ));
+        assert!(matches!(
+            check(&dir, "0003.jpg", b"", None),
+            Err(Problem::Empty)
+        ));
+        // 不认识的扩展名不检查文件头
+        assert!(check(&dir, "0001.gif", html, None).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn filesize() {
+        assert!(size_matches("29M", 29 * 1024 * 1024 + 300_000));
+        assert!(size_matches("29M", 28 * 1024 * 1024 + 300_000));
+        assert!(!size_matches("29M", 20 * 1024 * 1024));
+        assert!(size_matches("未知", 1));
+
+        let dir = temp_dir("filesize");
+        assert!(matches!(
+            check(&dir, "0001.mp4", &mp4(), Some("8M")),
+            Err(Problem::Size { got: 1124, .. })
+        ));
+        assert!(check(&dir, "0001.mp4", &mp4(), Some("1K")).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn quarantine_dir() {
+        let dir = temp_dir("quarantine");
+        let work = dir.join("秀人网").join("无名");
+        std::fs::create_dir_all(&work).unwrap();
+        std::fs::write(work.join("0001.jpg"), jpg()).unwrap();
+        std::fs::write(work.join("0002.jpg"), b"<html></html>").unwrap();
+        std::fs::write(work.join("0003.jpg.part"), b"<html>").unwrap();
+
+        let report = verify_dir(&dir, None, true);
+        assert_eq!((report.checked, report.bad.len()), (2, 1));
+        assert!(work.join("0002.jpg").exists());
+
+        let report = verify_dir(&dir, None, false);
+        assert_eq!(report.bad[0].0, work.join("0002.jpg"));
+        assert!(!work.join("0002.jpg").exists());
+        assert!(dir
+            .join(QUARANTINE_DIR)
+            .join("秀人网/无名/0002.jpg")
+            .exists());
+
+        // 隔离目录中的文件不再被校验
+        let report = verify_dir(&dir, None, false);
+        assert_eq!((report.checked, report.bad.len()), (1, 0));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
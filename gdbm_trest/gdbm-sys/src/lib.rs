@@ -1,97 +1,306 @@
-pub const GDBM_READER: ::std::os::raw::c_uint = 0;
-pub const GDBM_WRITER: ::std::os::raw::c_uint = 1;
-pub const GDBM_WRCREAT: ::std::os::raw::c_uint = 2;
-pub const GDBM_NEWDB: ::std::os::raw::c_uint = 3;
-pub const GDBM_FAST: ::std::os::raw::c_uint = 16;
-pub const GDBM_SYNC: ::std::os::raw::c_uint = 32;
-pub const GDBM_NOLOCK: ::std::os::raw::c_uint = 64;
-
-pub const GDBM_INSERT: ::std::os::raw::c_uint = 0;
-pub const GDBM_REPLACE: ::std::os::raw::c_uint = 1;
-pub const GDBM_CACHESIZE: ::std::os::raw::c_uint = 1;
-pub const GDBM_FASTMODE: ::std::os::raw::c_uint = 2;
-pub const GDBM_SYNCMODE: ::std::os::raw::c_uint = 3;
-pub const GDBM_CENTFREE: ::std::os::raw::c_uint = 4;
-pub const GDBM_COALESCEBLKS: ::std::os::raw::c_uint = 5;
-
-pub const GDBM_NO_ERROR: ::std::os::raw::c_uint = 0;
-pub const GDBM_MALLOC_ERROR: ::std::os::raw::c_uint = 1;
-pub const GDBM_BLOCK_SIZE_ERROR: ::std::os::raw::c_uint = 2;
-pub const GDBM_FILE_OPEN_ERROR: ::std::os::raw::c_uint = 3;
-pub const GDBM_FILE_WRITE_ERROR: ::std::os::raw::c_uint = 4;
-pub const GDBM_FILE_SEEK_ERROR: ::std::os::raw::c_uint = 5;
-pub const GDBM_FILE_READ_ERROR: ::std::os::raw::c_uint = 6;
-pub const GDBM_BAD_MAGIC_NUMBER: ::std::os::raw::c_uint = 7;
-pub const GDBM_EMPTY_DATABASE: ::std::os::raw::c_uint = 8;
-pub const GDBM_CANT_BE_READER: ::std::os::raw::c_uint = 9;
-pub const GDBM_CANT_BE_WRITER: ::std::os::raw::c_uint = 10;
-pub const GDBM_READER_CANT_DELETE: ::std::os::raw::c_uint = 11;
-pub const GDBM_READER_CANT_STORE: ::std::os::raw::c_uint = 12;
-pub const GDBM_READER_CANT_REORGANIZE: ::std::os::raw::c_uint = 13;
-pub const GDBM_UNKNOWN_UPDATE: ::std::os::raw::c_uint = 14;
-pub const GDBM_ITEM_NOT_FOUND: ::std::os::raw::c_uint = 15;
-pub const GDBM_REORGANIZE_FAILED: ::std::os::raw::c_uint = 16;
-pub const GDBM_CANNOT_REPLACE: ::std::os::raw::c_uint = 17;
-pub const GDBM_ILLEGAL_DATA: ::std::os::raw::c_uint = 18;
-pub const GDBM_OPT_ALREADY_SET: ::std::os::raw::c_uint = 19;
-pub const GDBM_OPT_ILLEGAL: ::std::os::raw::c_uint = 20;
+#![allow(non_camel_case_types, non_upper_case_globals)]
+
+//! FFI bindings for libgdbm.
+//!
+//! Everything up to gdbm 1.10 is always declared. Functions added in later
+//! releases are behind the `v1_11` ... `v1_21` features; each feature
+//! enables the ones before it, and the linked libgdbm must be at least that
+//! version.
+
+use std::os::raw::{c_char, c_int, c_uint, c_ulonglong, c_void};
+
+pub const GDBM_READER: c_uint = 0;
+pub const GDBM_WRITER: c_uint = 1;
+pub const GDBM_WRCREAT: c_uint = 2;
+pub const GDBM_NEWDB: c_uint = 3;
+pub const GDBM_OPENMASK: c_uint = 7;
+pub const GDBM_FAST: c_uint = 16;
+pub const GDBM_SYNC: c_uint = 32;
+pub const GDBM_NOLOCK: c_uint = 64;
+pub const GDBM_NOMMAP: c_uint = 128;
+pub const GDBM_CLOEXEC: c_uint = 256;
+pub const GDBM_BSEXACT: c_uint = 512;
+pub const GDBM_CLOERROR: c_uint = 1024;
+pub const GDBM_XVERIFY: c_uint = 2048;
+pub const GDBM_PREREAD: c_uint = 4096;
+pub const GDBM_NUMSYNC: c_uint = 8192;
+
+pub const GDBM_INSERT: c_uint = 0;
+pub const GDBM_REPLACE: c_uint = 1;
+
+pub const GDBM_CACHESIZE: c_uint = 1;
+pub const GDBM_FASTMODE: c_uint = 2;
+pub const GDBM_SYNCMODE: c_uint = 3;
+pub const GDBM_CENTFREE: c_uint = 4;
+pub const GDBM_COALESCEBLKS: c_uint = 5;
+pub const GDBM_SETCACHESIZE: c_uint = 1;
+pub const GDBM_SETSYNCMODE: c_uint = 3;
+pub const GDBM_SETCENTFREE: c_uint = 4;
+pub const GDBM_SETCOALESCEBLKS: c_uint = 5;
+pub const GDBM_SETMAXMAPSIZE: c_uint = 6;
+pub const GDBM_SETMMAP: c_uint = 7;
+pub const GDBM_GETFLAGS: c_uint = 8;
+pub const GDBM_GETMMAP: c_uint = 9;
+pub const GDBM_GETCACHESIZE: c_uint = 10;
+pub const GDBM_GETSYNCMODE: c_uint = 11;
+pub const GDBM_GETCENTFREE: c_uint = 12;
+pub const GDBM_GETCOALESCEBLKS: c_uint = 13;
+pub const GDBM_GETMAXMAPSIZE: c_uint = 14;
+pub const GDBM_GETDBNAME: c_uint = 15;
+pub const GDBM_GETBLOCKSIZE: c_uint = 16;
+pub const GDBM_GETDBFORMAT: c_uint = 17;
+pub const GDBM_GETDIRDEPTH: c_uint = 18;
+pub const GDBM_GETBUCKETSIZE: c_uint = 19;
+pub const GDBM_GETCACHEAUTO: c_uint = 20;
+pub const GDBM_SETCACHEAUTO: c_uint = 21;
+
+pub const GDBM_CACHE_AUTO: usize = 0;
+
+pub const GDBM_DUMP_FMT_BINARY: c_uint = 0;
+pub const GDBM_DUMP_FMT_ASCII: c_uint = 1;
+
+pub const GDBM_META_MASK_MODE: c_uint = 1;
+pub const GDBM_META_MASK_OWNER: c_uint = 2;
+
+pub const GDBM_RCVR_DEFAULT: c_uint = 0;
+pub const GDBM_RCVR_ERRFUN: c_uint = 1;
+pub const GDBM_RCVR_MAX_FAILED_KEYS: c_uint = 2;
+pub const GDBM_RCVR_MAX_FAILED_BUCKETS: c_uint = 4;
+pub const GDBM_RCVR_MAX_FAILURES: c_uint = 8;
+pub const GDBM_RCVR_BACKUP: c_uint = 16;
+pub const GDBM_RCVR_FORCE: c_uint = 32;
+
+pub const GDBM_SNAPSHOT_OK: c_int = 0;
+pub const GDBM_SNAPSHOT_BAD: c_int = 1;
+pub const GDBM_SNAPSHOT_ERR: c_int = 2;
+pub const GDBM_SNAPSHOT_SAME: c_int = 3;
+pub const GDBM_SNAPSHOT_SUSPICIOUS: c_int = 4;
+
+pub const GDBM_NO_ERROR: c_uint = 0;
+pub const GDBM_MALLOC_ERROR: c_uint = 1;
+pub const GDBM_BLOCK_SIZE_ERROR: c_uint = 2;
+pub const GDBM_FILE_OPEN_ERROR: c_uint = 3;
+pub const GDBM_FILE_WRITE_ERROR: c_uint = 4;
+pub const GDBM_FILE_SEEK_ERROR: c_uint = 5;
+pub const GDBM_FILE_READ_ERROR: c_uint = 6;
+pub const GDBM_BAD_MAGIC_NUMBER: c_uint = 7;
+pub const GDBM_EMPTY_DATABASE: c_uint = 8;
+pub const GDBM_CANT_BE_READER: c_uint = 9;
+pub const GDBM_CANT_BE_WRITER: c_uint = 10;
+pub const GDBM_READER_CANT_DELETE: c_uint = 11;
+pub const GDBM_READER_CANT_STORE: c_uint = 12;
+pub const GDBM_READER_CANT_REORGANIZE: c_uint = 13;
+pub const GDBM_UNKNOWN_ERROR: c_uint = 14;
+pub const GDBM_UNKNOWN_UPDATE: c_uint = 14;
+pub const GDBM_ITEM_NOT_FOUND: c_uint = 15;
+pub const GDBM_REORGANIZE_FAILED: c_uint = 16;
+pub const GDBM_CANNOT_REPLACE: c_uint = 17;
+pub const GDBM_ILLEGAL_DATA: c_uint = 18;
+pub const GDBM_MALFORMED_DATA: c_uint = 18;
+pub const GDBM_OPT_ALREADY_SET: c_uint = 19;
+pub const GDBM_OPT_ILLEGAL: c_uint = 20;
+pub const GDBM_OPT_BADVAL: c_uint = 20;
+pub const GDBM_BYTE_SWAPPED: c_uint = 21;
+pub const GDBM_BAD_FILE_OFFSET: c_uint = 22;
+pub const GDBM_BAD_OPEN_FLAGS: c_uint = 23;
+pub const GDBM_FILE_STAT_ERROR: c_uint = 24;
+pub const GDBM_FILE_EOF: c_uint = 25;
+pub const GDBM_NO_DBNAME: c_uint = 26;
+pub const GDBM_ERR_FILE_OWNER: c_uint = 27;
+pub const GDBM_ERR_FILE_MODE: c_uint = 28;
+pub const GDBM_NEED_RECOVERY: c_uint = 29;
+pub const GDBM_BACKUP_FAILED: c_uint = 30;
+pub const GDBM_DIR_OVERFLOW: c_uint = 31;
+pub const GDBM_BAD_BUCKET: c_uint = 32;
+pub const GDBM_BAD_HEADER: c_uint = 33;
+pub const GDBM_BAD_AVAIL: c_uint = 34;
+pub const GDBM_BAD_HASH_TABLE: c_uint = 35;
+pub const GDBM_BAD_DIR_ENTRY: c_uint = 36;
+pub const GDBM_FILE_CLOSE_ERROR: c_uint = 37;
+pub const GDBM_FILE_SYNC_ERROR: c_uint = 38;
+pub const GDBM_FILE_TRUNCATE_ERROR: c_uint = 39;
+pub const GDBM_BUCKET_CACHE_CORRUPTED: c_uint = 40;
+pub const GDBM_BAD_HASH_ENTRY: c_uint = 41;
+pub const GDBM_ERR_SNAPSHOT_CLONE: c_uint = 42;
+pub const GDBM_ERR_REALPATH: c_uint = 43;
+pub const GDBM_ERR_USAGE: c_uint = 44;
+
+pub const _GDBM_MIN_ERRNO: c_uint = 0;
+pub const _GDBM_MAX_ERRNO: c_uint = 44;
 
 #[repr(C)]
 #[derive(Debug, Copy)]
 pub struct datum {
-    pub dptr: *mut ::std::os::raw::c_char,
-    pub dsize: ::std::os::raw::c_int,
+    pub dptr: *mut c_char,
+    pub dsize: c_int,
 }
 impl Clone for datum {
     fn clone(&self) -> Self {
         *self
     }
 }
+impl<'a> From<&'a [u8]> for datum {
+    fn from(src: &'a [u8]) -> datum {
+        // gdbm never writes through the key or content pointer it is given
+        datum {
+            dptr: src.as_ptr() as *mut c_char,
+            dsize: src.len() as c_int,
+        }
+    }
+}
+
 #[repr(C)]
-#[derive(Debug, Copy)]
-pub struct _bindgen_ty_1 {
-    pub dummy: [::std::os::raw::c_int; 10usize],
+#[derive(Debug, Copy, Clone)]
+pub struct gdbm_file_info {
+    _private: [u8; 0],
 }
-impl Clone for _bindgen_ty_1 {
-    fn clone(&self) -> Self {
-        *self
-    }
+
+pub type GDBM_FILE = *mut gdbm_file_info;
+pub type gdbm_error = c_int;
+pub type gdbm_count_t = c_ulonglong;
+
+/// C's `FILE`, for the `*_to_file` and `*_from_file` functions.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct FILE {
+    _private: [u8; 0],
+}
+
+#[cfg(feature = "v1_13")]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct gdbm_recovery {
+    pub errfun: Option<unsafe extern "C" fn(data: *mut c_void, fmt: *const c_char, ...)>,
+    pub data: *mut c_void,
+    pub max_failed_keys: usize,
+    pub max_failed_buckets: usize,
+    pub max_failures: usize,
+    pub recovered_keys: usize,
+    pub recovered_buckets: usize,
+    pub failed_keys: usize,
+    pub failed_buckets: usize,
+    pub duplicate_keys: usize,
+    pub backup_name: *mut c_char,
 }
 
-#[allow(non_camel_case_types)]
-pub type GDBM_FILE = *mut _bindgen_ty_1;
-#[allow(non_camel_case_types)]
-pub type gdbm_error = ::std::os::raw::c_int;
+pub type gdbm_fatal_func = Option<unsafe extern "C" fn(arg1: *const c_char)>;
 
 #[cfg(any(target_os = "linux", target_os = "macos"))]
-#[link(name = "gdbm", kind="dylib")]
+#[link(name = "gdbm", kind = "dylib")]
 extern "C" {
-    pub static mut gdbm_version: *mut ::std::os::raw::c_char;
-    pub fn gdbm_open(arg1: *mut ::std::os::raw::c_char,
-                     arg2: ::std::os::raw::c_int,
-                     arg3: ::std::os::raw::c_int,
-                     arg4: ::std::os::raw::c_int,
-                     arg5: ::std::option::Option<unsafe extern "C" fn()>)
+    pub static gdbm_version: *const c_char;
+
+    pub fn gdbm_open(file_name: *const c_char,
+                     block_size: c_int,
+                     flags: c_int,
+                     mode: c_int,
+                     fatal_func: gdbm_fatal_func)
                      -> GDBM_FILE;
-    pub fn gdbm_close(arg1: GDBM_FILE);
-    pub fn gdbm_store(arg1: GDBM_FILE,
-                      arg2: datum,
-                      arg3: datum,
-                      arg4: ::std::os::raw::c_int)
-                      -> ::std::os::raw::c_int;
-    pub fn gdbm_fetch(arg1: GDBM_FILE, arg2: datum) -> datum;
-    pub fn gdbm_delete(arg1: GDBM_FILE, arg2: datum) -> ::std::os::raw::c_int;
-    pub fn gdbm_firstkey(arg1: GDBM_FILE) -> datum;
-    pub fn gdbm_nextkey(arg1: GDBM_FILE, arg2: datum) -> datum;
-    pub fn gdbm_reorganize(arg1: GDBM_FILE) -> ::std::os::raw::c_int;
-    pub fn gdbm_sync(arg1: GDBM_FILE);
-    pub fn gdbm_exists(arg1: GDBM_FILE, arg2: datum) -> ::std::os::raw::c_int;
-    pub fn gdbm_setopt(arg1: GDBM_FILE,
-                       arg2: ::std::os::raw::c_int,
-                       arg3: *mut ::std::os::raw::c_int,
-                       arg4: ::std::os::raw::c_int)
-                       -> ::std::os::raw::c_int;
-    pub fn gdbm_fdesc(arg1: GDBM_FILE) -> ::std::os::raw::c_int;
+    #[cfg(not(feature = "v1_17"))]
+    pub fn gdbm_close(dbf: GDBM_FILE);
+    #[cfg(feature = "v1_17")]
+    pub fn gdbm_close(dbf: GDBM_FILE) -> c_int;
+    pub fn gdbm_store(dbf: GDBM_FILE, key: datum, content: datum, flag: c_int) -> c_int;
+    pub fn gdbm_fetch(dbf: GDBM_FILE, key: datum) -> datum;
+    pub fn gdbm_delete(dbf: GDBM_FILE, key: datum) -> c_int;
+    pub fn gdbm_firstkey(dbf: GDBM_FILE) -> datum;
+    pub fn gdbm_nextkey(dbf: GDBM_FILE, prev: datum) -> datum;
+    pub fn gdbm_reorganize(dbf: GDBM_FILE) -> c_int;
+    #[cfg(not(feature = "v1_17"))]
+    pub fn gdbm_sync(dbf: GDBM_FILE);
+    #[cfg(feature = "v1_17")]
+    pub fn gdbm_sync(dbf: GDBM_FILE) -> c_int;
+    pub fn gdbm_exists(dbf: GDBM_FILE, key: datum) -> c_int;
+    pub fn gdbm_setopt(dbf: GDBM_FILE,
+                       option: c_int,
+                       value: *mut c_void,
+                       size: c_int)
+                       -> c_int;
+    pub fn gdbm_fdesc(dbf: GDBM_FILE) -> c_int;
+    pub fn gdbm_export(dbf: GDBM_FILE,
+                       export_file: *const c_char,
+                       flag: c_int,
+                       mode: c_int)
+                       -> c_int;
+    pub fn gdbm_import(dbf: GDBM_FILE, import_file: *const c_char, flag: c_int) -> c_int;
+
     pub fn gdbm_errno_location() -> *mut gdbm_error;
-    pub fn gdbm_strerror(arg1: gdbm_error) -> *const ::std::os::raw::c_char;
+    pub fn gdbm_strerror(error: gdbm_error) -> *const c_char;
+}
+
+#[cfg(all(feature = "v1_11", any(target_os = "linux", target_os = "macos")))]
+#[link(name = "gdbm", kind = "dylib")]
+extern "C" {
+    pub static gdbm_version_number: [c_int; 3];
+    pub fn gdbm_version_cmp(a: *const c_int, b: *const c_int) -> c_int;
+
+    pub fn gdbm_count(dbf: GDBM_FILE, pcount: *mut gdbm_count_t) -> c_int;
+
+    pub fn gdbm_export_to_file(dbf: GDBM_FILE, fp: *mut FILE) -> c_int;
+    pub fn gdbm_import_from_file(dbf: GDBM_FILE, fp: *mut FILE, flag: c_int) -> c_int;
+
+    pub fn gdbm_dump(dbf: GDBM_FILE,
+                     filename: *const c_char,
+                     format: c_int,
+                     open_flags: c_int,
+                     mode: c_int)
+                     -> c_int;
+    pub fn gdbm_dump_to_file(dbf: GDBM_FILE, fp: *mut FILE, format: c_int) -> c_int;
+    pub fn gdbm_load(pdbf: *mut GDBM_FILE,
+                     filename: *const c_char,
+                     replace: c_int,
+                     meta_mask: c_int,
+                     line: *mut ::std::os::raw::c_ulong)
+                     -> c_int;
+    pub fn gdbm_load_from_file(pdbf: *mut GDBM_FILE,
+                               fp: *mut FILE,
+                               replace: c_int,
+                               meta_mask: c_int,
+                               line: *mut ::std::os::raw::c_ulong)
+                               -> c_int;
+}
+
+#[cfg(all(feature = "v1_13", any(target_os = "linux", target_os = "macos")))]
+#[link(name = "gdbm", kind = "dylib")]
+extern "C" {
+    pub fn gdbm_recover(dbf: GDBM_FILE, rcvr: *mut gdbm_recovery, flags: c_int) -> c_int;
+    pub fn gdbm_needs_recovery(dbf: GDBM_FILE) -> c_int;
+    pub fn gdbm_copy_meta(dst: GDBM_FILE, src: GDBM_FILE) -> c_int;
+
+    pub fn gdbm_last_errno(dbf: GDBM_FILE) -> gdbm_error;
+    pub fn gdbm_last_syserr(dbf: GDBM_FILE) -> c_int;
+    pub fn gdbm_set_errno(dbf: GDBM_FILE, ec: gdbm_error, fatal: c_int);
+    pub fn gdbm_clear_error(dbf: GDBM_FILE);
+    pub fn gdbm_check_syserr(ec: gdbm_error) -> c_int;
+    pub fn gdbm_db_strerror(dbf: GDBM_FILE) -> *const c_char;
+}
+
+#[cfg(all(feature = "v1_14", any(target_os = "linux", target_os = "macos")))]
+#[link(name = "gdbm", kind = "dylib")]
+extern "C" {
+    pub fn gdbm_fd_open(fd: c_int,
+                        file_name: *const c_char,
+                        block_size: c_int,
+                        flags: c_int,
+                        fatal_func: gdbm_fatal_func)
+                        -> GDBM_FILE;
+}
+
+#[cfg(all(feature = "v1_20", any(target_os = "linux", target_os = "macos")))]
+#[link(name = "gdbm", kind = "dylib")]
+extern "C" {
+    pub fn gdbm_bucket_count(dbf: GDBM_FILE, pcount: *mut usize) -> c_int;
+    pub fn gdbm_avail_verify(dbf: GDBM_FILE) -> c_int;
+}
+
+#[cfg(all(feature = "v1_21", any(target_os = "linux", target_os = "macos")))]
+#[link(name = "gdbm", kind = "dylib")]
+extern "C" {
+    pub fn gdbm_failure_atomic(dbf: GDBM_FILE, even: *const c_char, odd: *const c_char)
+                               -> c_int;
+    pub fn gdbm_latest_snapshot(even: *const c_char,
+                                odd: *const c_char,
+                                result: *mut *const c_char)
+                                -> c_int;
+    pub fn gdbm_convert(dbf: GDBM_FILE, flag: c_int) -> c_int;
 }
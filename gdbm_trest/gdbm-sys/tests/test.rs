@@ -8,3 +8,31 @@ fn test_open() {
         let db_ptr = gdbm_sys::gdbm_open(path.as_ptr() as *mut i8, 0, 0, 0, None);
     }
 }
+
+#[cfg(feature = "v1_11")]
+#[test]
+fn test_count() {
+    use std::ffi::CString;
+    let path = std::env::temp_dir().join(format!("gdbm-sys-count-{}.db", std::process::id()));
+    let path = CString::new(path.to_str().unwrap()).unwrap();
+    unsafe {
+        assert!(gdbm_sys::gdbm_version_number[0] >= 1);
+        let db = gdbm_sys::gdbm_open(path.as_ptr(), 0, gdbm_sys::GDBM_NEWDB as i32, 0o600, None);
+        assert!(!db.is_null());
+        for key in &["a", "b", "c"] {
+            let key = gdbm_sys::datum::from(key.as_bytes());
+            assert_eq!(gdbm_sys::gdbm_store(db, key, key, gdbm_sys::GDBM_REPLACE as i32), 0);
+        }
+        let mut count = 0;
+        assert_eq!(gdbm_sys::gdbm_count(db, &mut count), 0);
+        assert_eq!(count, 3);
+        gdbm_sys::gdbm_close(db);
+        libc_unlink(path.as_ptr());
+    }
+}
+
+#[cfg(feature = "v1_11")]
+extern "C" {
+    #[link_name = "unlink"]
+    fn libc_unlink(path: *const std::os::raw::c_char) -> std::os::raw::c_int;
+}
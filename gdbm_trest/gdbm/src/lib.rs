@@ -7,13 +7,17 @@ use std::error::Error as StdError;
 use std::ffi::{CStr, CString, IntoStringError, NulError};
 use std::fmt;
 use std::io::Error;
+use std::marker::PhantomData;
+use std::ops::Deref;
 use std::os::unix::ffi::OsStrExt;
 use std::os::unix::io::{AsRawFd, RawFd};
 use std::path::Path;
 use std::str::Utf8Error;
 use std::string::FromUtf8Error;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use libc::{c_uint, c_void, free};
+use libc::{c_int, c_uint, c_void, free, size_t};
 
 use gdbm_sys::*;
 
@@ -109,8 +113,9 @@ fn get_error() -> String {
     }
 }
 
-fn datum(what: &str, data: impl AsRef<[u8]>) -> Result<datum, GdbmError> {
-    let data = data.as_ref();
+// `data` is borrowed rather than taken by value, so that the returned
+// pointer stays valid for as long as the caller holds on to `data`.
+fn datum(what: &str, data: &[u8]) -> Result<datum, GdbmError> {
     if data.len() > i32::MAX as usize {
         return Err(GdbmError::new(format!("{} too large", what)));
     }
@@ -149,6 +154,59 @@ bitflags! {
     }
 }
 
+/// A tunable option of an open database, with its value.
+///
+/// Used with [`Gdbm::set_option`](struct.Gdbm.html#method.set_option) and
+/// returned by [`Gdbm::get_option`](struct.Gdbm.html#method.get_option).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GdbmOption {
+    /// Number of buckets kept in the bucket cache.
+    CacheSize(usize),
+    /// Write changes to disk as they are made.
+    SyncMode(bool),
+    /// Return freed blocks to the central free list instead of the bucket's.
+    ///
+    /// gdbm 1.23 does not report this option reliably when it is read.
+    CentFree(bool),
+    /// Merge adjacent free blocks.
+    CoalesceBlocks(bool),
+    /// Use memory mapped I/O.
+    Mmap(bool),
+    /// Maximum size of a memory mapped region, in bytes.
+    MaxMmapSize(usize),
+    /// Block size of the database file, in bytes. This is fixed when the
+    /// file is created, so it can be read but not set.
+    BlockSize(usize),
+}
+
+/// Names a [`GdbmOption`](enum.GdbmOption.html) to read with
+/// [`Gdbm::get_option`](struct.Gdbm.html#method.get_option).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GdbmOptionKind {
+    CacheSize,
+    SyncMode,
+    CentFree,
+    CoalesceBlocks,
+    Mmap,
+    MaxMmapSize,
+    BlockSize,
+}
+
+impl GdbmOption {
+    /// The kind of this option, without its value.
+    pub fn kind(&self) -> GdbmOptionKind {
+        match *self {
+            GdbmOption::CacheSize(_) => GdbmOptionKind::CacheSize,
+            GdbmOption::SyncMode(_) => GdbmOptionKind::SyncMode,
+            GdbmOption::CentFree(_) => GdbmOptionKind::CentFree,
+            GdbmOption::CoalesceBlocks(_) => GdbmOptionKind::CoalesceBlocks,
+            GdbmOption::Mmap(_) => GdbmOptionKind::Mmap,
+            GdbmOption::MaxMmapSize(_) => GdbmOptionKind::MaxMmapSize,
+            GdbmOption::BlockSize(_) => GdbmOptionKind::BlockSize,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Gdbm {
     db_handle: GDBM_FILE, /* int gdbm_export (GDBM_FILE, const char *, int, int);
@@ -179,7 +237,11 @@ impl Drop for Gdbm {
 
 /// With locking disabled (if gdbm_open was called with ‘GDBM_NOLOCK’), the user may want
 /// to perform their own file locking on the database file in order to prevent multiple
-/// writers operating on the same file simultaneously.
+/// writers operating on the same file simultaneously. See [`Gdbm::lock_shared`] and
+/// [`Gdbm::lock_exclusive`].
+///
+/// [`Gdbm::lock_shared`]: struct.Gdbm.html#method.lock_shared
+/// [`Gdbm::lock_exclusive`]: struct.Gdbm.html#method.lock_exclusive
 impl AsRawFd for Gdbm {
     fn as_raw_fd(&self) -> RawFd {
         unsafe { gdbm_fdesc(self.db_handle) as RawFd }
@@ -213,8 +275,18 @@ impl Gdbm {
     /// database, the record is not stored and `false` is returned.
     /// Otherwise `true` is returned.
     pub fn store(&self, key: &str, content: &String, replace: bool) -> Result<bool, GdbmError> {
-        let key_datum = datum("key", key)?;
-        let content_datum = datum("content", content)?;
+        self.store_bytes(key, content, replace)
+    }
+
+    /// Store a record with arbitrary bytes as key and content. See
+    /// [`store`](#method.store).
+    pub fn store_bytes<K, V>(&self, key: K, content: V, replace: bool) -> Result<bool, GdbmError>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        let key_datum = datum("key", key.as_ref())?;
+        let content_datum = datum("content", content.as_ref())?;
         let flag = if replace {
             Store::REPLACE
         } else {
@@ -230,41 +302,45 @@ impl Gdbm {
 
     /// Retrieve a key from the database
     pub fn fetch(&self, key: &str) -> Result<String, GdbmError> {
-        // datum gdbm_fetch(dbf, key);
-        let key_datum = datum("key", key)?;
+        // handle the data as an utf8 encoded string slice
+        // that may or may not be terminated by a \0 byte.
+        self.fetch_datum(key)?.into_string()
+    }
 
-        unsafe {
-            let content = gdbm_fetch(self.db_handle, key_datum);
-            if content.dptr.is_null() {
-                return Err(GdbmError::new(get_error()));
-            } else if content.dsize < 0 {
-                return Err(GdbmError::new("content has negative size"));
-            } else {
-                // handle the data as an utf8 encoded string slice
-                // that may or may not be terminated by a \0 byte.
-                let ptr = content.dptr as *const u8;
-                let len = content.dsize as usize;
-                let mut slice = std::slice::from_raw_parts(ptr, len);
-                if len > 0 && slice[len - 1] == 0 {
-                    slice = &slice[0..len - 1];
-                }
-                let res = match std::str::from_utf8(slice) {
-                    Ok(s) => Ok(s.to_string()),
-                    Err(e) => Err(e.into()),
-                };
+    /// Retrieve the bytes stored for a key.
+    pub fn fetch_bytes<K: AsRef<[u8]>>(&self, key: K) -> Result<Vec<u8>, GdbmError> {
+        Ok(self.fetch_datum(key)?.into_bytes())
+    }
 
-                // Free the malloc'd content that the library gave us
-                // Rust will manage this memory
-                free(content.dptr as *mut c_void);
+    /// Retrieve the bytes stored for a key and pass them to `f`, without
+    /// copying them out of the buffer gdbm allocated.
+    ///
+    /// ```no_run
+    /// # use std::path::Path;
+    /// # let db = gdbm::Gdbm::new(Path::new("klines.db"), 0, gdbm::Open::READER, 0).unwrap();
+    /// let len = db.fetch_with("1609430400", |bytes| bytes.len()).unwrap();
+    /// ```
+    pub fn fetch_with<K, F, T>(&self, key: K, f: F) -> Result<T, GdbmError>
+    where
+        K: AsRef<[u8]>,
+        F: FnOnce(&[u8]) -> T,
+    {
+        let content = self.fetch_datum(key)?;
+        Ok(f(&content))
+    }
 
-                return res;
-            }
+    fn fetch_datum<K: AsRef<[u8]>>(&self, key: K) -> Result<Datum, GdbmError> {
+        // datum gdbm_fetch(dbf, key);
+        let key_datum = datum("key", key.as_ref())?;
+        match unsafe { owned_datum(gdbm_fetch(self.db_handle, key_datum)) } {
+            Some(content) => content,
+            None => Err(GdbmError::new(get_error())),
         }
     }
 
     /// Delete a key and value from the database
     pub fn delete(&self, key: &str) -> bool {
-        let key_datum = match datum("key", key) {
+        let key_datum = match datum("key", key.as_bytes()) {
             Ok(d) => d,
             Err(_) => return false,
         };
@@ -277,30 +353,159 @@ impl Gdbm {
             }
         }
     }
-    // TODO: Make an iterator out of this to hide the datum handling
-    // pub fn firstkey(&self, key: &str) -> Result<String, GdbmError> {
-    // unsafe {
-    // let content = gdbm_firstkey(self.db_handle);
-    // if content.dptr.is_null() {
-    // return Err(GdbmError::new(get_error()));
-    // } else {
-    // let c_string = CStr::from_ptr(content.dptr);
-    // let data = c_string.to_str()?.to_string();
-    // Free the malloc'd content that the library gave us
-    // Rust will manage this memory
-    // free(content.dptr as *mut c_void);
-    //
-    // return Ok(data);
-    // }
-    // }
-    // }
-    // pub fn nextkey(&self, key: &str) -> Result<String, GdbmError> {
-    // unsafe {
-    // datum gdbm_nextkey(dbf, key);
-    //
-    // }
-    // }
-    //
+
+    /// Iterate over the keys in the database, in gdbm's hash order.
+    ///
+    /// The database must not be modified while iterating, or keys may be
+    /// skipped or visited twice.
+    pub fn keys(&self) -> Keys<'_> {
+        let first = unsafe { owned_datum(gdbm_firstkey(self.db_handle)) };
+        Keys {
+            db_handle: self.db_handle,
+            next: first,
+            _db: PhantomData,
+        }
+    }
+
+    /// Iterate over the key/value pairs in the database, in gdbm's hash
+    /// order. See [`keys`](#method.keys).
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { keys: self.keys() }
+    }
+
+    /// Count the records in the database. This walks the whole database.
+    pub fn count(&self) -> Result<u64, GdbmError> {
+        let mut count: gdbm_count_t = 0;
+        let result = unsafe { gdbm_count(self.db_handle, &mut count) };
+        if result < 0 {
+            return Err(GdbmError::new(get_error()));
+        }
+        Ok(count as u64)
+    }
+
+    /// Take a shared lock on the database file, waiting until no other
+    /// handle holds an exclusive lock.
+    ///
+    /// This is for databases opened with `Open::NOLOCK`, where gdbm does no
+    /// locking of its own, and returns an error for any other handle. The
+    /// lock is an advisory `flock(2)` lock: it only keeps out other handles
+    /// that lock the file the same way. It belongs to the open file, so a
+    /// child forked after the lock is taken shares it; open the database
+    /// again in the child instead.
+    ///
+    /// The returned guard borrows the handle mutably, so a handle holds at
+    /// most one lock at a time; use the database through the guard while
+    /// the lock is held.
+    pub fn lock_shared(&mut self) -> Result<FileLock<'_>, GdbmError> {
+        self.flock(libc::LOCK_SH)
+    }
+
+    /// Take an exclusive lock on the database file, waiting until no other
+    /// handle holds any lock. See [`lock_shared`](#method.lock_shared).
+    ///
+    /// ```no_run
+    /// # use std::path::Path;
+    /// # use gdbm::{Gdbm, Open};
+    /// let mut db = Gdbm::new(Path::new("klines.db"), 0, Open::WRITER | Open::NOLOCK, 0o644).unwrap();
+    /// let lock = db.lock_exclusive().unwrap();
+    /// lock.store("max_epoch", &"1609430400".to_string(), true).unwrap();
+    /// lock.sync();
+    /// drop(lock);
+    /// ```
+    pub fn lock_exclusive(&mut self) -> Result<FileLock<'_>, GdbmError> {
+        self.flock(libc::LOCK_EX)
+    }
+
+    /// Take a shared lock if that is possible without waiting.
+    pub fn try_lock_shared(&mut self) -> Result<Option<FileLock<'_>>, GdbmError> {
+        self.try_flock(libc::LOCK_SH)
+    }
+
+    /// Take an exclusive lock if that is possible without waiting.
+    pub fn try_lock_exclusive(&mut self) -> Result<Option<FileLock<'_>>, GdbmError> {
+        self.try_flock(libc::LOCK_EX)
+    }
+
+    /// Take a shared lock, giving up after `timeout`.
+    pub fn try_lock_shared_for(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<FileLock<'_>>, GdbmError> {
+        self.flock_timeout(libc::LOCK_SH, timeout)
+    }
+
+    /// Take an exclusive lock, giving up after `timeout`.
+    pub fn try_lock_exclusive_for(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<FileLock<'_>>, GdbmError> {
+        self.flock_timeout(libc::LOCK_EX, timeout)
+    }
+
+    // a guard on a handle that gdbm locks itself would replace gdbm's lock,
+    // and unlock the file when dropped
+    fn check_nolock(&self) -> Result<(), GdbmError> {
+        let flags = self.getopt::<c_int>(GDBM_GETFLAGS)? as c_uint;
+        if flags & GDBM_NOLOCK == 0 {
+            return Err(GdbmError::new("database was not opened with Open::NOLOCK"));
+        }
+        Ok(())
+    }
+
+    /// Returns `false` if `operation` has `LOCK_NB` and the lock is held
+    /// elsewhere.
+    fn flock_fd(&self, operation: c_int) -> Result<bool, GdbmError> {
+        let fd = self.as_raw_fd();
+        loop {
+            if unsafe { libc::flock(fd, operation) } == 0 {
+                return Ok(true);
+            }
+            let err = Error::last_os_error();
+            match err.kind() {
+                std::io::ErrorKind::Interrupted => continue,
+                std::io::ErrorKind::WouldBlock => return Ok(false),
+                _ => return Err(err.into()),
+            }
+        }
+    }
+
+    fn flock(&mut self, operation: c_int) -> Result<FileLock<'_>, GdbmError> {
+        self.check_nolock()?;
+        self.flock_fd(operation)?;
+        Ok(FileLock { db: self })
+    }
+
+    fn try_flock(&mut self, operation: c_int) -> Result<Option<FileLock<'_>>, GdbmError> {
+        self.check_nolock()?;
+        if self.flock_fd(operation | libc::LOCK_NB)? {
+            Ok(Some(FileLock { db: self }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // flock has no timeout of its own, so poll with a growing delay
+    fn flock_timeout(
+        &mut self,
+        operation: c_int,
+        timeout: Duration,
+    ) -> Result<Option<FileLock<'_>>, GdbmError> {
+        self.check_nolock()?;
+        let deadline = Instant::now() + timeout;
+        let mut delay = Duration::from_millis(1);
+        loop {
+            if self.flock_fd(operation | libc::LOCK_NB)? {
+                return Ok(Some(FileLock { db: self }));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            thread::sleep(delay.min(deadline - now));
+            delay = (delay * 2).min(Duration::from_millis(50));
+        }
+    }
+
     // int gdbm_reorganize(dbf);
     pub fn sync(&self) {
         unsafe {
@@ -310,7 +515,7 @@ impl Gdbm {
 
     /// Check to see if a key exists in the database
     pub fn exists(&self, key: &str) -> Result<bool, GdbmError> {
-        let key_datum = datum("key", key)?;
+        let key_datum = datum("key", key.as_ref())?;
         unsafe {
             let result = gdbm_exists(self.db_handle, key_datum);
             if result == 0 {
@@ -324,11 +529,231 @@ impl Gdbm {
             }
         }
     }
-    // pub fn setopt(&self, key: &str) -> Result<(), GdbmError> {
-    // unsafe {
+
+    /// Set an option on the open database.
+    ///
+    /// ```no_run
+    /// # use std::path::Path;
+    /// # use gdbm::{Gdbm, GdbmOption, GdbmOptionKind, Open};
+    /// let db = Gdbm::new(Path::new("klines.db"), 0, Open::WRCREAT, 0o644).unwrap();
+    /// db.set_option(GdbmOption::CacheSize(64)).unwrap();
+    /// assert_eq!(db.get_option(GdbmOptionKind::CacheSize).unwrap(), GdbmOption::CacheSize(64));
+    /// ```
+    pub fn set_option(&self, option: GdbmOption) -> Result<(), GdbmError> {
+        match option {
+            GdbmOption::CacheSize(size) => self.setopt(GDBM_SETCACHESIZE, size as size_t),
+            GdbmOption::SyncMode(on) => self.setopt(GDBM_SETSYNCMODE, on as c_int),
+            GdbmOption::CentFree(on) => self.setopt(GDBM_SETCENTFREE, on as c_int),
+            GdbmOption::CoalesceBlocks(on) => self.setopt(GDBM_SETCOALESCEBLKS, on as c_int),
+            GdbmOption::Mmap(on) => self.setopt(GDBM_SETMMAP, on as c_int),
+            GdbmOption::MaxMmapSize(size) => self.setopt(GDBM_SETMAXMAPSIZE, size as size_t),
+            GdbmOption::BlockSize(_) => Err(GdbmError::new("block size can not be changed")),
+        }
+    }
+
+    /// Read the current value of an option.
+    pub fn get_option(&self, kind: GdbmOptionKind) -> Result<GdbmOption, GdbmError> {
+        Ok(match kind {
+            GdbmOptionKind::CacheSize => {
+                GdbmOption::CacheSize(self.getopt::<size_t>(GDBM_GETCACHESIZE)?)
+            }
+            GdbmOptionKind::SyncMode => GdbmOption::SyncMode(self.getopt_bool(GDBM_GETSYNCMODE)?),
+            GdbmOptionKind::CentFree => GdbmOption::CentFree(self.getopt_bool(GDBM_GETCENTFREE)?),
+            GdbmOptionKind::CoalesceBlocks => {
+                GdbmOption::CoalesceBlocks(self.getopt_bool(GDBM_GETCOALESCEBLKS)?)
+            }
+            GdbmOptionKind::Mmap => GdbmOption::Mmap(self.getopt_bool(GDBM_GETMMAP)?),
+            GdbmOptionKind::MaxMmapSize => {
+                GdbmOption::MaxMmapSize(self.getopt::<size_t>(GDBM_GETMAXMAPSIZE)?)
+            }
+            GdbmOptionKind::BlockSize => {
+                GdbmOption::BlockSize(self.getopt::<c_int>(GDBM_GETBLOCKSIZE)? as usize)
+            }
+        })
+    }
+
     // int gdbm_setopt(dbf, option, value, size);
-    //
-    // }
-    // }
-    //
+    // gdbm checks `size` against the type it expects for each option, so
+    // `T` must be exactly that type (int or size_t).
+    fn setopt<T>(&self, option: c_uint, mut value: T) -> Result<(), GdbmError> {
+        let result = unsafe {
+            gdbm_setopt(
+                self.db_handle,
+                option as c_int,
+                &mut value as *mut T as *mut c_void,
+                std::mem::size_of::<T>() as c_int,
+            )
+        };
+        if result < 0 {
+            return Err(GdbmError::new(get_error()));
+        }
+        Ok(())
+    }
+
+    fn getopt<T: Default>(&self, option: c_uint) -> Result<T, GdbmError> {
+        let mut value = T::default();
+        let result = unsafe {
+            gdbm_setopt(
+                self.db_handle,
+                option as c_int,
+                &mut value as *mut T as *mut c_void,
+                std::mem::size_of::<T>() as c_int,
+            )
+        };
+        if result < 0 {
+            return Err(GdbmError::new(get_error()));
+        }
+        Ok(value)
+    }
+
+    fn getopt_bool(&self, option: c_uint) -> Result<bool, GdbmError> {
+        Ok(self.getopt::<c_int>(option)? != 0)
+    }
+}
+
+/// A key or value allocated by gdbm, freed when dropped.
+///
+/// Derefs to the raw bytes; use [`to_str`](#method.to_str) or
+/// [`into_string`](#method.into_string) for text stored by
+/// [`Gdbm::store`](struct.Gdbm.html#method.store).
+pub struct Datum {
+    raw: datum,
+}
+
+impl Datum {
+    /// The stored bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.raw.dptr as *const u8, self.raw.dsize as usize) }
+    }
+
+    /// The stored bytes as a string slice, without the trailing \0 byte
+    /// that C programs often store.
+    pub fn to_str(&self) -> Result<&str, GdbmError> {
+        let mut bytes = self.as_bytes();
+        if let Some((&0, rest)) = bytes.split_last() {
+            bytes = rest;
+        }
+        Ok(std::str::from_utf8(bytes)?)
+    }
+
+    /// Copy the stored bytes into a `Vec`.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    /// Copy the stored text into a `String`, see [`to_str`](#method.to_str).
+    pub fn into_string(self) -> Result<String, GdbmError> {
+        self.to_str().map(|s| s.to_string())
+    }
+}
+
+impl Deref for Datum {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl AsRef<[u8]> for Datum {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl fmt::Debug for Datum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Datum").field(&self.as_bytes()).finish()
+    }
+}
+
+impl Drop for Datum {
+    fn drop(&mut self) {
+        unsafe { free(self.raw.dptr as *mut c_void) };
+    }
+}
+
+/// Take ownership of a datum returned by gdbm. A null `dptr` means the end
+/// of the database was reached, or an error if gdbm_errno says so.
+unsafe fn owned_datum(raw: datum) -> Option<Result<Datum, GdbmError>> {
+    if !raw.dptr.is_null() {
+        if raw.dsize < 0 {
+            free(raw.dptr as *mut c_void);
+            return Some(Err(GdbmError::new("datum has negative size")));
+        }
+        return Some(Ok(Datum { raw }));
+    }
+    match *gdbm_errno_location() as c_uint {
+        GDBM_NO_ERROR | GDBM_ITEM_NOT_FOUND => None,
+        _ => Some(Err(GdbmError::new(get_error()))),
+    }
+}
+
+/// An advisory lock on a database file, released when dropped. Created by
+/// [`Gdbm::lock_shared`](struct.Gdbm.html#method.lock_shared) and
+/// [`Gdbm::lock_exclusive`](struct.Gdbm.html#method.lock_exclusive).
+///
+/// The guard dereferences to the locked [`Gdbm`](struct.Gdbm.html).
+#[derive(Debug)]
+pub struct FileLock<'a> {
+    db: &'a mut Gdbm,
+}
+
+impl<'a> Deref for FileLock<'a> {
+    type Target = Gdbm;
+
+    fn deref(&self) -> &Gdbm {
+        self.db
+    }
+}
+
+impl<'a> Drop for FileLock<'a> {
+    fn drop(&mut self) {
+        unsafe { libc::flock(self.db.as_raw_fd(), libc::LOCK_UN) };
+    }
+}
+
+/// An iterator over the keys of a [`Gdbm`](struct.Gdbm.html), created by
+/// [`Gdbm::keys`](struct.Gdbm.html#method.keys).
+pub struct Keys<'a> {
+    db_handle: GDBM_FILE,
+    // gdbm_nextkey needs the previous key, so the iterator stays one ahead
+    // of the caller.
+    next: Option<Result<Datum, GdbmError>>,
+    _db: PhantomData<&'a Gdbm>,
+}
+
+impl<'a> Iterator for Keys<'a> {
+    type Item = Result<Datum, GdbmError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = match self.next.take()? {
+            Ok(key) => key,
+            Err(e) => return Some(Err(e)),
+        };
+        self.next = unsafe { owned_datum(gdbm_nextkey(self.db_handle, key.raw)) };
+        Some(Ok(key))
+    }
+}
+
+/// An iterator over the key/value pairs of a [`Gdbm`](struct.Gdbm.html),
+/// created by [`Gdbm::iter`](struct.Gdbm.html#method.iter).
+pub struct Iter<'a> {
+    keys: Keys<'a>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = Result<(Datum, Datum), GdbmError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = match self.keys.next()? {
+            Ok(key) => key,
+            Err(e) => return Some(Err(e)),
+        };
+        match unsafe { owned_datum(gdbm_fetch(self.keys.db_handle, key.raw)) } {
+            Some(Ok(value)) => Some(Ok((key, value))),
+            Some(Err(e)) => Some(Err(e)),
+            None => Some(Err(GdbmError::new(get_error()))),
+        }
+    }
 }
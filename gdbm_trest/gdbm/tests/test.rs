@@ -26,3 +26,231 @@ fn create_test() {
     drop(db);
     remove_file("test.db").expect("remove_file");
 }
+
+fn temp_db(name: &str) -> gdbm::Gdbm {
+    let path = std::env::temp_dir().join(format!("gdbm-{}-{}.db", name, std::process::id()));
+    let db = gdbm::Gdbm::new(&path, 0, gdbm::Open::NEWDB, (S_IRUSR | S_IWUSR) as i32)
+        .expect("Gdbm::new");
+    // the open handle keeps the file alive
+    remove_file(&path).expect("remove_file");
+    db
+}
+
+#[test]
+fn iterate_test() {
+    let db = temp_db("iter");
+    assert_eq!(db.count().expect("count"), 0);
+    assert!(db.keys().next().is_none());
+
+    for i in 0..100 {
+        db.store(&format!("{}", 1609430400 + i * 60), &format!("kline {}", i), true)
+            .expect("store");
+    }
+    assert_eq!(db.count().expect("count"), 100);
+
+    let mut keys: Vec<String> = db.keys().map(|k| k.unwrap().into_string().unwrap()).collect();
+    keys.sort();
+    let expected: Vec<String> = (0..100).map(|i| format!("{}", 1609430400 + i * 60)).collect();
+    assert_eq!(keys, expected);
+
+    let mut seen = 0;
+    for entry in db.iter() {
+        let (key, value) = entry.expect("iter");
+        let epoch: u64 = key.to_str().unwrap().parse().unwrap();
+        let i = (epoch - 1609430400) / 60;
+        assert_eq!(value.to_str().unwrap(), format!("kline {}", i));
+        assert_eq!(&*value, format!("kline {}", i).as_bytes());
+        seen += 1;
+    }
+    assert_eq!(seen, 100);
+}
+
+#[test]
+fn partial_iteration_test() {
+    let db = temp_db("partial");
+    for i in 0..10 {
+        db.store(&format!("key {}", i), &"value".to_string(), true).expect("store");
+    }
+    // dropping an iterator part way frees the key it read ahead
+    for _ in 0..100 {
+        assert_eq!(db.keys().take(3).count(), 3);
+    }
+    assert_eq!(db.iter().count(), 10);
+}
+
+#[test]
+fn bytes_test() {
+    let db = temp_db("bytes");
+    let key = 1609430400u64.to_be_bytes();
+    let content: Vec<u8> = (0..=255u8).cycle().take(3 << 20).collect();
+    assert!(db.store_bytes(&key, &content, true).expect("store_bytes"));
+    assert!(!db.store_bytes(&key[..], b"other", false).expect("store_bytes"));
+
+    assert_eq!(db.fetch_bytes(&key).expect("fetch_bytes"), content);
+    let sum = db.fetch_with(&key, |bytes| bytes.iter().map(|&b| b as u64).sum::<u64>())
+        .expect("fetch_with");
+    assert_eq!(sum, content.iter().map(|&b| b as u64).sum::<u64>());
+
+    // invalid utf-8 is only an error for the string api
+    db.store_bytes("text", [0xffu8, 0xfe], true).expect("store_bytes");
+    assert!(db.fetch("text").is_err());
+    assert_eq!(db.fetch_bytes("text").expect("fetch_bytes"), vec![0xff, 0xfe]);
+    assert!(db.fetch_with("missing", |_| ()).is_err());
+}
+
+fn round_trip(db: &gdbm::Gdbm, option: gdbm::GdbmOption) {
+    db.set_option(option).expect("set_option");
+    assert_eq!(db.get_option(option.kind()).expect("get_option"), option);
+}
+
+#[test]
+fn cache_size_option_test() {
+    let db = temp_db("opt-cache");
+    round_trip(&db, gdbm::GdbmOption::CacheSize(32));
+    round_trip(&db, gdbm::GdbmOption::CacheSize(128));
+}
+
+#[test]
+fn sync_mode_option_test() {
+    let db = temp_db("opt-sync");
+    round_trip(&db, gdbm::GdbmOption::SyncMode(true));
+    round_trip(&db, gdbm::GdbmOption::SyncMode(false));
+}
+
+#[test]
+fn centfree_option_test() {
+    let db = temp_db("opt-centfree");
+    // gdbm 1.23 reads this option back wrongly, so only the setter and the
+    // kind of the result are checked
+    for &on in &[true, false] {
+        db.set_option(gdbm::GdbmOption::CentFree(on)).expect("set_option");
+        let option = db.get_option(gdbm::GdbmOptionKind::CentFree).expect("get_option");
+        assert_eq!(option.kind(), gdbm::GdbmOptionKind::CentFree);
+    }
+}
+
+#[test]
+fn coalesce_blocks_option_test() {
+    let db = temp_db("opt-coalesce");
+    round_trip(&db, gdbm::GdbmOption::CoalesceBlocks(true));
+    round_trip(&db, gdbm::GdbmOption::CoalesceBlocks(false));
+}
+
+#[test]
+fn mmap_option_test() {
+    let db = temp_db("opt-mmap");
+    round_trip(&db, gdbm::GdbmOption::Mmap(false));
+    round_trip(&db, gdbm::GdbmOption::Mmap(true));
+}
+
+#[test]
+fn max_mmap_size_option_test() {
+    let db = temp_db("opt-maxmmap");
+    // gdbm rounds the size to a page boundary
+    round_trip(&db, gdbm::GdbmOption::MaxMmapSize(1 << 20));
+}
+
+#[test]
+fn block_size_option_test() {
+    let path = std::env::temp_dir().join(format!("gdbm-opt-block-{}.db", std::process::id()));
+    let db = gdbm::Gdbm::new(&path, 4096, gdbm::Open::NEWDB, (S_IRUSR | S_IWUSR) as i32)
+        .expect("Gdbm::new");
+    remove_file(&path).expect("remove_file");
+    assert_eq!(
+        db.get_option(gdbm::GdbmOptionKind::BlockSize).expect("get_option"),
+        gdbm::GdbmOption::BlockSize(4096)
+    );
+    assert!(db.set_option(gdbm::GdbmOption::BlockSize(512)).is_err());
+}
+
+fn nolock_pair(name: &str) -> (std::path::PathBuf, gdbm::Gdbm, gdbm::Gdbm) {
+    let path = std::env::temp_dir().join(format!("gdbm-{}-{}.db", name, std::process::id()));
+    let flags = gdbm::Open::NEWDB | gdbm::Open::NOLOCK;
+    let writer = gdbm::Gdbm::new(&path, 0, flags, (S_IRUSR | S_IWUSR) as i32).expect("Gdbm::new");
+    let flags = gdbm::Open::READER | gdbm::Open::NOLOCK;
+    let reader = gdbm::Gdbm::new(&path, 0, flags, 0).expect("Gdbm::new");
+    (path, writer, reader)
+}
+
+#[test]
+fn lock_test() {
+    let (path, mut writer, mut reader) = nolock_pair("lock");
+    remove_file(&path).expect("remove_file");
+
+    let shared = reader.lock_shared().expect("lock_shared");
+    assert!(writer.try_lock_exclusive().expect("try_lock_exclusive").is_none());
+    let other = writer.try_lock_shared().expect("try_lock_shared");
+    assert!(other.is_some());
+    drop(other);
+
+    let start = std::time::Instant::now();
+    let timeout = std::time::Duration::from_millis(100);
+    assert!(writer.try_lock_exclusive_for(timeout).expect("try_lock").is_none());
+    assert!(start.elapsed() >= timeout);
+
+    drop(shared);
+    let exclusive = writer.try_lock_exclusive_for(timeout).expect("try_lock");
+    assert!(exclusive.is_some());
+    assert!(reader.try_lock_shared().expect("try_lock_shared").is_none());
+    drop(exclusive);
+    assert!(reader.try_lock_shared().expect("try_lock_shared").is_some());
+}
+
+#[test]
+fn lock_needs_nolock_test() {
+    let path = std::env::temp_dir().join(format!("gdbm-lock-gdbm-{}.db", std::process::id()));
+    let mut db = gdbm::Gdbm::new(&path, 0, gdbm::Open::NEWDB, (S_IRUSR | S_IWUSR) as i32)
+        .expect("Gdbm::new");
+    remove_file(&path).expect("remove_file");
+    assert!(db.lock_shared().is_err());
+    assert!(db.try_lock_exclusive().is_err());
+}
+
+const LOCK_CHILD_ENV: &str = "GDBM_LOCK_CHILD";
+
+/// Not a test of its own: run by `lock_across_processes_test` in a new
+/// process, does nothing otherwise.
+#[test]
+fn lock_child() {
+    let path = match std::env::var_os(LOCK_CHILD_ENV) {
+        Some(path) => std::path::PathBuf::from(path),
+        None => return,
+    };
+    // the child opens its own handle, so it does not share the lock
+    let flags = gdbm::Open::READER | gdbm::Open::NOLOCK;
+    let mut db = gdbm::Gdbm::new(&path, 0, flags, 0).expect("Gdbm::new");
+    assert!(db.try_lock_shared().expect("try_lock_shared").is_none());
+    println!("locked out");
+    let shared = db.lock_shared().expect("lock_shared");
+    assert_eq!(shared.fetch("max_epoch").expect("fetch"), "1609430400");
+}
+
+#[test]
+fn lock_across_processes_test() {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+
+    let (path, mut writer, reader) = nolock_pair("lock-child");
+    drop(reader);
+    let lock = writer.lock_exclusive().expect("lock_exclusive");
+
+    let mut child = Command::new(std::env::current_exe().expect("current_exe"))
+        .args(["--exact", "lock_child", "--test-threads", "1", "--nocapture"])
+        .env(LOCK_CHILD_ENV, &path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("spawn child");
+    // wait until the child has found the database locked
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    let ready = lines.by_ref().any(|line| line.expect("read child").ends_with("locked out"));
+
+    lock.store("max_epoch", &"1609430400".to_string(), true).expect("store");
+    lock.sync();
+    drop(lock);
+
+    lines.for_each(drop);
+    let status = child.wait().expect("wait child");
+    remove_file(&path).expect("remove_file");
+    assert!(ready && status.success(), "child failed");
+}
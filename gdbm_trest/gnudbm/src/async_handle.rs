@@ -0,0 +1,630 @@
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use serde::{Deserialize, Serialize};
+use tokio::runtime::Handle;
+use tokio::sync::oneshot;
+
+use codec::Codec;
+use error::{Error, GdbmResult};
+use super::{GdbmOpener, ReadHandle, RwHandle};
+
+/// The number of entries an [`EntryStream`] reads per trip to the pool.
+const CHUNK_SIZE: usize = 64;
+
+type Job<H> = Box<dyn FnOnce(&mut H) + Send>;
+type Task = Box<dyn FnOnce() + Send>;
+
+/// A read/write database handle for use from async code.
+///
+/// Every operation is queued on the handle and returns a [`Request`] to
+/// await; the queue is worked off with `tokio::task::spawn_blocking`, one
+/// operation per blocking task. This keeps blocking gdbm calls off the async
+/// runtime, and since gdbm's error state is per-thread, each operation runs,
+/// together with its error lookup, on a single blocking thread.
+///
+/// Handles must be opened and used from within a tokio runtime; outside of
+/// one, requests fail with an I/O error.
+///
+/// Clones share the same queue, so their operations are run one at a time
+/// and in the order they were made. The database is closed, on the pool,
+/// once every clone has been dropped.
+///
+/// Created with [`GdbmOpener::readwrite_async`].
+///
+/// [`Request`]: struct.Request.html
+/// [`GdbmOpener::readwrite_async`]: struct.GdbmOpener.html#method.readwrite_async
+///
+/// # Examples
+///
+/// ```no_run,edition2018
+/// # use gnudbm::*;
+/// # async fn run() -> GdbmResult<()> {
+/// let db = GdbmOpener::new().create(true).readwrite_async("klines.db").await?;
+/// db.store("max_epoch", &1609430400u64).await?;
+///
+/// let entry = db.fetch("max_epoch").await?;
+/// assert_eq!(entry.deserialize::<u64>().unwrap(), 1609430400);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AsyncRwHandle {
+    worker: Worker<RwHandle>,
+}
+
+/// A readonly database handle for use from async code.
+///
+/// See [`AsyncRwHandle`] for how operations are run.
+///
+/// [`AsyncRwHandle`]: struct.AsyncRwHandle.html
+#[derive(Debug, Clone)]
+pub struct AsyncReadHandle {
+    worker: Worker<ReadHandle>,
+}
+
+/// A pending operation on an async handle.
+///
+/// The operation is queued as soon as the `Request` is created. If the
+/// `Request` is dropped before the pool gets to it, the operation is
+/// skipped; if it is already running it completes, and its result is
+/// discarded.
+#[derive(Debug)]
+pub struct Request<T> {
+    rx: Option<oneshot::Receiver<GdbmResult<T>>>,
+    err: Option<Error>,
+}
+
+/// An entry read through an async handle.
+///
+/// This owns a copy of the stored bytes, so it can be sent between tasks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryBuf(Vec<u8>);
+
+/// A [`Stream`] over the keys and values of a database, returned by
+/// [`AsyncRwHandle::iter`] and [`AsyncReadHandle::iter`].
+///
+/// Entries are read on the pool a chunk at a time, so other operations
+/// on the same handle can run between chunks. As with [`RwHandle::iter`],
+/// modifying the database while a stream is in progress may cause keys to
+/// be skipped or repeated. Dropping the stream stops the iteration.
+///
+/// [`Stream`]: https://docs.rs/futures-core/0.3/futures_core/stream/trait.Stream.html
+/// [`AsyncRwHandle::iter`]: struct.AsyncRwHandle.html#method.iter
+/// [`AsyncReadHandle::iter`]: struct.AsyncReadHandle.html#method.iter
+/// [`RwHandle::iter`]: struct.RwHandle.html#method.iter
+pub struct EntryStream {
+    next_chunk: Box<dyn Fn(Option<Vec<u8>>) -> Request<Chunk> + Send + Sync>,
+    pending: Option<Request<Chunk>>,
+    buffered: VecDeque<(Vec<u8>, EntryBuf)>,
+    next_key: Option<Vec<u8>>,
+    done: bool,
+}
+
+struct Chunk {
+    entries: Vec<(Vec<u8>, EntryBuf)>,
+    /// The key to continue from, if there are more.
+    next: Option<Vec<u8>>,
+}
+
+/// The operation queue of an async handle. Clones share the queue.
+struct Worker<H: 'static>
+where
+    Owned<H>: Send,
+{
+    shared: Arc<Shared<H>>,
+}
+
+struct Shared<H: 'static>
+where
+    Owned<H>: Send,
+{
+    /// Only taken when the last clone is dropped.
+    handle: Mutex<Option<Owned<H>>>,
+    queue: Mutex<Queue<H>>,
+}
+
+struct Queue<H> {
+    jobs: VecDeque<Job<H>>,
+    /// Whether a pool task to run the next job is pending or running.
+    scheduled: bool,
+    /// Set once a job has panicked; later requests fail.
+    stopped: bool,
+}
+
+/// A gdbm handle owned by an async handle rather than by any thread.
+struct Owned<H>(H);
+
+// As with pooled read handles, gdbm keeps no thread-affine state in a
+// handle; it only must not be used by two threads at once, which the mutex
+// in `Shared` prevents.
+unsafe impl Send for Owned<RwHandle> {}
+unsafe impl Send for Owned<ReadHandle> {}
+
+impl<H> Clone for Worker<H>
+where
+    Owned<H>: Send,
+{
+    fn clone(&self) -> Self {
+        Worker { shared: self.shared.clone() }
+    }
+}
+
+impl<H> fmt::Debug for Worker<H>
+where
+    Owned<H>: Send,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let queue = lock(&self.shared.queue);
+        f.debug_struct("Worker")
+            .field("queued", &queue.jobs.len())
+            .field("stopped", &queue.stopped)
+            .finish()
+    }
+}
+
+impl<H> Worker<H>
+where
+    Owned<H>: Send,
+{
+    /// Opens the database on the pool with `open`, and resolves to the
+    /// handle built by `wrap` once the database is open.
+    fn spawn<F, T>(open: F, wrap: fn(Worker<H>) -> T) -> Request<T>
+    where
+        F: FnOnce() -> GdbmResult<H> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let task: Task = Box::new(move || {
+            let result = open().map(|handle| {
+                wrap(Worker {
+                    shared: Arc::new(Shared {
+                        handle: Mutex::new(Some(Owned(handle))),
+                        queue: Mutex::new(Queue {
+                            jobs: VecDeque::new(),
+                            scheduled: false,
+                            stopped: false,
+                        }),
+                    }),
+                })
+            });
+            let _ = tx.send(result);
+        });
+
+        match spawn_blocking(task) {
+            Ok(()) => Request::new(rx),
+            Err(e) => Request::ready(Err(e.into())),
+        }
+    }
+
+    /// Queues `f` to be run on the pool.
+    fn request<T, F>(&self, f: F) -> Request<T>
+    where
+        F: FnOnce(&mut H) -> GdbmResult<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let job: Job<H> = Box::new(move |handle: &mut H| {
+            // the request was dropped before we got to it
+            if tx.is_closed() {
+                return;
+            }
+            let _ = tx.send(f(handle));
+        });
+
+        let mut queue = lock(&self.shared.queue);
+        if queue.stopped {
+            return Request::ready(Err(worker_gone()));
+        }
+        queue.jobs.push_back(job);
+        if !queue.scheduled {
+            queue.scheduled = true;
+            drop(queue);
+            Shared::schedule(&self.shared);
+        }
+        Request::new(rx)
+    }
+}
+
+impl<H> Shared<H>
+where
+    Owned<H>: Send,
+{
+    /// Hands the next queued job to the pool.
+    fn schedule(this: &Arc<Self>) {
+        let shared = this.clone();
+        if spawn_blocking(Box::new(move || shared.run_next())).is_err() {
+            // dropping the jobs fails their requests
+            this.stop();
+        }
+    }
+
+    /// Runs one job, and schedules the next one, if any, behind the tasks of
+    /// other handles.
+    fn run_next(self: Arc<Self>) {
+        let job = match lock(&self.queue).jobs.pop_front() {
+            Some(job) => job,
+            None => return,
+        };
+        let result = {
+            let mut handle = lock(&self.handle);
+            let handle = &mut handle.as_mut().expect("handle taken while in use").0;
+            panic::catch_unwind(AssertUnwindSafe(|| job(handle)))
+        };
+        if result.is_err() {
+            return self.stop();
+        }
+
+        let mut queue = lock(&self.queue);
+        if queue.jobs.is_empty() {
+            queue.scheduled = false;
+        } else {
+            drop(queue);
+            Shared::schedule(&self);
+        }
+    }
+
+    fn stop(&self) {
+        let mut queue = lock(&self.queue);
+        queue.stopped = true;
+        queue.scheduled = false;
+        queue.jobs.clear();
+    }
+}
+
+impl<H> Drop for Shared<H>
+where
+    Owned<H>: Send,
+{
+    fn drop(&mut self) {
+        let handle = self.handle.get_mut().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(handle) = handle {
+            // closing a writer syncs the file, so keep it off the caller's
+            // thread; if the pool can not take the task it closes it here
+            let _ = spawn_blocking(Box::new(move || drop(handle)));
+        }
+    }
+}
+
+/// Runs `task` on tokio's blocking thread pool. This fails outside of a
+/// tokio runtime.
+fn spawn_blocking(task: Task) -> io::Result<()> {
+    let runtime = Handle::try_current().map_err(io::Error::other)?;
+    // a panicking job stops its own handle, see `run_next`
+    drop(runtime.spawn_blocking(task));
+    Ok(())
+}
+
+// none of these locks is held while calling into gdbm or user code, except
+// the handle lock, whose panics stop the handle; so a poisoned lock is still
+// consistent
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+impl AsyncRwHandle {
+    /// Inserts a key value pair into the database, replacing any existing
+    /// value for that key. See [`RwHandle::store`].
+    ///
+    /// The value is serialized before this returns.
+    ///
+    /// [`RwHandle::store`]: struct.RwHandle.html#method.store
+    pub fn store<K, V>(&self, key: K, value: &V) -> Request<()>
+    where
+        K: AsRef<[u8]>,
+        V: ?Sized + Serialize,
+    {
+        match bincode::serialize(value) {
+            Ok(bytes) => self.store_bytes(key.as_ref().to_owned(), bytes),
+            Err(e) => Request::ready(Err(e.into())),
+        }
+    }
+
+    /// Inserts a key value pair into the database, encoding `value` with
+    /// `codec`. See [`RwHandle::store_with`].
+    ///
+    /// [`RwHandle::store_with`]: struct.RwHandle.html#method.store_with
+    pub fn store_with<K, V, C>(&self, codec: &C, key: K, value: &V) -> Request<()>
+    where
+        K: AsRef<[u8]>,
+        C: Codec<V>,
+    {
+        match codec.encode(value) {
+            Ok(bytes) => self.store_bytes(key.as_ref().to_owned(), bytes),
+            Err(e) => Request::ready(Err(e)),
+        }
+    }
+
+    fn store_bytes(&self, key: Vec<u8>, bytes: Vec<u8>) -> Request<()> {
+        self.worker.request(move |db| db.store_bytes(&key, &bytes, true).map(|_| ()))
+    }
+
+    /// Fetches an item from the database. See [`RwHandle::fetch`].
+    ///
+    /// [`RwHandle::fetch`]: struct.RwHandle.html#method.fetch
+    pub fn fetch<K: AsRef<[u8]>>(&self, key: K) -> Request<EntryBuf> {
+        let key = key.as_ref().to_owned();
+        self.worker.request(move |db| fetch_buf(db, &key))
+    }
+
+    /// Removes an entry from the database. See [`RwHandle::remove`].
+    ///
+    /// [`RwHandle::remove`]: struct.RwHandle.html#method.remove
+    pub fn remove<K: AsRef<[u8]>>(&self, key: K) -> Request<bool> {
+        let key = key.as_ref().to_owned();
+        self.worker.request(move |db| db.remove(&key))
+    }
+
+    /// Checks the database for the existence of `key`.
+    pub fn contains_key<K: AsRef<[u8]>>(&self, key: K) -> Request<bool> {
+        let key = key.as_ref().to_owned();
+        self.worker.request(move |db| db.contains_key(&key))
+    }
+
+    /// Counts the number of items in this database. This is not cached.
+    pub fn count(&self) -> Request<usize> {
+        self.worker.request(|db| db.count())
+    }
+
+    /// Synchronizes the changes in the database with the file on disk.
+    pub fn sync(&self) -> Request<()> {
+        self.worker.request(|db| {
+            db.sync();
+            Ok(())
+        })
+    }
+
+    /// Returns a stream over the keys and values in this database.
+    pub fn iter(&self) -> EntryStream {
+        let worker = self.worker.clone();
+        EntryStream::new(Box::new(move |from| {
+            worker.request(move |db| read_chunk(db, from))
+        }))
+    }
+}
+
+impl AsyncReadHandle {
+    /// Fetches an item from the database. See [`RwHandle::fetch`].
+    ///
+    /// [`RwHandle::fetch`]: struct.RwHandle.html#method.fetch
+    pub fn fetch<K: AsRef<[u8]>>(&self, key: K) -> Request<EntryBuf> {
+        let key = key.as_ref().to_owned();
+        self.worker.request(move |db| fetch_buf(&db.0, &key))
+    }
+
+    /// Checks the database for the existence of `key`.
+    pub fn contains_key<K: AsRef<[u8]>>(&self, key: K) -> Request<bool> {
+        let key = key.as_ref().to_owned();
+        self.worker.request(move |db| db.0.contains_key(&key))
+    }
+
+    /// Counts the number of items in this database. This is not cached.
+    pub fn count(&self) -> Request<usize> {
+        self.worker.request(|db| db.count())
+    }
+
+    /// Returns a stream over the keys and values in this database.
+    pub fn iter(&self) -> EntryStream {
+        let worker = self.worker.clone();
+        EntryStream::new(Box::new(move |from| {
+            worker.request(move |db| read_chunk(&db.0, from))
+        }))
+    }
+}
+
+impl GdbmOpener {
+    /// Opens the file at `path` on the blocking pool with the options
+    /// provided, resolving to an [`AsyncRwHandle`].
+    ///
+    /// This must be called from within a tokio runtime.
+    ///
+    /// [`AsyncRwHandle`]: struct.AsyncRwHandle.html
+    pub fn readwrite_async<P: AsRef<Path>>(&self, path: P) -> Request<AsyncRwHandle> {
+        let opener = self.clone();
+        let path = path.as_ref().to_owned();
+        Worker::spawn(move || opener.readwrite(&path), |worker| AsyncRwHandle { worker })
+    }
+
+    /// Opens the file at `path` on the blocking pool with the options
+    /// provided, resolving to an [`AsyncReadHandle`].
+    ///
+    /// This must be called from within a tokio runtime, and ignores any
+    /// settings applied by `create` or `overwrite`.
+    ///
+    /// [`AsyncReadHandle`]: struct.AsyncReadHandle.html
+    pub fn readonly_async<P: AsRef<Path>>(&self, path: P) -> Request<AsyncReadHandle> {
+        let mut opener = self.clone();
+        let path = path.as_ref().to_owned();
+        Worker::spawn(move || opener.readonly(&path), |worker| AsyncReadHandle { worker })
+    }
+}
+
+impl<T> Request<T> {
+    fn new(rx: oneshot::Receiver<GdbmResult<T>>) -> Self {
+        Request { rx: Some(rx), err: None }
+    }
+
+    fn ready(result: GdbmResult<T>) -> Self {
+        match result {
+            Ok(value) => {
+                let (tx, rx) = oneshot::channel();
+                let _ = tx.send(Ok(value));
+                Request::new(rx)
+            }
+            Err(e) => Request { rx: None, err: Some(e) },
+        }
+    }
+}
+
+impl<T> Future for Request<T> {
+    type Output = GdbmResult<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if let Some(e) = this.err.take() {
+            return Poll::Ready(Err(e));
+        }
+        let rx = match this.rx.as_mut() {
+            Some(rx) => rx,
+            None => return Poll::Ready(Err(worker_gone())),
+        };
+        match Pin::new(rx).poll(cx) {
+            Poll::Ready(Ok(result)) => Poll::Ready(result),
+            // the job panicked or the handle stopped, dropping our sender
+            Poll::Ready(Err(_)) => Poll::Ready(Err(worker_gone())),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl EntryBuf {
+    /// Returns the contents of the entry as a slice of bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Consumes the entry, returning its bytes.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    /// Attempts to deserialize this entry with bincode. See
+    /// [`Entry::deserialize`].
+    ///
+    /// [`Entry::deserialize`]: struct.Entry.html#method.deserialize
+    pub fn deserialize<'de, T>(&'de self) -> Result<T, bincode::Error>
+    where
+        T: Deserialize<'de>,
+    {
+        bincode::deserialize(&self.0)
+    }
+
+    /// Decodes this entry with `codec`. See [`Entry::decode`].
+    ///
+    /// [`Entry::decode`]: struct.Entry.html#method.decode
+    pub fn decode<T, C: Codec<T>>(&self, codec: &C) -> GdbmResult<T> {
+        codec.decode(&self.0)
+    }
+}
+
+impl EntryStream {
+    fn new(next_chunk: Box<dyn Fn(Option<Vec<u8>>) -> Request<Chunk> + Send + Sync>) -> Self {
+        EntryStream {
+            next_chunk,
+            pending: None,
+            buffered: VecDeque::new(),
+            next_key: None,
+            done: false,
+        }
+    }
+}
+
+impl Stream for EntryStream {
+    type Item = GdbmResult<(Vec<u8>, EntryBuf)>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(entry) = this.buffered.pop_front() {
+                return Poll::Ready(Some(Ok(entry)));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+
+            if this.pending.is_none() {
+                this.pending = Some((this.next_chunk)(this.next_key.take()));
+            }
+            let result = match Pin::new(this.pending.as_mut().unwrap()).poll(cx) {
+                Poll::Ready(result) => result,
+                Poll::Pending => return Poll::Pending,
+            };
+            this.pending = None;
+
+            match result {
+                Ok(chunk) => {
+                    this.done = chunk.next.is_none();
+                    this.next_key = chunk.next;
+                    this.buffered.extend(chunk.entries);
+                }
+                Err(e) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for EntryStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EntryStream")
+            .field("buffered", &self.buffered.len())
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+fn fetch_buf(db: &RwHandle, key: &[u8]) -> GdbmResult<EntryBuf> {
+    db.fetch(key).map(|entry| EntryBuf(entry.as_bytes().to_vec()))
+}
+
+/// Reads up to `CHUNK_SIZE` entries, starting at `from`, or at the first key
+/// if it is `None`.
+fn read_chunk(db: &RwHandle, from: Option<Vec<u8>>) -> GdbmResult<Chunk> {
+    let mut entries = Vec::with_capacity(CHUNK_SIZE);
+    let mut key = match from {
+        Some(from) => Some(from),
+        None => db.first_key(),
+    };
+
+    while let Some(k) = key {
+        key = db.next_key(&k);
+        match fetch_buf(db, &k) {
+            Ok(value) => entries.push((k, value)),
+            // removed since gdbm handed us the key
+            Err(ref e) if e.is_no_record() => {}
+            Err(e) => return Err(e),
+        }
+        if entries.len() == CHUNK_SIZE {
+            break;
+        }
+    }
+
+    Ok(Chunk { entries, next: key })
+}
+
+fn worker_gone() -> Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "gnudbm async handle has stopped").into()
+}
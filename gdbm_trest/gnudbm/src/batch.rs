@@ -0,0 +1,225 @@
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use codec::Codec;
+use super::{GdbmResult, RwHandle};
+
+/// A set of stores and removals that is applied to a database atomically.
+///
+/// Created with [`RwHandle::batch`]. Operations are only recorded in memory
+/// until [`commit`] is called; dropping the batch (or calling [`rollback`])
+/// leaves the database untouched.
+///
+/// On commit, the database file is copied to a shadow file next to it
+/// (`<name>.batch`), the operations are applied to the copy, the copy is
+/// flushed to disk and then renamed over the original. Because the rename is
+/// atomic, a crash at any point during the commit leaves the database with
+/// either all of the batch or none of it.
+///
+/// Every step that can fail happens before the rename; after it, the handle
+/// simply switches over to the already open shadow file.
+///
+/// # Note
+///
+/// The handle switches to the shadow file after a successful commit.
+/// Options given to the [`GdbmOpener`] are applied to it, but options set
+/// with methods such as [`RwHandle::set_cache_size`] must be set again.
+///
+/// The handle keeps its gdbm writer lock for the whole commit, so other
+/// writers can not open the database in the meantime. This does not hold
+/// for handles opened with `GDBM_NOLOCK`, whose writes between the copy and
+/// the rename are lost. Handles that were already open elsewhere keep
+/// reading the replaced file until they are reopened.
+///
+/// [`RwHandle::batch`]: struct.RwHandle.html#method.batch
+/// [`GdbmOpener`]: struct.GdbmOpener.html
+/// [`RwHandle::set_cache_size`]: struct.RwHandle.html#method.set_cache_size
+/// [`commit`]: #method.commit
+/// [`rollback`]: #method.rollback
+#[derive(Debug)]
+pub struct WriteBatch<'a> {
+    db: &'a mut RwHandle,
+    ops: Vec<BatchOp>,
+}
+
+#[derive(Debug)]
+enum BatchOp {
+    Store(Vec<u8>, Vec<u8>),
+    Remove(Vec<u8>),
+}
+
+impl<'a> WriteBatch<'a> {
+    pub(crate) fn new(db: &'a mut RwHandle) -> Self {
+        WriteBatch { db, ops: Vec::new() }
+    }
+
+    /// Adds a store to the batch, replacing any existing value for `key`
+    /// when the batch is committed.
+    ///
+    /// Returns an error if `value` can not be serialized.
+    pub fn store<K, V>(&mut self, key: K, value: &V) -> GdbmResult<()>
+    where
+        K: AsRef<[u8]>,
+        V: ?Sized + Serialize,
+    {
+        let bytes = bincode::serialize(value)?;
+        self.ops.push(BatchOp::Store(key.as_ref().to_owned(), bytes));
+        Ok(())
+    }
+
+    /// Adds a store to the batch, encoding `value` with `codec` rather than
+    /// bincode. Use this on databases written through a [`TypedHandle`] or
+    /// [`RwHandle::store_with`], so the value can be read back with the same
+    /// codec.
+    ///
+    /// Returns an error if `value` can not be encoded.
+    ///
+    /// [`TypedHandle`]: struct.TypedHandle.html
+    /// [`RwHandle::store_with`]: struct.RwHandle.html#method.store_with
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use gnudbm::*;
+    /// # let mut db = RwHandle::dummy();
+    /// let mut batch = db.batch();
+    /// batch.store_with(&Json, "1609430400", &vec![36.5, 36.7]).unwrap();
+    /// batch.store_with(&Json, "max_epoch", &1609430400u64).unwrap();
+    /// batch.commit().unwrap();
+    /// ```
+    pub fn store_with<K, V, C>(&mut self, codec: &C, key: K, value: &V) -> GdbmResult<()>
+    where
+        K: AsRef<[u8]>,
+        C: Codec<V>,
+    {
+        let bytes = codec.encode(value)?;
+        self.ops.push(BatchOp::Store(key.as_ref().to_owned(), bytes));
+        Ok(())
+    }
+
+    /// Adds a removal to the batch. Removing a key that does not exist is
+    /// not an error.
+    pub fn remove<K: AsRef<[u8]>>(&mut self, key: K) {
+        self.ops.push(BatchOp::Remove(key.as_ref().to_owned()));
+    }
+
+    /// Returns the number of operations in the batch.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Returns `true` if the batch contains no operations.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Applies every operation in the batch to the database.
+    ///
+    /// If this returns an error the database, and this handle, are left as
+    /// they were before the batch was created.
+    pub fn commit(self) -> GdbmResult<()> {
+        if self.ops.is_empty() {
+            return Ok(());
+        }
+
+        self.db.sync();
+        let path = self.db.path().to_owned();
+        let shadow = shadow_path(&path);
+
+        let copy = match self.write_shadow(&path, &shadow) {
+            Ok(copy) => copy,
+            Err(e) => {
+                let _ = fs::remove_file(&shadow);
+                return Err(e);
+            }
+        };
+        if let Err(e) = fs::rename(&shadow, &path) {
+            drop(copy);
+            let _ = fs::remove_file(&shadow);
+            return Err(e.into());
+        }
+        sync_parent_dir(&path);
+
+        // the open shadow handle now refers to the file at `path`
+        self.db.replace_handle(copy);
+        if let Some(ref index) = self.db.index {
+            for op in &self.ops {
+                match *op {
+                    BatchOp::Store(ref key, _) => index.insert(key),
+                    BatchOp::Remove(ref key) => index.remove(key),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Discards the batch without touching the database. This is the same as
+    /// dropping it.
+    pub fn rollback(self) {}
+
+    /// Writes the batch to a copy of the database at `shadow` and returns
+    /// the still open handle to it.
+    fn write_shadow(&self, path: &Path, shadow: &Path) -> GdbmResult<RwHandle> {
+        fs::copy(path, shadow)?;
+        let mut copy = self.db.open_sibling(shadow)?;
+        self.db.tuning.apply(&copy)?;
+        for op in &self.ops {
+            match *op {
+                BatchOp::Store(ref key, ref value) => {
+                    copy.store_bytes(key, value, true)?;
+                }
+                BatchOp::Remove(ref key) => {
+                    copy.remove(key)?;
+                }
+            }
+        }
+        copy.sync();
+        File::open(shadow)?.sync_all()?;
+        Ok(copy)
+    }
+}
+
+/// The shadow file a batch is written to before it replaces `path`.
+fn shadow_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".batch");
+    PathBuf::from(name)
+}
+
+/// Makes the rename of the shadow file durable. Failure only weakens the
+/// guarantee after a power loss, so it is not reported.
+fn sync_parent_dir(path: &Path) {
+    let parent = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    if let Ok(dir) = File::open(parent) {
+        let _ = dir.sync_all();
+    }
+}
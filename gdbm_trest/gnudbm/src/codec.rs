@@ -0,0 +1,123 @@
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use error::{Error, GdbmResult};
+
+/// Converts values of type `V` to and from their stored representation.
+///
+/// [`RwHandle::store`] and [`Entry::deserialize`] always use [`Bincode`];
+/// other codecs are used through [`TypedHandle`], [`RwHandle::store_with`]
+/// and [`Entry::decode`].
+///
+/// [`Bincode`]: struct.Bincode.html
+/// [`TypedHandle`]: struct.TypedHandle.html
+/// [`RwHandle::store`]: struct.RwHandle.html#method.store
+/// [`RwHandle::store_with`]: struct.RwHandle.html#method.store_with
+/// [`Entry::deserialize`]: struct.Entry.html#method.deserialize
+/// [`Entry::decode`]: struct.Entry.html#method.decode
+pub trait Codec<V> {
+    /// Encodes `value` into the bytes that will be stored.
+    fn encode(&self, value: &V) -> GdbmResult<Vec<u8>>;
+
+    /// Decodes a value previously written by `encode`.
+    fn decode(&self, bytes: &[u8]) -> GdbmResult<V>;
+}
+
+/// Encodes values with [bincode]. This is the format used by
+/// `RwHandle::store`.
+///
+/// [bincode]: https://github.com/TyOverby/bincode
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Bincode;
+
+/// Encodes values as JSON text.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Json;
+
+/// Encodes values as [MessagePack], with structs written as maps so that
+/// fields can be added without breaking existing data.
+///
+/// [MessagePack]: https://msgpack.org
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MsgPack;
+
+/// Stores bytes or strings as they are, without any framing.
+///
+/// This is implemented for `Vec<u8>` and `String`; decoding a `String`
+/// fails if the stored bytes are not valid UTF-8.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Raw;
+
+impl<V: Serialize + DeserializeOwned> Codec<V> for Bincode {
+    fn encode(&self, value: &V) -> GdbmResult<Vec<u8>> {
+        Ok(bincode::serialize(value)?)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> GdbmResult<V> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+impl<V: Serialize + DeserializeOwned> Codec<V> for Json {
+    fn encode(&self, value: &V) -> GdbmResult<Vec<u8>> {
+        serde_json::to_vec(value).map_err(Error::codec)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> GdbmResult<V> {
+        serde_json::from_slice(bytes).map_err(Error::codec)
+    }
+}
+
+impl<V: Serialize + DeserializeOwned> Codec<V> for MsgPack {
+    fn encode(&self, value: &V) -> GdbmResult<Vec<u8>> {
+        rmp_serde::to_vec_named(value).map_err(Error::codec)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> GdbmResult<V> {
+        rmp_serde::from_slice(bytes).map_err(Error::codec)
+    }
+}
+
+impl Codec<Vec<u8>> for Raw {
+    fn encode(&self, value: &Vec<u8>) -> GdbmResult<Vec<u8>> {
+        Ok(value.clone())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> GdbmResult<Vec<u8>> {
+        Ok(bytes.to_vec())
+    }
+}
+
+impl Codec<String> for Raw {
+    fn encode(&self, value: &String) -> GdbmResult<Vec<u8>> {
+        Ok(value.as_bytes().to_vec())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> GdbmResult<String> {
+        String::from_utf8(bytes.to_vec()).map_err(Error::codec)
+    }
+}
@@ -0,0 +1,150 @@
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use std::vec;
+
+use error::{Error, GdbmResult};
+use gdbm_sys;
+use super::{Entry, RwHandle};
+
+/// An iterator that removes and yields the entries matching a predicate,
+/// returned by [`RwHandle::drain_filter`].
+///
+/// The matching keys are collected before anything is removed, so removals
+/// can not disturb gdbm's key order. Each entry is removed when it is
+/// yielded; if the iterator is dropped early, the matching entries that
+/// were not yet yielded stay in the database.
+///
+/// [`RwHandle::drain_filter`]: struct.RwHandle.html#method.drain_filter
+#[derive(Debug)]
+pub struct DrainFilter<'a> {
+    db: &'a mut RwHandle,
+    keys: vec::IntoIter<Vec<u8>>,
+}
+
+impl RwHandle {
+    /// Removes every entry for which `pred` returns `true`, returning an
+    /// iterator over the removed keys and values.
+    ///
+    /// Unlike removing entries while walking [`RwHandle::iter`], which can
+    /// cause gdbm to skip or repeat keys, this sees every entry exactly once.
+    /// The predicate is run on every entry when this is called; the entries
+    /// are removed as the returned [`DrainFilter`] is consumed.
+    ///
+    /// [`RwHandle::iter`]: struct.RwHandle.html#method.iter
+    /// [`DrainFilter`]: struct.DrainFilter.html
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use gnudbm::*;
+    /// # let mut db = RwHandle::dummy();
+    /// let stale = db.drain_filter(|key, _| key.starts_with(b"tmp_")).unwrap();
+    /// for removed in stale {
+    ///     let (key, entry) = removed.unwrap();
+    ///     println!("removed {:?}: {} bytes", key, entry.as_bytes().len());
+    /// }
+    /// ```
+    pub fn drain_filter<F>(&mut self, mut pred: F) -> GdbmResult<DrainFilter<'_>>
+    where
+        F: FnMut(&[u8], &Entry) -> bool,
+    {
+        let mut matched = Vec::new();
+        let mut key = self.first_key();
+        while let Some(k) = key {
+            key = self.next_key(&k);
+            let entry = match self.fetch(&k) {
+                Ok(entry) => entry,
+                Err(ref e) if e.is_no_record() => continue,
+                Err(e) => return Err(e),
+            };
+            if pred(&k, &entry) {
+                matched.push(k);
+            }
+        }
+        Ok(DrainFilter { db: self, keys: matched.into_iter() })
+    }
+
+    /// Keeps only the entries for which `keep` returns `true`, removing the
+    /// rest. Returns the number of entries removed.
+    ///
+    /// See [`RwHandle::drain_filter`] for how this differs from removing
+    /// entries during iteration.
+    ///
+    /// [`RwHandle::drain_filter`]: #method.drain_filter
+    ///
+    /// # Examples
+    ///
+    /// Prune klines that are older than 30 days:
+    ///
+    /// ```no_run
+    /// # use gnudbm::*;
+    /// # let mut db = RwHandle::dummy();
+    /// let cutoff: u64 = 1609430400 - 30 * 24 * 60 * 60;
+    /// db.retain(|key, _| match std::str::from_utf8(key).ok().and_then(|k| k.parse::<u64>().ok()) {
+    ///     Some(epoch) => epoch >= cutoff,
+    ///     // keep `max_epoch` and anything else that is not an epoch
+    ///     None => true,
+    /// }).unwrap();
+    /// ```
+    pub fn retain<F>(&mut self, mut keep: F) -> GdbmResult<usize>
+    where
+        F: FnMut(&[u8], &Entry) -> bool,
+    {
+        let mut removed = 0;
+        for result in self.drain_filter(|key, entry| !keep(key, entry))? {
+            result?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+}
+
+impl<'a> Iterator for DrainFilter<'a> {
+    type Item = GdbmResult<(Vec<u8>, Entry<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for key in &mut self.keys {
+            // fetched directly, as the entry must outlive this borrow of `db`
+            let datum = unsafe { gdbm_sys::gdbm_fetch(self.db.handle, key[..].into()) };
+            if datum.dptr.is_null() {
+                match Error::from_handle(self.db.handle) {
+                    // removed since the scan
+                    ref e if e.is_no_record() => continue,
+                    e => return Some(Err(e)),
+                }
+            }
+            let entry = Entry::new(datum);
+            return match self.db.remove(&key) {
+                Ok(_) => Some(Ok((key, entry))),
+                Err(e) => Some(Err(e)),
+            };
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.keys.len()))
+    }
+}
@@ -0,0 +1,130 @@
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::ptr;
+
+use error::{Error, GdbmResult};
+use gdbm_sys;
+use index::KeyIndex;
+use super::{GdbmOpener, ReadHandle, RwHandle, DEFAULT_MODE};
+
+/// The format of a dump written by [`RwHandle::export_to`].
+///
+/// Either format can be read back with [`GdbmOpener::import_from`], by any
+/// build of gdbm recent enough to have `gdbm_load` (1.11 and later).
+///
+/// [`RwHandle::export_to`]: struct.RwHandle.html#method.export_to
+/// [`GdbmOpener::import_from`]: struct.GdbmOpener.html#method.import_from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// gdbm's ASCII dump format. Keys and values are base64 encoded, and the
+    /// header records the original file name and permissions. This format
+    /// is independent of the machine's byte order and word size, so it is
+    /// the one to use when moving a database between machines.
+    Ascii,
+    /// gdbm's binary ("flat") dump format, the same format written by the
+    /// older `gdbm_export`. It is more compact but not portable across
+    /// machines with different integer sizes or byte orders.
+    Binary,
+}
+
+impl RwHandle {
+    /// Writes every key and value in this database to a dump file at
+    /// `path`, replacing the file if it already exists.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use gnudbm::*;
+    /// # let db = RwHandle::dummy();
+    /// db.export_to("klines.dump", DumpFormat::Ascii).unwrap();
+    ///
+    /// let copy = GdbmOpener::new()
+    ///     .create(true)
+    ///     .import_from("klines.dump", "klines.db")
+    ///     .unwrap();
+    /// assert_eq!(copy.count().unwrap(), db.count().unwrap());
+    /// ```
+    pub fn export_to<P: AsRef<Path>>(&self, path: P, format: DumpFormat) -> GdbmResult<()> {
+        let path = CString::new(path.as_ref().as_os_str().as_bytes())?;
+        let format = match format {
+            DumpFormat::Ascii => gdbm_sys::GDBM_DUMP_FMT_ASCII,
+            DumpFormat::Binary => gdbm_sys::GDBM_DUMP_FMT_BINARY,
+        };
+        let result = unsafe {
+            gdbm_sys::gdbm_dump(self.handle, path.as_ptr(), format as i32,
+                                gdbm_sys::GDBM_NEWDB as i32, DEFAULT_MODE)
+        };
+        if result != 0 {
+            Err(Error::from_handle(self.handle))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl ReadHandle {
+    /// Writes every key and value in this database to a dump file at
+    /// `path`. See [`RwHandle::export_to`] for more information.
+    ///
+    /// [`RwHandle::export_to`]: struct.RwHandle.html#method.export_to
+    pub fn export_to<P: AsRef<Path>>(&self, path: P, format: DumpFormat) -> GdbmResult<()> {
+        self.0.export_to(path, format)
+    }
+}
+
+impl GdbmOpener {
+    /// Opens the database at `path` with the options provided and loads
+    /// every key and value from the dump file at `dump` into it, returning
+    /// a read/write handle.
+    ///
+    /// The format of the dump is detected automatically. Entries in the dump
+    /// replace existing entries with the same key. The file name, owner and
+    /// mode recorded in an ASCII dump are ignored; the database is always
+    /// written to `path`.
+    pub fn import_from<P, Q>(&self, dump: P, path: Q) -> GdbmResult<RwHandle>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let dump = CString::new(dump.as_ref().as_os_str().as_bytes())?;
+        let mut db = self.readwrite(path)?;
+        let meta_mask = gdbm_sys::GDBM_META_MASK_MODE | gdbm_sys::GDBM_META_MASK_OWNER;
+        let result = unsafe {
+            gdbm_sys::gdbm_load(&mut db.handle, dump.as_ptr(), gdbm_sys::GDBM_REPLACE as i32,
+                                meta_mask as i32, ptr::null_mut())
+        };
+        if result != 0 {
+            return Err(Error::from_handle(db.handle));
+        }
+        // the index was built before the load
+        if db.index.is_some() {
+            db.index = Some(KeyIndex::build(&db));
+        }
+        Ok(db)
+    }
+}
@@ -24,6 +24,7 @@
 
 use std::fmt;
 use std::ffi::{CStr, NulError};
+use std::io;
 
 use bincode::Error as BincodeError;
 
@@ -31,22 +32,14 @@ use gdbm_sys;
 
 type ErrorCode = u32;
 
-//TODO: merge with Error
-/// An error originating in the gdbm C library.
-#[derive(Debug)]
-pub enum GdbmError {
-    /// The file was not a valid database.
-    InvalidDatabase,
-    /// Database is in inconsistent state and needs recovery.
-    NeedsRecovery,
-    /// The end of the file was reached unexpectedly. This probably indicates
-    /// Database corruption.
-    UnexpectedEOF,
-    /// Another gdbm error.
-    Other(ErrorCode),
-}
-
 /// An error when interacting with a database.
+///
+/// Every error code reported by the gdbm C library has its own variant.
+/// Where gdbm reports that a system call failed, the variant carries the
+/// underlying `io::Error`, which is also returned by
+/// [`source`](https://doc.rust-lang.org/std/error/trait.Error.html#method.source).
+/// Errors wrapping another error leave it out of their `Display` output and
+/// return it from `source` instead.
 #[derive(Debug)]
 pub enum Error {
     /// The path was not a valid C String.
@@ -55,49 +48,312 @@ pub enum Error {
     KeyExists,
     /// No item with this key exists in the database.
     NoRecord,
+    /// A sorted key scan was requested on a handle opened without
+    /// [`GdbmOpener::key_index`](../struct.GdbmOpener.html#method.key_index).
+    NoKeyIndex,
     /// An error occured while encoding to or decoding from binary.
     Bincode(BincodeError),
-    /// An error originating in the gdbm C library.
-    Internal(GdbmError),
+    /// An error occured in a [`Codec`](../trait.Codec.html) other than bincode.
+    Codec(Box<dyn std::error::Error + Send + Sync>),
+    /// An I/O error outside of gdbm, for instance while writing the shadow
+    /// file of a [`WriteBatch`](../struct.WriteBatch.html).
+    Io(io::Error),
+    /// [`RwHandle::recover`](../struct.RwHandle.html#method.recover) gave up
+    /// after reaching one of the limits in its options.
+    RecoveryLimit,
+    /// An option given to a [`GdbmOpener`](../struct.GdbmOpener.html) needs a
+    /// newer version of libgdbm than gnudbm was built for; see the `v1_20`
+    /// and `v1_21` features.
+    Unsupported {
+        /// The name of the `GdbmOpener` method.
+        option: &'static str,
+        /// The first gdbm version with support, as `(major, minor)`.
+        requires: (i32, i32),
+    },
+    /// gdbm could not allocate memory.
+    OutOfMemory,
+    /// The requested block size was not valid.
+    BlockSize,
+    /// The database file could not be opened.
+    FileOpen(io::Error),
+    /// Writing to the database file failed.
+    FileWrite(io::Error),
+    /// Seeking in the database file failed.
+    FileSeek(io::Error),
+    /// Reading from the database file failed.
+    FileRead(io::Error),
+    /// The file is not a gdbm database.
+    BadMagicNumber,
+    /// The file is empty, and was opened for reading.
+    EmptyDatabase,
+    /// The file could not be locked for reading, because another process
+    /// has it open for writing.
+    ReadLockFailed,
+    /// The file could not be locked for writing, because another process
+    /// has it open.
+    WriteLockFailed,
+    /// A delete was attempted through a reader.
+    ReaderCantDelete,
+    /// A store was attempted through a reader.
+    ReaderCantStore,
+    /// A reorganization was attempted through a reader.
+    ReaderCantReorganize,
+    /// Reorganizing the database failed.
+    ReorganizeFailed,
+    /// A store without replacement found the key already present.
+    CannotReplace,
+    /// The key or value passed to gdbm was malformed.
+    MalformedData,
+    /// An option that can only be set once was already set.
+    OptionAlreadySet,
+    /// An option was unknown, or given an invalid value.
+    BadOptionValue,
+    /// The database was written on a machine with a different byte order.
+    ByteSwapped,
+    /// The database was written by a gdbm build with a different `off_t`
+    /// size.
+    BadFileOffset,
+    /// The flags passed when opening the database were invalid.
+    BadOpenFlags,
+    /// The database file could not be stat'ed.
+    FileStat(io::Error),
+    /// The end of the file was reached unexpectedly. This probably indicates
+    /// database corruption.
+    UnexpectedEof,
+    /// A database name was required, but not given.
+    NoDbName,
+    /// The owner of a file could not be restored, e.g. while loading a dump.
+    FileOwner,
+    /// The mode of a file could not be restored, e.g. while loading a dump.
+    FileMode,
+    /// The database is in an inconsistent state and needs recovery.
+    NeedsRecovery,
+    /// A backup copy of the database could not be created.
+    BackupFailed(io::Error),
+    /// The bucket directory would grow beyond its maximum size.
+    DirOverflow,
+    /// A bucket header in the database file is malformed.
+    BadBucket,
+    /// The database file header is malformed.
+    BadHeader,
+    /// An avail block in the database file is malformed.
+    BadAvail,
+    /// The hash table in the database file is malformed.
+    BadHashTable,
+    /// A bucket directory entry in the database file is invalid.
+    BadDirEntry,
+    /// Closing the database file failed.
+    FileClose(io::Error),
+    /// Syncing the database file to disk failed.
+    FileSync(io::Error),
+    /// Truncating the database file failed.
+    FileTruncate(io::Error),
+    /// gdbm's bucket cache is in an inconsistent state.
+    BucketCacheCorrupted,
+    /// A hash entry in a bucket is malformed.
+    BadHashEntry,
+    /// Cloning a crash tolerance snapshot failed.
+    SnapshotClone(io::Error),
+    /// The real path of the database file could not be resolved.
+    RealPath(io::Error),
+    /// A gdbm function was called incorrectly.
+    Usage(io::Error),
+    /// An error code not known to this version of gnudbm.
+    Unknown(ErrorCode),
 }
 
 /// The result type for Database operations.
 pub type GdbmResult<T> = Result<T, Error>;
 
-impl GdbmError {
+impl Error {
     /// Returns the last error reported by gdbm in the current thread.
+    ///
+    /// Only for calls that fail without a handle, such as opening a file;
+    /// `errno` must not have been touched since the failed call. Use
+    /// `from_handle` wherever a handle is available.
     pub (crate) fn from_last() -> Self {
-        last_errno().into()
+        Error::from_code(last_errno(), io::Error::last_os_error())
     }
-}
 
-impl Error {
-    /// Returns the last error reported by gdbm in the current thread.
-    pub (crate) fn from_last() -> Self {
-        last_errno().into()
+    /// Returns the last error reported by gdbm for `dbf`, along with the
+    /// system error gdbm saved for it, if any.
+    pub (crate) fn from_handle(dbf: gdbm_sys::GDBM_FILE) -> Self {
+        let (code, syserr) = unsafe {
+            (gdbm_sys::gdbm_last_errno(dbf), gdbm_sys::gdbm_last_syserr(dbf))
+        };
+        Error::from_code(code as ErrorCode, sys_error(syserr))
+    }
+
+    pub (crate) fn codec<E>(src: E) -> Self
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Error::Codec(src.into())
+    }
+
+    /// Builds the error for a gdbm error code; `sys` is used as the source
+    /// of errors that gdbm reports alongside a failed system call.
+    fn from_code(code: ErrorCode, sys: io::Error) -> Self {
+        match code {
+            gdbm_sys::GDBM_MALLOC_ERROR => Error::OutOfMemory,
+            gdbm_sys::GDBM_BLOCK_SIZE_ERROR => Error::BlockSize,
+            gdbm_sys::GDBM_FILE_OPEN_ERROR => Error::FileOpen(sys),
+            gdbm_sys::GDBM_FILE_WRITE_ERROR => Error::FileWrite(sys),
+            gdbm_sys::GDBM_FILE_SEEK_ERROR => Error::FileSeek(sys),
+            gdbm_sys::GDBM_FILE_READ_ERROR => Error::FileRead(sys),
+            gdbm_sys::GDBM_BAD_MAGIC_NUMBER => Error::BadMagicNumber,
+            gdbm_sys::GDBM_EMPTY_DATABASE => Error::EmptyDatabase,
+            gdbm_sys::GDBM_CANT_BE_READER => Error::ReadLockFailed,
+            gdbm_sys::GDBM_CANT_BE_WRITER => Error::WriteLockFailed,
+            gdbm_sys::GDBM_READER_CANT_DELETE => Error::ReaderCantDelete,
+            gdbm_sys::GDBM_READER_CANT_STORE => Error::ReaderCantStore,
+            gdbm_sys::GDBM_READER_CANT_REORGANIZE => Error::ReaderCantReorganize,
+            gdbm_sys::GDBM_ITEM_NOT_FOUND => Error::NoRecord,
+            gdbm_sys::GDBM_REORGANIZE_FAILED => Error::ReorganizeFailed,
+            gdbm_sys::GDBM_CANNOT_REPLACE => Error::CannotReplace,
+            gdbm_sys::GDBM_ILLEGAL_DATA => Error::MalformedData,
+            gdbm_sys::GDBM_OPT_ALREADY_SET => Error::OptionAlreadySet,
+            gdbm_sys::GDBM_OPT_ILLEGAL => Error::BadOptionValue,
+            gdbm_sys::GDBM_BYTE_SWAPPED => Error::ByteSwapped,
+            gdbm_sys::GDBM_BAD_FILE_OFFSET => Error::BadFileOffset,
+            gdbm_sys::GDBM_BAD_OPEN_FLAGS => Error::BadOpenFlags,
+            gdbm_sys::GDBM_FILE_STAT_ERROR => Error::FileStat(sys),
+            gdbm_sys::GDBM_FILE_EOF => Error::UnexpectedEof,
+            gdbm_sys::GDBM_NO_DBNAME => Error::NoDbName,
+            gdbm_sys::GDBM_ERR_FILE_OWNER => Error::FileOwner,
+            gdbm_sys::GDBM_ERR_FILE_MODE => Error::FileMode,
+            gdbm_sys::GDBM_NEED_RECOVERY => Error::NeedsRecovery,
+            gdbm_sys::GDBM_BACKUP_FAILED => Error::BackupFailed(sys),
+            gdbm_sys::GDBM_DIR_OVERFLOW => Error::DirOverflow,
+            gdbm_sys::GDBM_BAD_BUCKET => Error::BadBucket,
+            gdbm_sys::GDBM_BAD_HEADER => Error::BadHeader,
+            gdbm_sys::GDBM_BAD_AVAIL => Error::BadAvail,
+            gdbm_sys::GDBM_BAD_HASH_TABLE => Error::BadHashTable,
+            gdbm_sys::GDBM_BAD_DIR_ENTRY => Error::BadDirEntry,
+            gdbm_sys::GDBM_FILE_CLOSE_ERROR => Error::FileClose(sys),
+            gdbm_sys::GDBM_FILE_SYNC_ERROR => Error::FileSync(sys),
+            gdbm_sys::GDBM_FILE_TRUNCATE_ERROR => Error::FileTruncate(sys),
+            gdbm_sys::GDBM_BUCKET_CACHE_CORRUPTED => Error::BucketCacheCorrupted,
+            gdbm_sys::GDBM_BAD_HASH_ENTRY => Error::BadHashEntry,
+            gdbm_sys::GDBM_ERR_SNAPSHOT_CLONE => Error::SnapshotClone(sys),
+            gdbm_sys::GDBM_ERR_REALPATH => Error::RealPath(sys),
+            gdbm_sys::GDBM_ERR_USAGE => Error::Usage(sys),
+            other => Error::Unknown(other),
+        }
+    }
+
+    /// Returns the gdbm error code this error was created from, if any.
+    pub fn gdbm_errno(&self) -> Option<ErrorCode> {
+        let code = match *self {
+            Error::NoRecord => gdbm_sys::GDBM_ITEM_NOT_FOUND,
+            Error::OutOfMemory => gdbm_sys::GDBM_MALLOC_ERROR,
+            Error::BlockSize => gdbm_sys::GDBM_BLOCK_SIZE_ERROR,
+            Error::FileOpen(_) => gdbm_sys::GDBM_FILE_OPEN_ERROR,
+            Error::FileWrite(_) => gdbm_sys::GDBM_FILE_WRITE_ERROR,
+            Error::FileSeek(_) => gdbm_sys::GDBM_FILE_SEEK_ERROR,
+            Error::FileRead(_) => gdbm_sys::GDBM_FILE_READ_ERROR,
+            Error::BadMagicNumber => gdbm_sys::GDBM_BAD_MAGIC_NUMBER,
+            Error::EmptyDatabase => gdbm_sys::GDBM_EMPTY_DATABASE,
+            Error::ReadLockFailed => gdbm_sys::GDBM_CANT_BE_READER,
+            Error::WriteLockFailed => gdbm_sys::GDBM_CANT_BE_WRITER,
+            Error::ReaderCantDelete => gdbm_sys::GDBM_READER_CANT_DELETE,
+            Error::ReaderCantStore => gdbm_sys::GDBM_READER_CANT_STORE,
+            Error::ReaderCantReorganize => gdbm_sys::GDBM_READER_CANT_REORGANIZE,
+            Error::ReorganizeFailed => gdbm_sys::GDBM_REORGANIZE_FAILED,
+            Error::CannotReplace => gdbm_sys::GDBM_CANNOT_REPLACE,
+            Error::MalformedData => gdbm_sys::GDBM_ILLEGAL_DATA,
+            Error::OptionAlreadySet => gdbm_sys::GDBM_OPT_ALREADY_SET,
+            Error::BadOptionValue => gdbm_sys::GDBM_OPT_ILLEGAL,
+            Error::ByteSwapped => gdbm_sys::GDBM_BYTE_SWAPPED,
+            Error::BadFileOffset => gdbm_sys::GDBM_BAD_FILE_OFFSET,
+            Error::BadOpenFlags => gdbm_sys::GDBM_BAD_OPEN_FLAGS,
+            Error::FileStat(_) => gdbm_sys::GDBM_FILE_STAT_ERROR,
+            Error::UnexpectedEof => gdbm_sys::GDBM_FILE_EOF,
+            Error::NoDbName => gdbm_sys::GDBM_NO_DBNAME,
+            Error::FileOwner => gdbm_sys::GDBM_ERR_FILE_OWNER,
+            Error::FileMode => gdbm_sys::GDBM_ERR_FILE_MODE,
+            Error::NeedsRecovery => gdbm_sys::GDBM_NEED_RECOVERY,
+            Error::BackupFailed(_) => gdbm_sys::GDBM_BACKUP_FAILED,
+            Error::DirOverflow => gdbm_sys::GDBM_DIR_OVERFLOW,
+            Error::BadBucket => gdbm_sys::GDBM_BAD_BUCKET,
+            Error::BadHeader => gdbm_sys::GDBM_BAD_HEADER,
+            Error::BadAvail => gdbm_sys::GDBM_BAD_AVAIL,
+            Error::BadHashTable => gdbm_sys::GDBM_BAD_HASH_TABLE,
+            Error::BadDirEntry => gdbm_sys::GDBM_BAD_DIR_ENTRY,
+            Error::FileClose(_) => gdbm_sys::GDBM_FILE_CLOSE_ERROR,
+            Error::FileSync(_) => gdbm_sys::GDBM_FILE_SYNC_ERROR,
+            Error::FileTruncate(_) => gdbm_sys::GDBM_FILE_TRUNCATE_ERROR,
+            Error::BucketCacheCorrupted => gdbm_sys::GDBM_BUCKET_CACHE_CORRUPTED,
+            Error::BadHashEntry => gdbm_sys::GDBM_BAD_HASH_ENTRY,
+            Error::SnapshotClone(_) => gdbm_sys::GDBM_ERR_SNAPSHOT_CLONE,
+            Error::RealPath(_) => gdbm_sys::GDBM_ERR_REALPATH,
+            Error::Usage(_) => gdbm_sys::GDBM_ERR_USAGE,
+            Error::Unknown(code) => code,
+            Error::InvalidPath | Error::KeyExists | Error::NoKeyIndex | Error::Bincode(_)
+                | Error::Codec(_) | Error::Io(_) | Error::RecoveryLimit
+                | Error::Unsupported { .. } => return None,
+        };
+        Some(code)
     }
 
     /// Returns `true` iff `self` is the `NoRecord` enum member.
     pub fn is_no_record(&self) -> bool {
+        matches!(*self, Error::NoRecord)
+    }
+
+    /// Returns `true` if the operation may succeed if it is tried again
+    /// later: the file was locked by another process, memory ran out, or a
+    /// system call was interrupted or timed out.
+    pub fn is_retryable(&self) -> bool {
         match *self {
-            Error::NoRecord => true,
-            _ => false
+            Error::ReadLockFailed | Error::WriteLockFailed | Error::OutOfMemory => true,
+            _ => matches!(
+                self.io_error().map(io::Error::kind),
+                Some(io::ErrorKind::Interrupted)
+                    | Some(io::ErrorKind::WouldBlock)
+                    | Some(io::ErrorKind::TimedOut)
+            ),
         }
     }
-}
 
-#[doc(hidden)]
-impl fmt::Display for GdbmError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    /// Returns `true` if the error means the database file is damaged.
+    ///
+    /// These are the errors after which gdbm marks the database as needing
+    /// recovery; see [`RwHandle::recover`](../struct.RwHandle.html#method.recover).
+    pub fn is_corruption(&self) -> bool {
+        matches!(
+            *self,
+            Error::NeedsRecovery
+                | Error::RecoveryLimit
+                | Error::UnexpectedEof
+                | Error::MalformedData
+                | Error::DirOverflow
+                | Error::BadBucket
+                | Error::BadHeader
+                | Error::BadAvail
+                | Error::BadHashTable
+                | Error::BadDirEntry
+                | Error::BucketCacheCorrupted
+                | Error::BadHashEntry
+        )
+    }
+
+    fn io_error(&self) -> Option<&io::Error> {
         match *self {
-            GdbmError::InvalidDatabase => write!(f, "The file is not a valid database"),
-            GdbmError::NeedsRecovery => write!(f, "The database is corrupted"),
-            GdbmError::UnexpectedEOF => write!(f, "Unexpected EOF in database"),
-            GdbmError::Other(code) => unsafe {
-                let err_ptr = gdbm_sys::gdbm_strerror(code as i32);
-                let err_string = CStr::from_ptr(err_ptr);
-                write!(f, "{}", err_string.to_string_lossy())
-            },
+            Error::Io(ref e)
+            | Error::FileOpen(ref e)
+            | Error::FileWrite(ref e)
+            | Error::FileSeek(ref e)
+            | Error::FileRead(ref e)
+            | Error::FileStat(ref e)
+            | Error::BackupFailed(ref e)
+            | Error::FileClose(ref e)
+            | Error::FileSync(ref e)
+            | Error::FileTruncate(ref e)
+            | Error::SnapshotClone(ref e)
+            | Error::RealPath(ref e)
+            | Error::Usage(ref e) => Some(e),
+            _ => None,
         }
     }
 }
@@ -106,35 +362,40 @@ impl fmt::Display for GdbmError {
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Error::Internal(ref e) => e.fmt(f),
             Error::InvalidPath => write!(f, "Invalid path (interior null byte)"),
             Error::KeyExists => write!(f, "key already exists in database"),
             Error::NoRecord => write!(f, "key does not exist in database"),
-            Error::Bincode(ref e) => e.fmt(f),
+            Error::NoKeyIndex => write!(f, "handle was opened without a key index"),
+            Error::Bincode(_) => write!(f, "bincode serialization failed"),
+            Error::Codec(_) => write!(f, "codec failed"),
+            Error::Io(_) => write!(f, "I/O error"),
+            Error::RecoveryLimit => write!(f, "recovery stopped at the failure limit"),
+            Error::Unsupported { option, requires: (major, minor) } => {
+                write!(f, "`{}` requires gdbm {}.{} or later", option, major, minor)
+            }
+            ref other => {
+                let code = other.gdbm_errno().unwrap_or(gdbm_sys::GDBM_UNKNOWN_ERROR);
+                let err_string = unsafe { CStr::from_ptr(gdbm_sys::gdbm_strerror(code as i32)) };
+                write!(f, "{}", err_string.to_string_lossy())
+            }
         }
     }
 }
 
-impl std::error::Error for Error {}
-
-#[doc(hidden)]
-impl From<u32> for Error {
-    fn from(src: u32) -> Error {
-        match src {
-            errno if errno == gdbm_sys::GDBM_ITEM_NOT_FOUND => Error::NoRecord,
-            errno => Error::Internal(errno.into()),
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            Error::Bincode(ref e) => Some(e),
+            Error::Codec(ref e) => Some(&**e),
+            _ => self.io_error().map(|e| e as &(dyn std::error::Error + 'static)),
         }
     }
 }
 
 #[doc(hidden)]
-impl From<u32> for GdbmError {
-    fn from(src: u32) -> GdbmError {
-        match src {
-            errno if errno == gdbm_sys::GDBM_EMPTY_DATABASE => GdbmError::InvalidDatabase,
-            errno if errno == gdbm_sys::GDBM_NEED_RECOVERY => GdbmError::NeedsRecovery,
-            other => GdbmError::Other(other),
-        }
+impl From<u32> for Error {
+    fn from(src: u32) -> Error {
+        Error::from_code(src, sys_error(0))
     }
 }
 
@@ -146,16 +407,26 @@ impl From<NulError> for Error {
 }
 
 #[doc(hidden)]
-impl From<GdbmError> for Error {
-    fn from(src: GdbmError) -> Error {
-        Error::Internal(src)
+impl From<BincodeError> for Error {
+    fn from(src: BincodeError) -> Error {
+        Error::Bincode(src)
     }
 }
 
 #[doc(hidden)]
-impl From<BincodeError> for Error {
-    fn from(src: BincodeError) -> Error {
-        Error::Bincode(src)
+impl From<io::Error> for Error {
+    fn from(src: io::Error) -> Error {
+        Error::Io(src)
+    }
+}
+
+/// The source of an error that gdbm reports alongside a failed system call,
+/// for the `errno` gdbm saved; 0 if it saved none.
+fn sys_error(errno: i32) -> io::Error {
+    if errno == 0 {
+        io::Error::other("no system error was reported")
+    } else {
+        io::Error::from_raw_os_error(errno)
     }
 }
 
@@ -0,0 +1,214 @@
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
+use std::fmt;
+use std::ops::RangeBounds;
+
+use error::{Error, GdbmResult};
+use super::{ReadHandle, RwHandle};
+
+/// A sorted copy of every key in a database, kept by handles opened with
+/// [`GdbmOpener::key_index`].
+///
+/// [`GdbmOpener::key_index`]: struct.GdbmOpener.html#method.key_index
+pub(crate) struct KeyIndex {
+    keys: RefCell<BTreeSet<Vec<u8>>>,
+}
+
+/// An iterator over the keys of a database in sorted order, returned by
+/// [`RwHandle::range`] and [`RwHandle::iter_prefix`].
+///
+/// Each step looks up the next key after the one last returned, so the
+/// database can be modified while the iterator is in use: keys added ahead
+/// of it are returned, and keys removed ahead of it are not.
+///
+/// [`RwHandle::range`]: struct.RwHandle.html#method.range
+/// [`RwHandle::iter_prefix`]: struct.RwHandle.html#method.iter_prefix
+#[derive(Debug)]
+pub struct KeyRange<'a> {
+    index: &'a KeyIndex,
+    from: Bound<Vec<u8>>,
+    to: Bound<Vec<u8>>,
+}
+
+impl KeyIndex {
+    /// Builds the index by walking every key in `db`.
+    pub(crate) fn build(db: &RwHandle) -> Self {
+        let mut keys = BTreeSet::new();
+        let mut key = db.first_key();
+        while let Some(k) = key {
+            key = db.next_key(&k);
+            keys.insert(k);
+        }
+        KeyIndex { keys: RefCell::new(keys) }
+    }
+
+    pub(crate) fn insert(&self, key: &[u8]) {
+        let mut keys = self.keys.borrow_mut();
+        if !keys.contains(key) {
+            keys.insert(key.to_owned());
+        }
+    }
+
+    pub(crate) fn remove(&self, key: &[u8]) {
+        self.keys.borrow_mut().remove(key);
+    }
+
+    /// Returns the first key within `from` and `to`.
+    fn first_in(&self, from: Bound<&[u8]>, to: Bound<&[u8]>) -> Option<Vec<u8>> {
+        // BTreeSet::range panics on an empty range rather than returning nothing
+        match (from, to) {
+            (Included(a), Included(b)) | (Included(a), Excluded(b)) | (Excluded(a), Included(b))
+                if a > b => return None,
+            (Excluded(a), Excluded(b)) if a >= b => return None,
+            _ => {}
+        }
+        self.keys.borrow().range::<[u8], _>((from, to)).next().cloned()
+    }
+}
+
+impl fmt::Debug for KeyIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KeyIndex").field("len", &self.keys.borrow().len()).finish()
+    }
+}
+
+impl<'a> Iterator for KeyRange<'a> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let key = self.index.first_in(as_slice(&self.from), as_slice(&self.to))?;
+        self.from = Excluded(key.clone());
+        Some(key)
+    }
+}
+
+impl RwHandle {
+    /// Returns the keys within `range`, in sorted (bytewise) order.
+    ///
+    /// This requires the handle to have been opened with
+    /// [`GdbmOpener::key_index`], and returns [`Error::NoKeyIndex`] otherwise.
+    ///
+    /// [`GdbmOpener::key_index`]: struct.GdbmOpener.html#method.key_index
+    /// [`Error::NoKeyIndex`]: error/enum.Error.html#variant.NoKeyIndex
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use gnudbm::*;
+    /// let db = GdbmOpener::new()
+    ///     .key_index(true)
+    ///     .readwrite("btcusdt_1min.db")
+    ///     .unwrap();
+    ///
+    /// // the first hour of 2021
+    /// for key in db.range("1609430400".."1609434000").unwrap() {
+    ///     let entry = db.fetch(&key).unwrap();
+    ///     // ...
+    /// }
+    /// ```
+    pub fn range<K, R>(&self, range: R) -> GdbmResult<KeyRange<'_>>
+    where
+        K: ?Sized + AsRef<[u8]>,
+        R: RangeBounds<K>,
+    {
+        let index = self.index.as_ref().ok_or(Error::NoKeyIndex)?;
+        Ok(KeyRange {
+            index,
+            from: to_owned(range.start_bound()),
+            to: to_owned(range.end_bound()),
+        })
+    }
+
+    /// Returns the keys starting with `prefix`, in sorted (bytewise) order.
+    ///
+    /// This requires the handle to have been opened with
+    /// [`GdbmOpener::key_index`], and returns [`Error::NoKeyIndex`] otherwise.
+    ///
+    /// [`GdbmOpener::key_index`]: struct.GdbmOpener.html#method.key_index
+    /// [`Error::NoKeyIndex`]: error/enum.Error.html#variant.NoKeyIndex
+    pub fn iter_prefix<K: AsRef<[u8]>>(&self, prefix: K) -> GdbmResult<KeyRange<'_>> {
+        let index = self.index.as_ref().ok_or(Error::NoKeyIndex)?;
+        let prefix = prefix.as_ref();
+        Ok(KeyRange {
+            index,
+            from: Included(prefix.to_owned()),
+            to: prefix_end(prefix),
+        })
+    }
+}
+
+impl ReadHandle {
+    /// Returns the keys within `range`, in sorted order. See
+    /// [`RwHandle::range`] for more information.
+    ///
+    /// [`RwHandle::range`]: struct.RwHandle.html#method.range
+    pub fn range<K, R>(&self, range: R) -> GdbmResult<KeyRange<'_>>
+    where
+        K: ?Sized + AsRef<[u8]>,
+        R: RangeBounds<K>,
+    {
+        self.0.range(range)
+    }
+
+    /// Returns the keys starting with `prefix`, in sorted order. See
+    /// [`RwHandle::iter_prefix`] for more information.
+    ///
+    /// [`RwHandle::iter_prefix`]: struct.RwHandle.html#method.iter_prefix
+    pub fn iter_prefix<K: AsRef<[u8]>>(&self, prefix: K) -> GdbmResult<KeyRange<'_>> {
+        self.0.iter_prefix(prefix)
+    }
+}
+
+fn to_owned<K: ?Sized + AsRef<[u8]>>(bound: Bound<&K>) -> Bound<Vec<u8>> {
+    match bound {
+        Included(k) => Included(k.as_ref().to_owned()),
+        Excluded(k) => Excluded(k.as_ref().to_owned()),
+        Unbounded => Unbounded,
+    }
+}
+
+/// Returns the bound just past every key starting with `prefix`.
+fn prefix_end(prefix: &[u8]) -> Bound<Vec<u8>> {
+    // drop trailing 0xff bytes, which can not be incremented, then bump the last
+    let mut end = prefix.to_owned();
+    while let Some(last) = end.pop() {
+        if last < 0xff {
+            end.push(last + 1);
+            return Excluded(end);
+        }
+    }
+    Unbounded
+}
+
+fn as_slice(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    match *bound {
+        Included(ref k) => Included(k),
+        Excluded(ref k) => Excluded(k),
+        Unbounded => Unbounded,
+    }
+}
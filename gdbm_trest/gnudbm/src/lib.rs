@@ -26,7 +26,8 @@
 //! Gnudbm is an ergonomic, idiomatic wrapper for [gdbm].
 //!
 //! With built in support for [Serde] and [bincode], It provides fast and easy
-//! local key/value storage of any type implementing [`Serialize`].
+//! local key/value storage of any type implementing [`Serialize`]. Values can
+//! also be stored as JSON, MessagePack or raw bytes by way of a [`Codec`].
 //!
 //! For an overview of available database operations, see the documentation for
 //! [`RwHandle`].
@@ -106,19 +107,38 @@
 //! [`Serialize`]: https://serde.rs/impl-serialize.html
 //! [`RwHandle`]: struct.RwHandle.html
 //! [`GdbmOpener`]: struct.GdbmOpener.html
+//! [`Codec`]: trait.Codec.html
 //!
 
 extern crate bincode;
+extern crate gdbm_sys;
+#[cfg(feature = "async")]
+extern crate futures_core;
 extern crate libc;
+extern crate rmp_serde;
 extern crate serde;
+extern crate serde_json;
+#[cfg(feature = "async")]
+extern crate tokio;
 
 
-mod gdbm_sys;
 mod error;
+mod batch;
+mod codec;
+mod drain;
+mod dump;
+mod index;
+mod pool;
+mod recover;
+mod tuning;
+mod typed;
+#[cfg(feature = "async")]
+mod async_handle;
 
 use std::ops::Drop;
 use std::default::Default;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::ptr;
 use std::slice;
 use std::ffi::CString;
 use std::os::unix::ffi::OsStrExt;
@@ -127,16 +147,36 @@ use std::mem;
 
 use serde::{Deserialize, Serialize};
 
-use error::last_errno;
-pub use error::{Error, GdbmError, GdbmResult};
-
-//TODO: use umask
+use index::KeyIndex;
+use tuning::Tuning;
+pub use error::{Error, GdbmResult};
+pub use batch::WriteBatch;
+pub use codec::{Bincode, Codec, Json, MsgPack, Raw};
+pub use drain::DrainFilter;
+pub use dump::DumpFormat;
+pub use index::KeyRange;
+pub use pool::{PoolGuard, ReadPool};
+#[cfg(feature = "v1_20")]
+pub use recover::ConsistencyReport;
+pub use recover::{RecoverOptions, RecoveryReport};
+pub use typed::TypedHandle;
+#[cfg(feature = "async")]
+pub use async_handle::{AsyncReadHandle, AsyncRwHandle, EntryBuf, EntryStream, Request};
+
+/// The mode of created files, unless `GdbmOpener::mode` is used. The process
+/// umask is applied to it.
 const DEFAULT_MODE: i32 = 0o666;
 
 /// A read/write reference to a gdbm database.
 #[derive(Debug)]
 pub struct RwHandle {
     handle: gdbm_sys::GDBM_FILE,
+    path: PathBuf,
+    // the sync/nolock/nommap/preread flags and the options this handle was
+    // opened with; used when the file has to be reopened after a batch commit.
+    flags: i32,
+    tuning: Tuning,
+    index: Option<KeyIndex>,
 }
 
 /// A readonly reference to a gdbm database.
@@ -147,7 +187,7 @@ pub struct RwHandle {
 pub struct ReadHandle(RwHandle);
 
 /// A builder used to open gdbm files.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct GdbmOpener {
     sync: bool,
     no_lock: bool,
@@ -155,7 +195,12 @@ pub struct GdbmOpener {
     create: bool,
     overwrite: bool,
     readonly: bool,
+    key_index: bool,
+    preread: bool,
     block_size: i32,
+    mode: Option<u32>,
+    crash_tolerance: Option<(PathBuf, PathBuf)>,
+    tuning: Tuning,
 }
 
 /// An entry in a gdbm database.
@@ -244,7 +289,34 @@ impl RwHandle {
         V: ?Sized + Serialize,
     {
         let bytes = bincode::serialize(value)?;
-        let key_d: gdbm_sys::datum = key.as_ref().into();
+        self.store_bytes(key.as_ref(), &bytes, replace)
+    }
+
+    /// Inserts a key value pair into the database, encoding `value` with
+    /// `codec` instead of bincode, and replacing any existing value for that
+    /// key.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use gnudbm::*;
+    /// # let mut db = RwHandle::dummy();
+    /// db.store_with(&Json, "max_epoch", &1609430400u64).unwrap();
+    ///
+    /// let entry = db.fetch("max_epoch").unwrap();
+    /// assert_eq!(entry.as_bytes(), b"1609430400");
+    /// ```
+    pub fn store_with<K, V, C>(&mut self, codec: &C, key: K, value: &V) -> GdbmResult<()>
+    where
+        K: AsRef<[u8]>,
+        C: Codec<V>,
+    {
+        let bytes = codec.encode(value)?;
+        self.store_bytes(key.as_ref(), &bytes, true).map(|_| ())
+    }
+
+    pub(crate) fn store_bytes(&mut self, key: &[u8], bytes: &[u8], replace: bool) -> GdbmResult<i32> {
+        let key_d: gdbm_sys::datum = key.into();
 
         let value_d = gdbm_sys::datum {
             dptr: bytes.as_ptr() as *mut i8,
@@ -260,10 +332,36 @@ impl RwHandle {
         let result = unsafe { gdbm_sys::gdbm_store(self.handle, key_d, value_d, flag as i32) };
 
         if result == -1 {
-            Err(GdbmError::from_last().into())
-        } else {
-            Ok(result)
+            return Err(Error::from_handle(self.handle));
+        }
+        if let Some(ref index) = self.index {
+            index.insert(key);
         }
+        Ok(result)
+    }
+
+    /// Returns a [`WriteBatch`] that collects stores and removals and applies
+    /// them to the database all at once.
+    ///
+    /// Nothing is written until [`WriteBatch::commit`] is called; dropping the
+    /// batch discards it. See [`WriteBatch`] for how atomicity is achieved.
+    ///
+    /// [`WriteBatch`]: struct.WriteBatch.html
+    /// [`WriteBatch::commit`]: struct.WriteBatch.html#method.commit
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use gnudbm::*;
+    /// # let mut db = RwHandle::dummy();
+    /// let mut batch = db.batch();
+    /// batch.store("1609430400", &vec![1u64, 2, 3]).unwrap();
+    /// batch.store("max_epoch", &1609430400u64).unwrap();
+    /// batch.remove("1609344000");
+    /// batch.commit().unwrap();
+    /// ```
+    pub fn batch(&mut self) -> WriteBatch<'_> {
+        WriteBatch::new(self)
     }
 
     /// Attempts to fetch an item from the database.
@@ -294,7 +392,7 @@ impl RwHandle {
         let result = unsafe { gdbm_sys::gdbm_fetch(self.handle, key_d) };
 
         if result.dptr.is_null() {
-            Err(GdbmError::from_last().into())
+            Err(Error::from_handle(self.handle))
         } else {
             Ok(Entry::new(result))
         }
@@ -308,14 +406,17 @@ impl RwHandle {
     pub fn remove<K>(&self, key: K) -> GdbmResult<bool>
         where K: AsRef<[u8]>,
     {
-        let key_d = key.as_ref().into();
-        let result = unsafe { gdbm_sys::gdbm_delete(self.handle, key_d) };
+        let key = key.as_ref();
+        let result = unsafe { gdbm_sys::gdbm_delete(self.handle, key.into()) };
         if result != 0 {
-            match Error::from_last() {
+            match Error::from_handle(self.handle) {
                 ref e if e.is_no_record() => Ok(false),
                 e => Err(e)
             }
         } else {
+            if let Some(ref index) = self.index {
+                index.remove(key);
+            }
             Ok(true)
         }
     }
@@ -344,7 +445,7 @@ impl RwHandle {
         let count_ptr: *mut u64 = &mut count;
         let r = unsafe { gdbm_sys::gdbm_count(self.handle, count_ptr) };
         if r == -1 {
-            Err(GdbmError::from_last().into())
+            Err(Error::from_handle(self.handle))
         } else {
             Ok(count as usize)
         }
@@ -353,8 +454,14 @@ impl RwHandle {
     /// Returns an iterator over the keys and values in this database.
     /// The iterator's element type is `(`[`Key`], [`Entry`]`)`.
     ///
+    /// Keys are returned in gdbm's hash order. Removing entries while the
+    /// iterator is in use can cause keys to be skipped or returned twice; use
+    /// [`drain_filter`] or [`retain`] instead.
+    ///
     /// [`Key`]: struct.Key.html
     /// [`Entry`]: struct.Entry.html
+    /// [`drain_filter`]: #method.drain_filter
+    /// [`retain`]: #method.retain
     ///
     /// # Examples
     ///
@@ -390,22 +497,19 @@ impl RwHandle {
         let key_d: gdbm_sys::datum = key.into();
         let result = unsafe { gdbm_sys::gdbm_exists(self.handle, key_d) };
 
-        if result == 0 {
-            let errno = last_errno();
-            if errno != gdbm_sys::GDBM_NO_ERROR {
-                Err(errno.into())
-            } else {
-                Ok(false)
-            }
-        } else {
-            Ok(true)
+        if result != 0 {
+            return Ok(true);
+        }
+        match unsafe { gdbm_sys::gdbm_last_errno(self.handle) } as u32 {
+            gdbm_sys::GDBM_NO_ERROR | gdbm_sys::GDBM_ITEM_NOT_FOUND => Ok(false),
+            _ => Err(Error::from_handle(self.handle)),
         }
     }
 
     /// Synchronizes the changes in the database with the file on disk.
     pub fn sync(&self) {
         //TODO: this should be failable, but docs don't show how we get the error :|
-        unsafe { gdbm_sys::gdbm_sync(self.handle) }
+        unsafe { gdbm_sys::gdbm_sync(self.handle); }
     }
 
     /// Reorganizes the database file, potentially reducing its size on disk.
@@ -423,22 +527,23 @@ impl RwHandle {
     pub fn reorganize(&mut self) -> GdbmResult<()> {
         let result = unsafe { gdbm_sys::gdbm_reorganize(self.handle) };
         if result != 0 {
-            Err(Error::from_last())
+            Err(Error::from_handle(self.handle))
         } else {
             Ok(())
         }
     }
 
-    //TODO: this should actually be an option in GdbmOpener
-    /// Set the size of the internal bucket cache.
+    /// Set the size of the internal bucket cache. This can also be set when
+    /// opening the database, with [`GdbmOpener::cache_size`].
     ///
     /// # Note
     ///
-    /// This option may only be set _once_ on each database handle. Subsequent
-    /// calls may fail silently.
+    /// Before gdbm 1.13, this option may only be set _once_ on each database
+    /// handle.
+    ///
+    /// [`GdbmOpener::cache_size`]: struct.GdbmOpener.html#method.cache_size
     pub fn set_cache_size(&mut self, size: usize) -> GdbmResult<()> {
-        self.set_opt(gdbm_sys::GDBM_SETCACHESIZE, size);
-        Ok(())
+        self.set_opt(gdbm_sys::GDBM_SETCACHESIZE, size)
     }
 
     /// Returns the size of the internal bucket cache.
@@ -450,8 +555,7 @@ impl RwHandle {
     /// changes to the database are written to disk as they occur.
     pub fn set_sync_mode(&mut self, mode: bool) -> GdbmResult<()> {
         let mode = if mode { 1i32 } else { 0 };
-        self.set_opt(gdbm_sys::GDBM_SETSYNCMODE, mode);
-        Ok(())
+        self.set_opt(gdbm_sys::GDBM_SETSYNCMODE, mode)
     }
 
     /// Returns `true` if the database is in sync mode.
@@ -461,7 +565,7 @@ impl RwHandle {
         match r {
             0 => Ok(false),
             1 => Ok(true),
-            _ => Err(Error::from_last()),
+            _ => Err(Error::from_handle(self.handle)),
         }
     }
 
@@ -472,8 +576,7 @@ impl RwHandle {
     ///
     /// By default, this is equal to `usize::max_value()`.
     pub fn set_max_mmap_size(&mut self, size: usize) -> GdbmResult<()> {
-        self.set_opt(gdbm_sys::GDBM_SETMAXMAPSIZE, size);
-        Ok(())
+        self.set_opt(gdbm_sys::GDBM_SETMAXMAPSIZE, size)
     }
 
     /// Returns the current maximum size of a memory mapped region.
@@ -484,8 +587,7 @@ impl RwHandle {
     /// Set whether or not the database should use memory mapping.
     pub fn set_mmap_enabled(&mut self, mode: bool) -> GdbmResult<()> {
         let mode = if mode { 1i32 } else { 0 };
-        self.set_opt(gdbm_sys::GDBM_SETMMAP, mode);
-        Ok(())
+        self.set_opt(gdbm_sys::GDBM_SETMMAP, mode)
     }
 
     /// Returns whether or not the database should use memory mapping.
@@ -494,22 +596,27 @@ impl RwHandle {
         match r {
             0 => Ok(false),
             1 => Ok(true),
-            _ => Err(Error::from_last()),
+            _ => Err(Error::from_handle(self.handle)),
         }
     }
 
     /// Returns the block size, in bytes. Block size is set when the database
     /// is first created, and cannot be changed.
     pub fn get_block_size(&self) -> GdbmResult<usize> {
-        Ok(self.get_opt(gdbm_sys::GDBM_GETBLOCKSIZE))
+        // gdbm only accepts an int for this option
+        let size: i32 = self.get_opt(gdbm_sys::GDBM_GETBLOCKSIZE);
+        Ok(size as usize)
     }
 
-    fn set_opt<T>(&self, opt: u32, value: T) {
+    pub(crate) fn set_opt<T>(&self, opt: u32, value: T) -> GdbmResult<()> {
         let mut value = value;
         let ptr = &mut value as *mut T;
         let ptr = ptr as *mut os_c_void;
         let size = mem::size_of::<T>() as i32;
-        unsafe { gdbm_sys::gdbm_setopt(self.handle, opt as i32, ptr, size) };
+        if unsafe { gdbm_sys::gdbm_setopt(self.handle, opt as i32, ptr, size) } != 0 {
+            return Err(Error::from_handle(self.handle));
+        }
+        Ok(())
     }
 
     fn get_opt<T: Default>(&self, opt: u32) -> T {
@@ -527,15 +634,48 @@ impl RwHandle {
     #[allow(dead_code)]
     #[doc(hidden)]
     pub fn dummy() -> RwHandle {
-        use std::ptr;
-        RwHandle { handle: ptr::null_mut() }
+        RwHandle {
+            handle: ptr::null_mut(),
+            path: PathBuf::new(),
+            flags: 0,
+            tuning: Tuning::default(),
+            index: None,
+        }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Switches to the gdbm handle of `other`, e.g. after the file `other`
+    /// has open was renamed over `self.path`, and closes the old one.
+    ///
+    /// The key index, if any, is kept; the caller is responsible for
+    /// updating it.
+    pub(crate) fn replace_handle(&mut self, mut other: RwHandle) {
+        mem::swap(&mut self.handle, &mut other.handle);
+        // `other` now holds the old handle and closes it when dropped
+    }
+
+    /// Opens `path` as a read/write handle sharing this handle's flags.
+    pub(crate) fn open_sibling(&self, path: &Path) -> GdbmResult<RwHandle> {
+        let flags = gdbm_sys::GDBM_WRITER as i32 | self.flags;
+        let handle = open_raw(path, 0, flags, DEFAULT_MODE)?;
+        Ok(RwHandle {
+            handle,
+            path: path.to_owned(),
+            flags: self.flags,
+            tuning: Tuning::default(),
+            index: None,
+        })
     }
 }
 
 #[doc(hidden)]
 impl Drop for RwHandle {
     fn drop(&mut self) {
-        unsafe { gdbm_sys::gdbm_close(self.handle) }
+        if self.handle.is_null() { return };
+        unsafe { gdbm_sys::gdbm_close(self.handle); }
     }
 }
 
@@ -604,12 +744,61 @@ impl GdbmOpener {
         self
     }
 
+    /// Sets the option to keep a sorted index of every key in memory,
+    /// enabling [`RwHandle::range`] and [`RwHandle::iter_prefix`].
+    ///
+    /// The index is built by walking every key when the database is opened,
+    /// and is then kept up to date by the handle's own stores and removals.
+    /// Changes made by other handles or processes are not seen.
+    ///
+    /// [`RwHandle::range`]: struct.RwHandle.html#method.range
+    /// [`RwHandle::iter_prefix`]: struct.RwHandle.html#method.iter_prefix
+    pub fn key_index(&mut self, key_index: bool) -> &mut Self {
+        self.key_index = key_index;
+        self
+    }
+
     /// Attempts to open the file at `path` with the options provided,
     /// returning a read/write database handle.
+    ///
+    /// If an option can not be applied, the file is closed again and the
+    /// error is returned; in particular, options that the linked libgdbm
+    /// does not support fail with [`Error::Unsupported`] before the file is
+    /// opened.
+    ///
+    /// [`Error::Unsupported`]: error/enum.Error.html#variant.Unsupported
     pub fn readwrite<P: AsRef<Path>>(&self, path: P) -> GdbmResult<RwHandle> {
         let path = path.as_ref();
-        let handle = self.gdbm_open(&path)?;
-        Ok(RwHandle { handle })
+        let tuning = self.tuning()?;
+        let handle = self.gdbm_open(path)?;
+        let mut db = RwHandle {
+            handle,
+            path: path.to_owned(),
+            flags: self.extra_flags(),
+            tuning,
+            index: None,
+        };
+        // dropping `db` on error closes the file
+        db.tuning.apply(&db)?;
+        if self.key_index {
+            db.index = Some(KeyIndex::build(&db));
+        }
+        Ok(db)
+    }
+
+    /// Attempts to open the file at `path` with the options provided,
+    /// returning a [`TypedHandle`] that encodes every value with `codec`.
+    ///
+    /// [`TypedHandle`]: struct.TypedHandle.html
+    pub fn readwrite_typed<K, V, C, P>(&self, path: P, codec: C)
+        -> GdbmResult<TypedHandle<K, V, C>>
+    where
+        K: ?Sized + AsRef<[u8]>,
+        P: AsRef<Path>,
+        C: Codec<V>,
+    {
+        let db = self.readwrite(path)?;
+        Ok(TypedHandle::new(db, codec))
     }
 
     /// Attempts to open the file at `path` with the options provided,
@@ -623,9 +812,6 @@ impl GdbmOpener {
     }
 
     fn gdbm_open(&self, path: &Path) -> GdbmResult<gdbm_sys::GDBM_FILE> {
-        let path = CString::new(path.as_os_str().as_bytes())?;
-        let path_ptr = path.as_ptr() as *mut i8;
-
         let mut flags = gdbm_sys::GDBM_WRITER as i32;
         if self.readonly {
             flags = gdbm_sys::GDBM_READER as i32;
@@ -635,6 +821,13 @@ impl GdbmOpener {
             flags = gdbm_sys::GDBM_WRCREAT as i32;
         }
 
+        flags |= self.extra_flags();
+        let mode = self.mode.map_or(DEFAULT_MODE, |mode| mode as i32);
+        open_raw(path, self.block_size, flags, mode)
+    }
+
+    fn extra_flags(&self) -> i32 {
+        let mut flags = 0;
         if self.sync {
             flags |= gdbm_sys::GDBM_SYNC as i32
         }
@@ -644,15 +837,26 @@ impl GdbmOpener {
         if self.no_mmap {
             flags |= gdbm_sys::GDBM_NOMMAP as i32
         }
+        if self.preread {
+            flags |= gdbm_sys::GDBM_PREREAD as i32
+        }
+        flags
+    }
+}
 
-        let handle =
-            unsafe { gdbm_sys::gdbm_open(path_ptr, self.block_size, flags, DEFAULT_MODE, None) };
+fn open_raw(path: &Path, block_size: i32, flags: i32, mode: i32)
+    -> GdbmResult<gdbm_sys::GDBM_FILE>
+{
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let path_ptr = path.as_ptr() as *mut i8;
 
-        if handle.is_null() {
-            Err(GdbmError::from_last().into())
-        } else {
-            Ok(handle)
-        }
+    let handle =
+        unsafe { gdbm_sys::gdbm_open(path_ptr, block_size, flags, mode, None) };
+
+    if handle.is_null() {
+        Err(Error::from_last())
+    } else {
+        Ok(handle)
     }
 }
 
@@ -715,6 +919,24 @@ impl<'a> Entry<'a> {
     {
         bincode::deserialize(self.slice)
     }
+
+    /// Decodes this entry with `codec`, for values that were not stored with
+    /// bincode.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use gnudbm::*;
+    /// # let mut db = RwHandle::dummy();
+    /// db.store_with(&Json, "1609430400", &vec![1.5f64, 2.5]).unwrap();
+    ///
+    /// let entry = db.fetch("1609430400").unwrap();
+    /// let prices: Vec<f64> = entry.decode(&Json).unwrap();
+    /// assert_eq!(prices, vec![1.5, 2.5]);
+    /// ```
+    pub fn decode<T, C: Codec<T>>(&self, codec: &C) -> GdbmResult<T> {
+        codec.decode(self.slice)
+    }
 }
 
 #[doc(hidden)]
@@ -734,6 +956,28 @@ impl<'a> Key<'a> {
     }
 }
 
+/// Copies the data out of a datum returned by gdbm and frees it.
+fn datum_into_vec(datum: gdbm_sys::datum) -> Option<Vec<u8>> {
+    if datum.dptr.is_null() {
+        return None;
+    }
+    let bytes = unsafe { slice::from_raw_parts(datum.dptr as *const u8, datum.dsize as usize) }.to_vec();
+    unsafe { libc::free(datum.dptr as *mut libc::c_void) };
+    Some(bytes)
+}
+
+impl RwHandle {
+    /// Returns a copy of the first key in gdbm's iteration order.
+    pub(crate) fn first_key(&self) -> Option<Vec<u8>> {
+        datum_into_vec(unsafe { gdbm_sys::gdbm_firstkey(self.handle) })
+    }
+
+    /// Returns a copy of the key following `key` in gdbm's iteration order.
+    pub(crate) fn next_key(&self, key: &[u8]) -> Option<Vec<u8>> {
+        datum_into_vec(unsafe { gdbm_sys::gdbm_nextkey(self.handle, key.into()) })
+    }
+}
+
 impl<'a> Iter<'a> {
     fn new(db: &'a RwHandle) -> Self {
         let firstkey = unsafe { gdbm_sys::gdbm_firstkey(db.handle) };
@@ -0,0 +1,175 @@
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+use error::GdbmResult;
+use super::{GdbmOpener, ReadHandle};
+
+/// A fixed set of [`ReadHandle`]s to the same file, shared between threads.
+///
+/// Opening a gdbm file reads its header and bucket directory, which is
+/// wasted work if it is done for every read. A pool opens its handles once,
+/// and lends them out with [`get`]; each handle is used by one thread at a
+/// time, and goes back to the pool when its [`PoolGuard`] is dropped.
+///
+/// `ReadPool` is `Send` and `Sync`, so it can be put in an `Arc` or a
+/// `static` and used from any thread. A [`PoolGuard`] is neither: gdbm
+/// reports errors through `gdbm_errno`, which is thread-local in builds of
+/// gdbm with thread support (1.9 and later), and gnudbm reads it right
+/// after each failing call. Keeping each borrowed handle on the thread that
+/// borrowed it ensures the error read is the one that call set.
+///
+/// Since the handles are readers, the file can not be opened for writing
+/// while the pool is alive, unless it was opened with `no_lock`.
+///
+/// Created with [`GdbmOpener::read_pool`].
+///
+/// [`ReadHandle`]: struct.ReadHandle.html
+/// [`get`]: #method.get
+/// [`PoolGuard`]: struct.PoolGuard.html
+/// [`GdbmOpener::read_pool`]: struct.GdbmOpener.html#method.read_pool
+///
+/// # Examples
+///
+/// ```no_run
+/// # use gnudbm::*;
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let pool = Arc::new(GdbmOpener::new().read_pool("btcusdt_1min.db", 4).unwrap());
+/// let readers: Vec<_> = (0..8).map(|_| {
+///     let pool = pool.clone();
+///     thread::spawn(move || {
+///         let db = pool.get();
+///         let epoch: u64 = db.fetch("max_epoch").unwrap().deserialize().unwrap();
+///         epoch
+///     })
+/// }).collect();
+/// for reader in readers {
+///     reader.join().unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ReadPool {
+    idle: Mutex<Vec<Pooled>>,
+    returned: Condvar,
+    size: usize,
+}
+
+/// A [`ReadHandle`] borrowed from a [`ReadPool`]. The handle is returned to
+/// the pool when this is dropped.
+///
+/// [`ReadHandle`]: struct.ReadHandle.html
+/// [`ReadPool`]: struct.ReadPool.html
+#[derive(Debug)]
+pub struct PoolGuard<'a> {
+    pool: &'a ReadPool,
+    db: Option<ReadHandle>,
+}
+
+/// An idle handle, owned by the pool rather than by any thread.
+#[derive(Debug)]
+struct Pooled(ReadHandle);
+
+// gdbm keeps no thread-affine state in a handle; it only must not be used by
+// two threads at once. Idle handles are only reachable through the mutex,
+// and a borrowed handle is held by a `PoolGuard`, which can not leave its
+// thread.
+unsafe impl Send for Pooled {}
+
+impl ReadPool {
+    /// Borrows a handle, blocking until one is free.
+    pub fn get(&self) -> PoolGuard<'_> {
+        let mut idle = self.lock();
+        loop {
+            if let Some(Pooled(db)) = idle.pop() {
+                return PoolGuard { pool: self, db: Some(db) };
+            }
+            idle = self.returned.wait(idle).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Borrows a handle if one is free, without blocking.
+    pub fn try_get(&self) -> Option<PoolGuard<'_>> {
+        self.lock().pop().map(|Pooled(db)| PoolGuard { pool: self, db: Some(db) })
+    }
+
+    /// Returns the number of handles in the pool.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the number of handles not currently borrowed.
+    pub fn idle(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Pooled>> {
+        // the lock is never held while calling into gdbm or user code, so a
+        // poisoned pool is still consistent
+        self.idle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<'a> Deref for PoolGuard<'a> {
+    type Target = ReadHandle;
+
+    fn deref(&self) -> &ReadHandle {
+        self.db.as_ref().expect("handle already returned")
+    }
+}
+
+impl<'a> Drop for PoolGuard<'a> {
+    fn drop(&mut self) {
+        if let Some(db) = self.db.take() {
+            self.pool.lock().push(Pooled(db));
+            self.pool.returned.notify_one();
+        }
+    }
+}
+
+impl GdbmOpener {
+    /// Opens the file at `path` `size` times with the options provided,
+    /// returning a [`ReadPool`] of readonly handles.
+    ///
+    /// This ignores any settings applied by `create` or `overwrite`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is 0.
+    ///
+    /// [`ReadPool`]: struct.ReadPool.html
+    pub fn read_pool<P: AsRef<Path>>(&self, path: P, size: usize) -> GdbmResult<ReadPool> {
+        assert!(size > 0, "a ReadPool needs at least one handle");
+        let mut opener = self.clone();
+        let mut idle = Vec::with_capacity(size);
+        for _ in 0..size {
+            idle.push(Pooled(opener.readonly(path.as_ref())?));
+        }
+        Ok(ReadPool { idle: Mutex::new(idle), returned: Condvar::new(), size })
+    }
+}
@@ -0,0 +1,343 @@
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use std::ffi::CStr;
+use std::os::unix::ffi::OsStrExt;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::ptr;
+
+use error::{last_errno, Error, GdbmResult};
+use gdbm_sys;
+use index::KeyIndex;
+use super::RwHandle;
+
+/// Options for [`RwHandle::recover`].
+///
+/// By default, recovery keeps going however many keys or buckets it fails
+/// to read, and does not keep a backup of the damaged file.
+///
+/// [`RwHandle::recover`]: struct.RwHandle.html#method.recover
+///
+/// # Examples
+///
+/// ```no_run
+/// # use gnudbm::*;
+/// # let mut db = RwHandle::dummy();
+/// let report = db.recover(RecoverOptions::new()
+///     .backup(true)
+///     .max_failed_keys(100))
+///     .unwrap();
+/// println!("lost {} keys, backup at {:?}", report.failed_keys, report.backup);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct RecoverOptions {
+    backup: bool,
+    force: bool,
+    max_failed_keys: Option<usize>,
+    max_failed_buckets: Option<usize>,
+    max_failures: Option<usize>,
+}
+
+/// The outcome of a successful [`RwHandle::recover`].
+///
+/// [`RwHandle::recover`]: struct.RwHandle.html#method.recover
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// The number of keys copied to the recovered database.
+    pub recovered_keys: usize,
+    /// The number of buckets read successfully.
+    pub recovered_buckets: usize,
+    /// The number of keys that could not be read, and were lost.
+    pub failed_keys: usize,
+    /// The number of buckets that could not be read.
+    pub failed_buckets: usize,
+    /// The number of keys found more than once; only the first was kept.
+    pub duplicate_keys: usize,
+    /// The copy of the damaged file, if a backup was requested.
+    pub backup: Option<PathBuf>,
+}
+
+/// The result of [`RwHandle::check`].
+///
+/// [`RwHandle::check`]: struct.RwHandle.html#method.check
+#[cfg(feature = "v1_20")]
+#[derive(Debug)]
+pub struct ConsistencyReport {
+    /// Whether gdbm has flagged the database as needing recovery. If it was
+    /// flagged before the check, gdbm refuses to read it, and the other
+    /// fields are left empty.
+    pub needs_recovery: bool,
+    /// The number of buckets in the database.
+    pub buckets: usize,
+    /// The number of keys recorded in the bucket headers, or 0 if they could
+    /// not be counted.
+    pub expected_keys: usize,
+    /// The error that stopped the keys from being counted, if any.
+    pub count_error: Option<Error>,
+    /// The number of keys reached by iterating over the database.
+    pub keys: usize,
+    /// Keys that were reached, but whose value could not be read.
+    pub unreadable_keys: Vec<Vec<u8>>,
+    /// The error that stopped the walk early, if any.
+    pub walk_error: Option<Error>,
+    /// The error found when verifying the list of free space, if any.
+    pub avail_error: Option<Error>,
+}
+
+impl RecoverOptions {
+    /// Create a new `RecoverOptions`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the option to keep a copy of the damaged file. The name of the
+    /// copy is returned in [`RecoveryReport::backup`].
+    ///
+    /// This corresponds to gdbm's `GDBM_RCVR_BACKUP` flag.
+    ///
+    /// [`RecoveryReport::backup`]: struct.RecoveryReport.html#structfield.backup
+    pub fn backup(&mut self, backup: bool) -> &mut Self {
+        self.backup = backup;
+        self
+    }
+
+    /// Sets the option to recover even if gdbm has not flagged the database
+    /// as needing recovery.
+    ///
+    /// This corresponds to gdbm's `GDBM_RCVR_FORCE` flag.
+    pub fn force(&mut self, force: bool) -> &mut Self {
+        self.force = force;
+        self
+    }
+
+    /// Gives up, leaving the database untouched, as soon as `max` keys can
+    /// not be recovered. gdbm treats a `max` of 0 as no limit.
+    pub fn max_failed_keys(&mut self, max: usize) -> &mut Self {
+        self.max_failed_keys = Some(max);
+        self
+    }
+
+    /// Gives up, leaving the database untouched, as soon as `max` buckets
+    /// can not be read. gdbm treats a `max` of 0 as no limit.
+    pub fn max_failed_buckets(&mut self, max: usize) -> &mut Self {
+        self.max_failed_buckets = Some(max);
+        self
+    }
+
+    /// Gives up, leaving the database untouched, as soon as `max` keys and
+    /// buckets together can not be recovered. gdbm treats a `max` of 0 as no
+    /// limit.
+    pub fn max_failures(&mut self, max: usize) -> &mut Self {
+        self.max_failures = Some(max);
+        self
+    }
+}
+
+#[cfg(feature = "v1_20")]
+impl ConsistencyReport {
+    /// Returns `true` if no problems were found.
+    pub fn is_consistent(&self) -> bool {
+        !self.needs_recovery
+            && self.keys == self.expected_keys
+            && self.unreadable_keys.is_empty()
+            && self.count_error.is_none()
+            && self.walk_error.is_none()
+            && self.avail_error.is_none()
+    }
+}
+
+impl RwHandle {
+    /// Rebuilds a damaged database from the data that can still be read,
+    /// replacing the database file.
+    ///
+    /// gdbm flags a database as needing recovery when it finds that it is
+    /// damaged; after that, most operations fail with
+    /// [`Error::NeedsRecovery`] until this is called. Unless
+    /// [`RecoverOptions::force`] is set, this does nothing on a database that
+    /// is not flagged.
+    ///
+    /// If the handle keeps a key index, it is rebuilt.
+    ///
+    /// [`Error::NeedsRecovery`]: error/enum.Error.html#variant.NeedsRecovery
+    /// [`RecoverOptions::force`]: struct.RecoverOptions.html#method.force
+    pub fn recover(&mut self, options: &RecoverOptions) -> GdbmResult<RecoveryReport> {
+        let mut rcvr = gdbm_sys::gdbm_recovery {
+            errfun: None,
+            data: ptr::null_mut(),
+            max_failed_keys: 0,
+            max_failed_buckets: 0,
+            max_failures: 0,
+            recovered_keys: 0,
+            recovered_buckets: 0,
+            failed_keys: 0,
+            failed_buckets: 0,
+            duplicate_keys: 0,
+            backup_name: ptr::null_mut(),
+        };
+        let mut flags = gdbm_sys::GDBM_RCVR_DEFAULT;
+        if options.backup {
+            flags |= gdbm_sys::GDBM_RCVR_BACKUP;
+        }
+        if options.force {
+            flags |= gdbm_sys::GDBM_RCVR_FORCE;
+        }
+        if let Some(max) = options.max_failed_keys {
+            rcvr.max_failed_keys = max;
+            flags |= gdbm_sys::GDBM_RCVR_MAX_FAILED_KEYS;
+        }
+        if let Some(max) = options.max_failed_buckets {
+            rcvr.max_failed_buckets = max;
+            flags |= gdbm_sys::GDBM_RCVR_MAX_FAILED_BUCKETS;
+        }
+        if let Some(max) = options.max_failures {
+            rcvr.max_failures = max;
+            flags |= gdbm_sys::GDBM_RCVR_MAX_FAILURES;
+        }
+
+        clear_errno();
+        let result = unsafe { gdbm_sys::gdbm_recover(self.handle, &mut rcvr, flags as i32) };
+        let error = match (result, last_errno()) {
+            (0, _) => None,
+            // gdbm does not set an error code when a limit is reached
+            (_, gdbm_sys::GDBM_NO_ERROR) => Some(Error::RecoveryLimit),
+            _ => Some(Error::from_handle(self.handle)),
+        };
+
+        let backup = if rcvr.backup_name.is_null() {
+            None
+        } else {
+            let name = unsafe { CStr::from_ptr(rcvr.backup_name) };
+            let path = PathBuf::from(OsStr::from_bytes(name.to_bytes()));
+            unsafe { libc::free(rcvr.backup_name as *mut libc::c_void) };
+            Some(path)
+        };
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        if self.index.is_some() {
+            self.index = Some(KeyIndex::build(self));
+        }
+        Ok(RecoveryReport {
+            recovered_keys: rcvr.recovered_keys,
+            recovered_buckets: rcvr.recovered_buckets,
+            failed_keys: rcvr.failed_keys,
+            failed_buckets: rcvr.failed_buckets,
+            duplicate_keys: rcvr.duplicate_keys,
+            backup,
+        })
+    }
+
+    /// Counts the keys in the bucket headers, reads every key and value in
+    /// the order of [`iter`], verifies the list of free space, and reports
+    /// any damage found.
+    ///
+    /// This reads the whole database, and does not modify it. Requires the
+    /// `v1_20` feature. Finding damage
+    /// may cause gdbm to flag the database as needing recovery; see
+    /// [`recover`].
+    ///
+    /// [`iter`]: #method.iter
+    /// [`recover`]: #method.recover
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use gnudbm::*;
+    /// # let mut db = RwHandle::dummy();
+    /// let report = db.check().unwrap();
+    /// if !report.is_consistent() {
+    ///     db.recover(RecoverOptions::new().backup(true).force(true)).unwrap();
+    /// }
+    /// ```
+    #[cfg(feature = "v1_20")]
+    pub fn check(&self) -> GdbmResult<ConsistencyReport> {
+        let mut report = ConsistencyReport {
+            needs_recovery: true,
+            buckets: 0,
+            expected_keys: 0,
+            count_error: None,
+            keys: 0,
+            unreadable_keys: Vec::new(),
+            walk_error: None,
+            avail_error: None,
+        };
+        // gdbm refuses to read a database that is already flagged
+        if self.needs_recovery() {
+            return Ok(report);
+        }
+
+        let mut buckets = 0;
+        if unsafe { gdbm_sys::gdbm_bucket_count(self.handle, &mut buckets) } != 0 {
+            return Err(Error::from_handle(self.handle));
+        }
+        let avail_error = if unsafe { gdbm_sys::gdbm_avail_verify(self.handle) } != 0 {
+            Some(Error::from_handle(self.handle))
+        } else {
+            None
+        };
+        let (expected_keys, count_error) = match self.count() {
+            Ok(count) => (count, None),
+            Err(e) => (0, Some(e)),
+        };
+
+        let mut keys = 0;
+        let mut unreadable_keys = Vec::new();
+        let mut walk_error = None;
+        clear_errno();
+        let mut key = self.first_key();
+        while let Some(k) = key {
+            keys += 1;
+            if self.fetch(&k).is_err() {
+                unreadable_keys.push(k.clone());
+            }
+            clear_errno();
+            key = self.next_key(&k);
+        }
+        match last_errno() {
+            0 | gdbm_sys::GDBM_ITEM_NOT_FOUND => {}
+            _ => walk_error = Some(Error::from_handle(self.handle)),
+        }
+
+        report.needs_recovery = self.needs_recovery();
+        report.buckets = buckets;
+        report.expected_keys = expected_keys;
+        report.count_error = count_error;
+        report.keys = keys;
+        report.unreadable_keys = unreadable_keys;
+        report.walk_error = walk_error;
+        report.avail_error = avail_error;
+        Ok(report)
+    }
+
+    #[cfg(feature = "v1_20")]
+    fn needs_recovery(&self) -> bool {
+        unsafe { gdbm_sys::gdbm_needs_recovery(self.handle) != 0 }
+    }
+}
+
+fn clear_errno() {
+    unsafe { *gdbm_sys::gdbm_errno_location() = 0 };
+}
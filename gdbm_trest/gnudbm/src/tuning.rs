@@ -0,0 +1,171 @@
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+#[cfg(feature = "v1_21")]
+use std::ffi::CString;
+#[cfg(feature = "v1_21")]
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use error::{Error, GdbmResult};
+use gdbm_sys;
+use super::{GdbmOpener, RwHandle};
+
+/// Options that are applied to a handle once gdbm has opened it, and again
+/// whenever it is reopened.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Tuning {
+    cache_size: Option<usize>,
+    max_mmap_size: Option<usize>,
+    #[cfg(feature = "v1_21")]
+    crash_tolerance: Option<(CString, CString)>,
+}
+
+impl Tuning {
+    pub(crate) fn apply(&self, db: &RwHandle) -> GdbmResult<()> {
+        if let Some(size) = self.cache_size {
+            db.set_opt(gdbm_sys::GDBM_SETCACHESIZE, size)?;
+        }
+        if let Some(size) = self.max_mmap_size {
+            db.set_opt(gdbm_sys::GDBM_SETMAXMAPSIZE, size)?;
+        }
+        #[cfg(feature = "v1_21")]
+        if let Some((ref even, ref odd)) = self.crash_tolerance {
+            let result = unsafe {
+                gdbm_sys::gdbm_failure_atomic(db.handle, even.as_ptr(), odd.as_ptr())
+            };
+            if result != 0 {
+                return Err(Error::from_handle(db.handle));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GdbmOpener {
+    /// Sets the permissions of the file if it is created, before the
+    /// process umask is applied. Defaults to `0o666`.
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Sets the block size of the file if it is created. gdbm rounds it up
+    /// to a power of two, and uses the file system's block size if this is
+    /// not set. An existing file keeps the block size it was created with.
+    pub fn block_size(&mut self, size: usize) -> &mut Self {
+        self.block_size = size as i32;
+        self
+    }
+
+    /// Sets the number of buckets kept in gdbm's bucket cache. By default
+    /// gdbm grows the cache as needed.
+    ///
+    /// See also [`RwHandle::set_cache_size`].
+    ///
+    /// [`RwHandle::set_cache_size`]: struct.RwHandle.html#method.set_cache_size
+    pub fn cache_size(&mut self, size: usize) -> &mut Self {
+        self.tuning.cache_size = Some(size);
+        self
+    }
+
+    /// Sets the maximum size of a memory mapped region. This will be rounded
+    /// to the nearest page boundary.
+    ///
+    /// See also [`RwHandle::set_max_mmap_size`].
+    ///
+    /// [`RwHandle::set_max_mmap_size`]: struct.RwHandle.html#method.set_max_mmap_size
+    pub fn max_mmap_size(&mut self, size: usize) -> &mut Self {
+        self.tuning.max_mmap_size = Some(size);
+        self
+    }
+
+    /// Sets the option to read the memory mapped file into memory when it
+    /// is opened, rather than as it is accessed. This speeds up the first
+    /// reads of a database that is read in full.
+    ///
+    /// This corresponds to gdbm's `GDBM_PREREAD` flag. It has no effect if
+    /// `no_mmap` is set. Requires the `v1_20` feature, and so gdbm 1.20 or
+    /// later; without it, opening fails with [`Error::Unsupported`].
+    ///
+    /// [`Error::Unsupported`]: error/enum.Error.html#variant.Unsupported
+    pub fn preread(&mut self, preread: bool) -> &mut Self {
+        self.preread = preread;
+        self
+    }
+
+    /// Enables crash tolerance: gdbm keeps two snapshot files, `even` and
+    /// `odd`, from which the last synced state of the database can be
+    /// restored after a crash.
+    ///
+    /// The snapshot files must be on the same file system as the database,
+    /// and that file system must support reflinks (e.g. XFS or Btrfs), or
+    /// syncing the database will fail. Requires the `v1_21` feature, and so
+    /// gdbm 1.21 or later; without it, opening fails with
+    /// [`Error::Unsupported`].
+    ///
+    /// [`Error::Unsupported`]: error/enum.Error.html#variant.Unsupported
+    pub fn crash_tolerance<P, Q>(&mut self, even: P, odd: Q) -> &mut Self
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        self.crash_tolerance = Some((even.as_ref().to_owned(), odd.as_ref().to_owned()));
+        self
+    }
+
+    /// Checks that gnudbm was built with support for every option that was
+    /// set, and builds the options to apply after opening.
+    pub(crate) fn tuning(&self) -> GdbmResult<Tuning> {
+        #[cfg(not(feature = "v1_20"))]
+        {
+            if self.preread {
+                return Err(unsupported("preread", (1, 20)));
+            }
+        }
+        let tuning = match self.crash_tolerance {
+            #[cfg(feature = "v1_21")]
+            Some((ref even, ref odd)) => Tuning {
+                crash_tolerance: Some((path_to_cstring(even)?, path_to_cstring(odd)?)),
+                ..self.tuning.clone()
+            },
+            #[cfg(not(feature = "v1_21"))]
+            Some(_) => return Err(unsupported("crash_tolerance", (1, 21))),
+            None => self.tuning.clone(),
+        };
+        Ok(tuning)
+    }
+}
+
+/// The error for an option that needs a newer gdbm than the version
+/// features gnudbm was built with.
+#[cfg(not(feature = "v1_21"))]
+fn unsupported(option: &'static str, requires: (i32, i32)) -> Error {
+    Error::Unsupported { option, requires }
+}
+
+#[cfg(feature = "v1_21")]
+fn path_to_cstring(path: &Path) -> GdbmResult<CString> {
+    Ok(CString::new(path.as_os_str().as_bytes())?)
+}
@@ -0,0 +1,124 @@
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use std::marker::PhantomData;
+
+use codec::Codec;
+use error::{Error, GdbmResult};
+use super::RwHandle;
+
+/// Ties the key and value types to a handle without owning either.
+type Types<K, V> = PhantomData<(fn(&K), fn() -> V)>;
+
+/// A read/write handle whose keys are always `K` and whose values are always
+/// `V`, encoded with the codec `C`.
+///
+/// Created with [`GdbmOpener::readwrite_typed`], or from an existing handle
+/// with [`TypedHandle::new`].
+///
+/// [`GdbmOpener::readwrite_typed`]: struct.GdbmOpener.html#method.readwrite_typed
+/// [`TypedHandle::new`]: #method.new
+///
+/// # Examples
+///
+/// ```no_run
+/// # use gnudbm::*;
+/// let mut db = GdbmOpener::new()
+///     .create(true)
+///     .readwrite_typed::<str, Vec<u64>, _, _>("klines.db", Json)
+///     .unwrap();
+///
+/// db.store("1609430400", &vec![1, 2, 3]).unwrap();
+/// let fetched: Vec<u64> = db.fetch("1609430400").unwrap();
+/// assert_eq!(fetched, vec![1, 2, 3]);
+/// ```
+#[derive(Debug)]
+pub struct TypedHandle<K: ?Sized, V, C> {
+    db: RwHandle,
+    codec: C,
+    _types: Types<K, V>,
+}
+
+impl<K, V, C> TypedHandle<K, V, C>
+where
+    K: ?Sized + AsRef<[u8]>,
+    C: Codec<V>,
+{
+    /// Wraps `db`, using `codec` for every value.
+    pub fn new(db: RwHandle, codec: C) -> Self {
+        TypedHandle { db, codec, _types: PhantomData }
+    }
+
+    /// Inserts a key value pair into the database, replacing any existing
+    /// value for that key. See [`RwHandle::store`].
+    ///
+    /// [`RwHandle::store`]: struct.RwHandle.html#method.store
+    pub fn store(&mut self, key: &K, value: &V) -> GdbmResult<()> {
+        self.db.store_with(&self.codec, key, value)
+    }
+
+    /// Inserts a key value pair into the database, failing if the key already
+    /// exists. See [`RwHandle::store_checked`].
+    ///
+    /// [`RwHandle::store_checked`]: struct.RwHandle.html#method.store_checked
+    pub fn store_checked(&mut self, key: &K, value: &V) -> GdbmResult<()> {
+        let bytes = self.codec.encode(value)?;
+        match self.db.store_bytes(key.as_ref(), &bytes, false)? {
+            1 => Err(Error::KeyExists),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fetches and decodes the value stored for `key`.
+    pub fn fetch(&self, key: &K) -> GdbmResult<V> {
+        self.db.fetch(key)?.decode(&self.codec)
+    }
+
+    /// Removes an entry from the database. See [`RwHandle::remove`].
+    ///
+    /// [`RwHandle::remove`]: struct.RwHandle.html#method.remove
+    pub fn remove(&self, key: &K) -> GdbmResult<bool> {
+        self.db.remove(key)
+    }
+
+    /// Checks the database for the existence of `key`.
+    pub fn contains_key(&self, key: &K) -> GdbmResult<bool> {
+        self.db.contains_key(key.as_ref())
+    }
+
+    /// Counts the number of items in this database. This is not cached.
+    pub fn count(&self) -> GdbmResult<usize> {
+        self.db.count()
+    }
+
+    /// Returns the underlying untyped handle, e.g. for iteration.
+    pub fn as_raw(&self) -> &RwHandle {
+        &self.db
+    }
+
+    /// Consumes `self`, returning the underlying untyped handle.
+    pub fn into_raw(self) -> RwHandle {
+        self.db
+    }
+}
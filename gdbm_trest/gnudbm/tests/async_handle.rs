@@ -0,0 +1,134 @@
+#![cfg(feature = "async")]
+
+extern crate futures_core;
+extern crate gnudbm;
+extern crate tokio;
+
+use std::collections::BTreeSet;
+use std::fs::remove_file;
+use std::future::{poll_fn, Future};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::time::Duration;
+
+use futures_core::Stream;
+use gnudbm::{EntryBuf, EntryStream, GdbmOpener, GdbmResult, Json};
+use tokio::runtime::{Builder, Runtime};
+
+fn db_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("gnudbm-async-{}-{}.db", name, std::process::id()));
+    let _ = remove_file(&path);
+    path
+}
+
+fn runtime() -> Runtime {
+    Builder::new_current_thread().enable_time().build().unwrap()
+}
+
+fn wait<F: Future>(rt: &Runtime, f: F) -> F::Output {
+    rt.block_on(f)
+}
+
+fn next(rt: &Runtime, stream: &mut EntryStream) -> Option<GdbmResult<(Vec<u8>, EntryBuf)>> {
+    rt.block_on(poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)))
+}
+
+#[test]
+fn store_fetch_remove() {
+    let rt = runtime();
+    let _enter = rt.enter();
+    let path = db_path("basic");
+    let db = wait(&rt, GdbmOpener::new().create(true).readwrite_async(&path)).unwrap();
+
+    wait(&rt, db.store("max_epoch", &1609430400u64)).unwrap();
+    wait(&rt, db.store_with(&Json, "1609430400", &vec![1.5f64, 2.5])).unwrap();
+    assert_eq!(wait(&rt, db.count()).unwrap(), 2);
+
+    let entry = wait(&rt, db.fetch("max_epoch")).unwrap();
+    assert_eq!(entry.deserialize::<u64>().unwrap(), 1609430400);
+    let entry = wait(&rt, db.fetch("1609430400")).unwrap();
+    assert_eq!(entry.decode::<Vec<f64>, _>(&Json).unwrap(), vec![1.5, 2.5]);
+
+    assert!(wait(&rt, db.fetch("missing")).unwrap_err().is_no_record());
+    assert!(wait(&rt, db.remove("max_epoch")).unwrap());
+    assert!(!wait(&rt, db.remove("max_epoch")).unwrap());
+    assert!(!wait(&rt, db.contains_key("max_epoch")).unwrap());
+
+    drop(db);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn open_errors_are_reported() {
+    let rt = runtime();
+    let _enter = rt.enter();
+    let path = db_path("missing");
+    assert!(wait(&rt, GdbmOpener::new().readonly_async(&path)).is_err());
+}
+
+#[test]
+fn stream_visits_every_entry() {
+    let rt = runtime();
+    let _enter = rt.enter();
+    let path = db_path("stream");
+    let db = wait(&rt, GdbmOpener::new().create(true).readwrite_async(&path)).unwrap();
+    for i in 0..500u64 {
+        wait(&rt, db.store(format!("{}", 1609430400 + i * 60), &i)).unwrap();
+    }
+    wait(&rt, db.sync()).unwrap();
+    let reader = wait(&rt, GdbmOpener::new().no_lock(true).readonly_async(&path)).unwrap();
+
+    let mut stream = reader.iter();
+    let mut seen = BTreeSet::new();
+    while let Some(entry) = next(&rt, &mut stream) {
+        let (key, value) = entry.unwrap();
+        let i: u64 = value.deserialize().unwrap();
+        assert_eq!(key, format!("{}", 1609430400 + i * 60).into_bytes());
+        seen.insert(i);
+    }
+    assert_eq!(seen, (0..500).collect());
+
+    drop(reader);
+    drop(db);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn dropped_stream_and_requests_leave_handle_usable() {
+    let rt = runtime();
+    let _enter = rt.enter();
+    let path = db_path("cancel");
+    let db = wait(&rt, GdbmOpener::new().create(true).readwrite_async(&path)).unwrap();
+    for i in 0..200u64 {
+        wait(&rt, db.store(format!("key {}", i), &i)).unwrap();
+    }
+
+    let mut stream = db.iter();
+    assert!(next(&rt, &mut stream).is_some());
+    drop(stream);
+
+    // requests dropped before they finish are skipped or discarded
+    for i in 0..50u64 {
+        drop(db.fetch(format!("key {}", i)));
+        drop(db.iter());
+    }
+
+    let _entered = rt.enter();
+    let count = wait(&rt, tokio::time::timeout(Duration::from_secs(5), db.count()));
+    assert_eq!(count.expect("worker is stuck").unwrap(), 200);
+
+    // other requests can run while a stream is only partly consumed
+    let mut stream = db.iter();
+    let mut n = 0;
+    while let Some(entry) = next(&rt, &mut stream) {
+        entry.unwrap();
+        if n % 50 == 0 {
+            wait(&rt, db.fetch("key 0")).unwrap();
+        }
+        n += 1;
+    }
+    assert_eq!(n, 200);
+
+    drop(db);
+    remove_file(&path).unwrap();
+}
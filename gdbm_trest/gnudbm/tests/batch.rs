@@ -0,0 +1,188 @@
+extern crate gnudbm;
+
+use std::env;
+use std::fs::remove_file;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use gnudbm::{GdbmOpener, Json, RwHandle};
+
+fn db_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("gnudbm-{}-{}.db", name, std::process::id()));
+    let _ = remove_file(&path);
+    let _ = remove_file(path.with_extension("db.batch"));
+    path
+}
+
+fn seed(path: &PathBuf, n: usize) -> RwHandle {
+    let mut db = GdbmOpener::new().create(true).readwrite(path).expect("open");
+    for i in 0..n {
+        db.store(format!("old {}", i), &vec![i as u64; 16]).unwrap();
+    }
+    db
+}
+
+#[test]
+fn commit_applies_everything() {
+    let path = db_path("commit");
+    let mut db = seed(&path, 10);
+
+    let mut batch = db.batch();
+    batch.store("new", "value").unwrap();
+    batch.remove("old 0");
+    batch.remove("missing");
+    assert_eq!(batch.len(), 3);
+    batch.commit().unwrap();
+
+    assert_eq!(db.count().unwrap(), 10);
+    assert!(!db.contains_key(b"old 0").unwrap());
+    let entry = db.fetch("new").unwrap();
+    assert_eq!(entry.deserialize::<&str>().unwrap(), "value");
+    drop(entry);
+
+    // the handle is still writable after the commit
+    db.store("after", &1u8).unwrap();
+    assert!(!path.with_extension("db.batch").exists());
+
+    drop(db);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn store_with_codec() {
+    let path = db_path("codec");
+    let mut db = seed(&path, 1);
+
+    let mut batch = db.batch();
+    batch.store_with(&Json, "max_epoch", &1609430400u64).unwrap();
+    batch.store_with(&Json, "1609430400", &vec![36.5, 36.75]).unwrap();
+    batch.commit().unwrap();
+
+    let entry = db.fetch("max_epoch").unwrap();
+    assert_eq!(entry.as_bytes(), b"1609430400");
+    drop(entry);
+    let prices: Vec<f64> = db.fetch("1609430400").unwrap().decode(&Json).unwrap();
+    assert_eq!(prices, vec![36.5, 36.75]);
+
+    drop(db);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn drop_rolls_back() {
+    let path = db_path("drop");
+    let mut db = seed(&path, 10);
+
+    {
+        let mut batch = db.batch();
+        batch.store("new", "value").unwrap();
+        batch.remove("old 1");
+    }
+    let mut batch = db.batch();
+    batch.remove("old 2");
+    batch.rollback();
+
+    assert_eq!(db.count().unwrap(), 10);
+    assert!(!db.contains_key(b"new").unwrap());
+    assert!(db.contains_key(b"old 1").unwrap());
+    assert!(db.contains_key(b"old 2").unwrap());
+
+    drop(db);
+    remove_file(&path).unwrap();
+}
+
+const OLD: usize = 200;
+const NEW: usize = 5000;
+
+/// Set, to the database path, when this test binary is run by
+/// `commit_in_child`.
+const CHILD_ENV: &str = "GNUDBM_BATCH_CHILD";
+
+/// Not a test of its own: commits a batch replacing every `old` key with
+/// `NEW` new ones when run by `commit_in_child`, does nothing otherwise.
+#[test]
+fn batch_child() {
+    let path = match env::var_os(CHILD_ENV) {
+        Some(path) => PathBuf::from(path),
+        None => return,
+    };
+    let mut db = GdbmOpener::new().readwrite(&path).expect("open in child");
+    let mut batch = db.batch();
+    for i in 0..NEW {
+        batch.store(format!("new {}", i), &vec![i as u64; 64]).unwrap();
+    }
+    for i in 0..OLD {
+        batch.remove(format!("old {}", i));
+    }
+    batch.commit().unwrap();
+}
+
+/// Runs `batch_child` in a new process of this test binary. The child is
+/// killed after `kill_after`, if given.
+fn commit_in_child(path: &PathBuf, kill_after: Option<Duration>) {
+    let mut child = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "batch_child", "--test-threads", "1"])
+        .env(CHILD_ENV, path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("spawn child");
+
+    match kill_after {
+        Some(delay) => {
+            thread::sleep(delay);
+            let _ = child.kill();
+            child.wait().unwrap();
+        }
+        None => assert!(child.wait().unwrap().success(), "child commit failed"),
+    }
+}
+
+/// Checks that the database holds either all of the child's batch or none
+/// of it, and puts the original contents back. Returns `true` if the batch
+/// had been applied.
+fn check_and_restore(path: &PathBuf) -> bool {
+    let mut db = GdbmOpener::new().readwrite(path).expect("reopen");
+    let count = db.count().unwrap();
+    let has_new = db.contains_key(b"new 0").unwrap();
+    if count == OLD && !has_new {
+        return false;
+    }
+    assert!(count == NEW && has_new, "partial batch: {} items, new keys: {}", count, has_new);
+    assert!(db.contains_key(format!("new {}", NEW - 1).as_bytes()).unwrap());
+    assert!(!db.contains_key(b"old 0").unwrap());
+
+    let mut batch = db.batch();
+    for i in 0..NEW {
+        batch.remove(format!("new {}", i));
+    }
+    for i in 0..OLD {
+        batch.store(format!("old {}", i), &vec![i as u64; 16]).unwrap();
+    }
+    batch.commit().unwrap();
+    true
+}
+
+/// Kills a process at different points while it commits a large batch and
+/// checks that the database never ends up half-updated.
+#[test]
+fn killed_commit_is_all_or_nothing() {
+    let path = db_path("kill");
+    drop(seed(&path, OLD));
+
+    // time an uninterrupted commit, so the kills below are spread over it
+    let start = Instant::now();
+    commit_in_child(&path, None);
+    let full = start.elapsed();
+    assert!(check_and_restore(&path));
+
+    for step in 0..24u32 {
+        commit_in_child(&path, Some(full * step / 16));
+        check_and_restore(&path);
+    }
+
+    let _ = remove_file(path.with_extension("db.batch"));
+    remove_file(&path).unwrap();
+}
@@ -0,0 +1,112 @@
+extern crate gnudbm;
+#[macro_use]
+extern crate serde;
+
+use std::fs::remove_file;
+use std::path::PathBuf;
+
+use gnudbm::{Bincode, Codec, GdbmOpener, Json, MsgPack, Raw, TypedHandle};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Kline {
+    id: u64,
+    open: f64,
+    close: f64,
+    high: f64,
+    low: f64,
+    count: f64,
+    amount: f64,
+    vol: f64,
+}
+
+fn klines() -> Vec<Kline> {
+    (0..3)
+        .map(|i| Kline {
+            id: 1609430400 + i * 60,
+            open: 0.5,
+            close: 0.75,
+            high: 1.0,
+            low: 0.25,
+            count: 10.0,
+            amount: 100.0,
+            vol: 50.0,
+        })
+        .collect()
+}
+
+fn db_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("gnudbm-codec-{}-{}.db", name, std::process::id()));
+    let _ = remove_file(&path);
+    path
+}
+
+fn round_trip<C: Codec<Vec<Kline>>>(name: &str, codec: C) -> Vec<u8> {
+    let path = db_path(name);
+    let mut db: TypedHandle<str, Vec<Kline>, C> = GdbmOpener::new()
+        .create(true)
+        .readwrite_typed(&path, codec)
+        .expect("open");
+
+    db.store("1609430400", &klines()).unwrap();
+    assert_eq!(db.fetch("1609430400").unwrap(), klines());
+    assert!(db.store_checked("1609430400", &klines()).is_err());
+    assert!(db.fetch("missing").is_err());
+
+    let raw = db.as_raw().fetch("1609430400").unwrap().as_bytes().to_vec();
+    drop(db);
+    remove_file(&path).unwrap();
+    raw
+}
+
+#[test]
+fn bincode_round_trip() {
+    let raw = round_trip("bincode", Bincode);
+    assert_eq!(raw, Bincode.encode(&klines()).unwrap());
+}
+
+#[test]
+fn json_round_trip() {
+    let raw = round_trip("json", Json);
+    assert!(raw.starts_with(b"[{\"id\":1609430400,"));
+}
+
+#[test]
+fn msgpack_round_trip() {
+    let raw = round_trip("msgpack", MsgPack);
+    let decoded: Vec<Kline> = MsgPack.decode(&raw).unwrap();
+    assert_eq!(decoded, klines());
+}
+
+#[test]
+fn raw_round_trip() {
+    let path = db_path("raw");
+    let mut db = GdbmOpener::new()
+        .create(true)
+        .readwrite_typed::<str, Vec<u8>, _, _>(&path, Raw)
+        .expect("open");
+    db.store("bytes", &vec![0, 159, 146, 150]).unwrap();
+    assert_eq!(db.fetch("bytes").unwrap(), vec![0, 159, 146, 150]);
+
+    let mut db = TypedHandle::<str, String, _>::new(db.into_raw(), Raw);
+    db.store("text", &"max_epoch".to_string()).unwrap();
+    assert_eq!(db.as_raw().fetch("text").unwrap().as_bytes(), b"max_epoch");
+    assert!(db.fetch("bytes").is_err());
+
+    drop(db);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn untyped_handle_with_codec() {
+    let path = db_path("untyped");
+    let mut db = GdbmOpener::new().create(true).readwrite(&path).expect("open");
+    db.store_with(&Json, "max_epoch", &1609430400u64).unwrap();
+
+    let entry = db.fetch("max_epoch").unwrap();
+    assert_eq!(entry.as_bytes(), b"1609430400");
+    assert_eq!(entry.decode::<u64, _>(&Json).unwrap(), 1609430400);
+    drop(entry);
+
+    drop(db);
+    remove_file(&path).unwrap();
+}
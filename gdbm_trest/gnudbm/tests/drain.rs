@@ -0,0 +1,135 @@
+extern crate gnudbm;
+
+use std::collections::BTreeSet;
+use std::fs::remove_file;
+use std::path::PathBuf;
+
+use gnudbm::{GdbmOpener, RwHandle};
+
+const START: u64 = 1609430400;
+const DAY: u64 = 24 * 60 * 60;
+
+fn db_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("gnudbm-drain-{}-{}.db", name, std::process::id()));
+    let _ = remove_file(&path);
+    path
+}
+
+/// Stores 10 days of 5 minute klines, plus `max_epoch`.
+fn seed(path: &PathBuf) -> RwHandle {
+    let mut db = GdbmOpener::new().create(true).readwrite(path).unwrap();
+    for epoch in (START..START + 10 * DAY).step_by(300) {
+        db.store(epoch.to_string(), &vec![epoch; 8]).unwrap();
+    }
+    db.store("max_epoch", &(START + 10 * DAY - 300)).unwrap();
+    db
+}
+
+fn epoch(key: &[u8]) -> Option<u64> {
+    std::str::from_utf8(key).ok().and_then(|k| k.parse().ok())
+}
+
+fn keys(db: &RwHandle) -> BTreeSet<Vec<u8>> {
+    db.iter().map(|(k, _)| k.as_bytes().to_vec()).collect()
+}
+
+#[test]
+fn retain_prunes_old_klines() {
+    let path = db_path("retain");
+    let mut db = seed(&path);
+    let before = keys(&db);
+    let cutoff = START + 7 * DAY;
+
+    let mut visited = Vec::new();
+    let removed = db
+        .retain(|key, entry| {
+            visited.push(key.to_vec());
+            match epoch(key) {
+                Some(epoch) => {
+                    assert_eq!(entry.deserialize::<Vec<u64>>().unwrap()[0], epoch);
+                    epoch >= cutoff
+                }
+                None => true,
+            }
+        })
+        .unwrap();
+
+    // every entry was seen exactly once, even though entries were being removed
+    assert_eq!(visited.len(), before.len());
+    assert_eq!(visited.into_iter().collect::<BTreeSet<_>>(), before);
+
+    assert_eq!(removed, (7 * DAY / 300) as usize);
+    let after = keys(&db);
+    assert_eq!(after.len(), before.len() - removed);
+    assert_eq!(db.count().unwrap(), after.len());
+    for key in &before {
+        let kept = epoch(key).filter(|&e| e < cutoff).is_none();
+        assert_eq!(after.contains(key), kept, "{:?}", String::from_utf8_lossy(key));
+    }
+
+    drop(db);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn drain_filter_yields_what_it_removes() {
+    let path = db_path("drain");
+    let mut db = seed(&path);
+    let total = db.count().unwrap();
+
+    let mut drained = Vec::new();
+    for removed in db.drain_filter(|key, _| epoch(key).filter(|e| e % 3600 == 0).is_some()).unwrap() {
+        let (key, entry) = removed.unwrap();
+        assert_eq!(entry.deserialize::<Vec<u64>>().unwrap()[0], epoch(&key).unwrap());
+        drained.push(key);
+    }
+
+    assert_eq!(drained.len(), 240);
+    assert_eq!(db.count().unwrap(), total - 240);
+    for key in &drained {
+        assert!(!db.contains_key(key).unwrap());
+    }
+
+    drop(db);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn dropped_drain_keeps_the_rest() {
+    let path = db_path("partial");
+    let mut db = seed(&path);
+    let total = db.count().unwrap();
+
+    let taken: Vec<_> = db
+        .drain_filter(|key, _| epoch(key).is_some())
+        .unwrap()
+        .take(100)
+        .map(|r| r.unwrap().0)
+        .collect();
+
+    assert_eq!(taken.len(), 100);
+    assert_eq!(db.count().unwrap(), total - 100);
+
+    // draining everything that is left empties the database
+    let rest = db.drain_filter(|_, _| true).unwrap().count();
+    assert_eq!(rest, total - 100);
+    assert_eq!(db.count().unwrap(), 0);
+
+    drop(db);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn retain_keeps_key_index_in_step() {
+    let path = db_path("index");
+    drop(seed(&path));
+    let mut db = GdbmOpener::new().key_index(true).readwrite(&path).unwrap();
+
+    db.retain(|key, _| !key.starts_with(b"16094")).unwrap();
+    let left: Vec<_> = db.range::<str, _>(..).unwrap().collect();
+    assert_eq!(left, keys(&db).into_iter().collect::<Vec<_>>());
+    assert!(db.iter_prefix("16094").unwrap().next().is_none());
+
+    drop(db);
+    remove_file(&path).unwrap();
+}
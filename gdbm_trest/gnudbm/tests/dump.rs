@@ -0,0 +1,118 @@
+extern crate gnudbm;
+
+use std::collections::BTreeMap;
+use std::fs::remove_file;
+use std::path::PathBuf;
+
+use gnudbm::{DumpFormat, GdbmOpener, Raw, RwHandle};
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("gnudbm-dump-{}-{}", name, std::process::id()));
+    let _ = remove_file(&path);
+    path
+}
+
+fn contents(db: &RwHandle) -> BTreeMap<Vec<u8>, Vec<u8>> {
+    db.iter()
+        .map(|(k, v)| (k.as_bytes().to_vec(), v.as_bytes().to_vec()))
+        .collect()
+}
+
+fn seed(path: &PathBuf) -> RwHandle {
+    let mut db = GdbmOpener::new().create(true).readwrite(path).unwrap();
+    for i in 0..1000u64 {
+        db.store(format!("{}", 1609430400 + i * 60), &vec![i; 8]).unwrap();
+    }
+    // bytes that are not valid text, and a value with line breaks
+    db.store_with(&Raw, [0u8, 0xff, b'\n', 0x80], &vec![0xfeu8, 0, b'\r', b'\n']).unwrap();
+    db.store_with(&Raw, "multi\nline", &"a\nb\n".to_string()).unwrap();
+    db
+}
+
+fn round_trip(format: DumpFormat, name: &str) {
+    let src = temp_path(&format!("{}-src.db", name));
+    let dump = temp_path(&format!("{}.dump", name));
+    let dst = temp_path(&format!("{}-dst.db", name));
+
+    let db = seed(&src);
+    db.export_to(&dump, format).unwrap();
+
+    let copy = GdbmOpener::new().create(true).import_from(&dump, &dst).unwrap();
+    assert_eq!(copy.count().unwrap(), 1002);
+    assert_eq!(contents(&copy), contents(&db));
+
+    drop(copy);
+    drop(db);
+    for path in &[src, dump, dst] {
+        remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn ascii_round_trip() {
+    round_trip(DumpFormat::Ascii, "ascii");
+}
+
+#[test]
+fn binary_round_trip() {
+    round_trip(DumpFormat::Binary, "binary");
+}
+
+#[test]
+fn import_replaces_existing_keys() {
+    let src = temp_path("replace-src.db");
+    let dump = temp_path("replace.dump");
+    let dst = temp_path("replace-dst.db");
+
+    let db = seed(&src);
+    db.export_to(&dump, DumpFormat::Ascii).unwrap();
+
+    let mut existing = GdbmOpener::new().create(true).readwrite(&dst).unwrap();
+    existing.store("1609430400", "stale").unwrap();
+    existing.store("only here", "kept").unwrap();
+    drop(existing);
+
+    let copy = GdbmOpener::new().import_from(&dump, &dst).unwrap();
+    assert_eq!(copy.count().unwrap(), 1003);
+    assert_eq!(copy.fetch("1609430400").unwrap().deserialize::<Vec<u64>>().unwrap(), vec![0; 8]);
+    assert_eq!(copy.fetch("only here").unwrap().deserialize::<&str>().unwrap(), "kept");
+
+    drop(copy);
+    drop(db);
+    for path in &[src, dump, dst] {
+        remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn import_fills_key_index() {
+    let src = temp_path("index-src.db");
+    let dump = temp_path("index.dump");
+    let dst = temp_path("index-dst.db");
+
+    let db = seed(&src);
+    db.export_to(&dump, DumpFormat::Binary).unwrap();
+
+    let copy = GdbmOpener::new().create(true).key_index(true).import_from(&dump, &dst).unwrap();
+    let keys: Vec<Vec<u8>> = contents(&db).into_keys().collect();
+    assert_eq!(copy.range::<[u8], _>(..).unwrap().collect::<Vec<_>>(), keys);
+    assert_eq!(copy.iter_prefix("1609430").unwrap().count(), 10);
+
+    drop(copy);
+    drop(db);
+    for path in &[src, dump, dst] {
+        remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn import_of_garbage_fails() {
+    let dump = temp_path("garbage.dump");
+    let dst = temp_path("garbage-dst.db");
+    std::fs::write(&dump, b"this is not a gdbm dump\n").unwrap();
+
+    assert!(GdbmOpener::new().create(true).import_from(&dump, &dst).is_err());
+
+    let _ = remove_file(&dst);
+    remove_file(&dump).unwrap();
+}
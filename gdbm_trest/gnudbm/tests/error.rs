@@ -0,0 +1,98 @@
+extern crate gnudbm;
+
+use std::error::Error as StdError;
+use std::fs::{self, remove_file, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use gnudbm::{Error, GdbmOpener};
+
+fn db_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("gnudbm-error-{}-{}.db", name, std::process::id()));
+    let _ = remove_file(&path);
+    path
+}
+
+#[test]
+fn missing_file_chains_io_error() {
+    let path = db_path("missing");
+    let err = GdbmOpener::new().readonly(&path).unwrap_err();
+
+    match err {
+        Error::FileOpen(ref e) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+        ref other => panic!("unexpected error: {:?}", other),
+    }
+    let source = err.source().expect("no source");
+    assert_eq!(source.downcast_ref::<io::Error>().unwrap().kind(), io::ErrorKind::NotFound);
+    assert_eq!(err.gdbm_errno(), Some(3));
+    assert!(!err.is_retryable());
+    assert!(!err.is_corruption());
+    assert_eq!(err.to_string(), "File open error");
+}
+
+#[test]
+fn not_a_database() {
+    let path = db_path("garbage");
+    fs::write(&path, vec![0x5a; 4096]).unwrap();
+    let err = GdbmOpener::new().readonly(&path).unwrap_err();
+    assert!(matches!(err, Error::BadMagicNumber), "{:?}", err);
+    assert!(err.source().is_none());
+
+    fs::write(&path, b"").unwrap();
+    let err = GdbmOpener::new().readonly(&path).unwrap_err();
+    assert!(matches!(err, Error::EmptyDatabase), "{:?}", err);
+
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn second_writer_is_locked_out() {
+    let path = db_path("locked");
+    let _db = GdbmOpener::new().create(true).readwrite(&path).unwrap();
+
+    let err = GdbmOpener::new().readwrite(&path).unwrap_err();
+    assert!(matches!(err, Error::WriteLockFailed), "{:?}", err);
+    assert!(err.is_retryable());
+    assert!(!err.is_corruption());
+
+    let err = GdbmOpener::new().readonly(&path).unwrap_err();
+    assert!(matches!(err, Error::ReadLockFailed), "{:?}", err);
+    assert!(err.is_retryable());
+
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn damaged_header_is_corruption() {
+    let path = db_path("damaged");
+    let mut db = GdbmOpener::new().create(true).readwrite(&path).unwrap();
+    for i in 0..100u32 {
+        db.store(i.to_string(), &i).unwrap();
+    }
+    drop(db);
+
+    // point the bucket directory somewhere that is not a directory
+    let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+    file.seek(SeekFrom::Start(8)).unwrap();
+    file.write_all(&[0xff; 4]).unwrap();
+    drop(file);
+
+    let err = GdbmOpener::new().readonly(&path).unwrap_err();
+    assert!(err.is_corruption(), "{:?}", err);
+    assert!(!err.is_retryable());
+
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn errors_outside_gdbm() {
+    let err = GdbmOpener::new().readonly("nul\0byte").unwrap_err();
+    assert!(matches!(err, Error::InvalidPath));
+    assert_eq!(err.gdbm_errno(), None);
+
+    let err = Error::from(io::Error::new(io::ErrorKind::Interrupted, "signal"));
+    assert!(err.is_retryable());
+    // the io::Error is only shown through `source`
+    assert_eq!(err.to_string(), "I/O error");
+    assert_eq!(err.source().unwrap().to_string(), "signal");
+}
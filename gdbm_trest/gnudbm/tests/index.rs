@@ -0,0 +1,126 @@
+extern crate gnudbm;
+
+use std::fs::remove_file;
+use std::path::PathBuf;
+
+use gnudbm::{GdbmOpener, RwHandle};
+
+fn db_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("gnudbm-index-{}-{}.db", name, std::process::id()));
+    let _ = remove_file(&path);
+    let _ = remove_file(path.with_extension("db.batch"));
+    path
+}
+
+fn strings(keys: Vec<Vec<u8>>) -> Vec<String> {
+    keys.into_iter().map(|k| String::from_utf8(k).unwrap()).collect()
+}
+
+fn epochs(from: u64, n: u64) -> Vec<String> {
+    (0..n).map(|i| format!("{}", from + i * 60)).collect()
+}
+
+/// Stores an hour of minute epochs plus `max_epoch`, in reverse order.
+fn seed(db: &mut RwHandle) {
+    for key in epochs(1609430400, 60).iter().rev() {
+        db.store(key, &0u8).unwrap();
+    }
+    db.store("max_epoch", &1609433940u64).unwrap();
+}
+
+#[test]
+fn range_and_prefix_are_sorted() {
+    let path = db_path("sorted");
+    let mut db = GdbmOpener::new().create(true).key_index(true).readwrite(&path).unwrap();
+    seed(&mut db);
+
+    let all = strings(db.range::<str, _>(..).unwrap().collect());
+    let mut expected = epochs(1609430400, 60);
+    expected.push("max_epoch".into());
+    assert_eq!(all, expected);
+
+    let ten = strings(db.range("1609430400".."1609431000").unwrap().collect());
+    assert_eq!(ten, epochs(1609430400, 10));
+    let inclusive = strings(db.range("1609431000"..="1609431120").unwrap().collect());
+    assert_eq!(inclusive, epochs(1609431000, 3));
+    assert_eq!(db.range("1609431000".."1609430000").unwrap().count(), 0);
+
+    assert_eq!(strings(db.iter_prefix("max").unwrap().collect()), vec!["max_epoch"]);
+    assert_eq!(db.iter_prefix("1609433").unwrap().count(), 16);
+    assert_eq!(db.iter_prefix("").unwrap().count(), 61);
+    assert_eq!(db.iter_prefix("zzz").unwrap().count(), 0);
+
+    drop(db);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn index_follows_writes() {
+    let path = db_path("writes");
+    let mut db = GdbmOpener::new().create(true).readwrite(&path).unwrap();
+    seed(&mut db);
+    drop(db);
+
+    // the index is built from the existing keys on open
+    let mut db = GdbmOpener::new().key_index(true).readwrite(&path).unwrap();
+    assert_eq!(db.range::<str, _>(..).unwrap().count(), 61);
+
+    db.remove("1609430400").unwrap();
+    db.store("1609430340", &0u8).unwrap();
+    assert!(db.store_checked("1609430340", &0u8).is_err());
+    let first = strings(db.range::<str, _>(..).unwrap().take(2).collect());
+    assert_eq!(first, vec!["1609430340", "1609430460"]);
+
+    let mut batch = db.batch();
+    batch.store("1609434000", &0u8).unwrap();
+    batch.remove("max_epoch");
+    batch.commit().unwrap();
+    let last = strings(db.iter_prefix("16094340").unwrap().collect());
+    assert_eq!(last, vec!["1609434000"]);
+    assert_eq!(db.iter_prefix("max").unwrap().count(), 0);
+
+    // a rolled back batch leaves the index alone
+    let mut batch = db.batch();
+    batch.remove("1609434000");
+    batch.rollback();
+    assert_eq!(db.iter_prefix("16094340").unwrap().count(), 1);
+
+    drop(db);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn removing_while_scanning() {
+    let path = db_path("scan");
+    let mut db = GdbmOpener::new().create(true).key_index(true).readwrite(&path).unwrap();
+    seed(&mut db);
+
+    let mut seen = 0;
+    for key in db.range("1609430400".."1609433940").unwrap() {
+        db.remove(&key).unwrap();
+        seen += 1;
+    }
+    assert_eq!(seen, 59);
+    assert_eq!(db.count().unwrap(), 2);
+    assert_eq!(strings(db.range::<str, _>(..).unwrap().collect()), vec!["1609433940", "max_epoch"]);
+
+    drop(db);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn scans_need_an_index() {
+    let path = db_path("noindex");
+    let mut db = GdbmOpener::new().create(true).readwrite(&path).unwrap();
+    seed(&mut db);
+
+    assert!(db.iter_prefix("1609").is_err());
+    assert!(db.range("1609430400".."1609431000").is_err());
+    drop(db);
+
+    let db = GdbmOpener::new().key_index(true).readonly(&path).unwrap();
+    assert_eq!(db.iter_prefix("1609").unwrap().count(), 60);
+
+    drop(db);
+    remove_file(&path).unwrap();
+}
@@ -0,0 +1,109 @@
+extern crate gnudbm;
+
+use std::fs::{metadata, remove_file};
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+
+use gnudbm::{Error, GdbmOpener};
+
+fn db_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("gnudbm-opener-{}-{}.db", name, std::process::id()));
+    let _ = remove_file(&path);
+    path
+}
+
+#[test]
+fn mode_applies_to_new_files() {
+    let path = db_path("mode");
+    drop(GdbmOpener::new().create(true).mode(0o600).readwrite(&path).unwrap());
+    let mode = metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn block_size_is_used_when_creating() {
+    let path = db_path("block");
+    let db = GdbmOpener::new().create(true).block_size(8192).readwrite(&path).unwrap();
+    assert_eq!(db.get_block_size().unwrap(), 8192);
+    drop(db);
+
+    // an existing file keeps its block size
+    let db = GdbmOpener::new().block_size(1024).readwrite(&path).unwrap();
+    assert_eq!(db.get_block_size().unwrap(), 8192);
+    drop(db);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn tuning_is_applied_and_survives_batches() {
+    let path = db_path("tuning");
+    let mut db = GdbmOpener::new()
+        .create(true)
+        .cache_size(16)
+        .max_mmap_size(1 << 20)
+        .readwrite(&path)
+        .unwrap();
+    assert_eq!(db.get_cache_size().unwrap(), 16);
+    assert_eq!(db.get_max_mmap_size().unwrap(), 1 << 20);
+
+    let mut batch = db.batch();
+    batch.store("1609430400", &1u64).unwrap();
+    batch.commit().unwrap();
+    assert_eq!(db.get_cache_size().unwrap(), 16);
+    assert_eq!(db.get_max_mmap_size().unwrap(), 1 << 20);
+    assert_eq!(db.fetch("1609430400").unwrap().deserialize::<u64>().unwrap(), 1);
+    drop(db);
+
+    let db = GdbmOpener::new().readonly(&path).unwrap();
+    assert_eq!(db.count().unwrap(), 1);
+    drop(db);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn preread_needs_v1_20() {
+    let path = db_path("preread");
+    let result = GdbmOpener::new().create(true).preread(true).readwrite(&path);
+    if cfg!(feature = "v1_20") {
+        result.unwrap().store("1609430400", &1u64).unwrap();
+        let db = GdbmOpener::new().preread(true).readonly(&path).unwrap();
+        assert_eq!(db.count().unwrap(), 1);
+        drop(db);
+        remove_file(&path).unwrap();
+    } else {
+        match result {
+            Err(Error::Unsupported { option, requires }) => {
+                assert_eq!((option, requires), ("preread", (1, 20)))
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+}
+
+#[test]
+fn failed_options_close_the_file() {
+    let path = db_path("fail");
+    let dir = std::env::temp_dir().join(format!("gnudbm-opener-missing-{}", std::process::id()));
+    let result = GdbmOpener::new()
+        .create(true)
+        .crash_tolerance(dir.join("even"), dir.join("odd"))
+        .readwrite(&path);
+    match result {
+        Err(Error::Unsupported { option, .. }) => assert_eq!(option, "crash_tolerance"),
+        Err(_) => (),
+        Ok(_) => panic!("snapshot files in a missing directory were accepted"),
+    }
+
+    // the failed open did not leave the file locked
+    let db = GdbmOpener::new().create(true).readwrite(&path).unwrap();
+    drop(db);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn unsupported_error_names_the_option() {
+    let err = Error::Unsupported { option: "preread", requires: (1, 20) };
+    assert_eq!(err.to_string(), "`preread` requires gdbm 1.20 or later");
+    assert_eq!(err.gdbm_errno(), None);
+}
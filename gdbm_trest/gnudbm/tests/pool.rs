@@ -0,0 +1,109 @@
+extern crate gnudbm;
+
+use std::fs::remove_file;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+
+use gnudbm::{GdbmOpener, ReadPool};
+
+const KEYS: u64 = 500;
+
+fn db_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("gnudbm-pool-{}-{}.db", name, std::process::id()));
+    let _ = remove_file(&path);
+    path
+}
+
+fn seed(path: &PathBuf) {
+    let mut db = GdbmOpener::new().create(true).readwrite(path).unwrap();
+    for i in 0..KEYS {
+        db.store(format!("{}", 1609430400 + i * 60), &i).unwrap();
+    }
+}
+
+#[test]
+fn pool_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<ReadPool>();
+}
+
+#[test]
+fn threads_share_handles() {
+    let path = db_path("threads");
+    seed(&path);
+    let pool = Arc::new(GdbmOpener::new().read_pool(&path, 3).unwrap());
+    assert_eq!(pool.size(), 3);
+
+    let readers: Vec<_> = (0..8u64)
+        .map(|t| {
+            let pool = pool.clone();
+            thread::spawn(move || {
+                for i in (t..KEYS).step_by(8) {
+                    let db = pool.get();
+                    let value: u64 = db.fetch(format!("{}", 1609430400 + i * 60)).unwrap().deserialize().unwrap();
+                    assert_eq!(value, i);
+                    // errors are reported to the thread that caused them
+                    assert!(db.fetch(format!("missing {}", i)).unwrap_err().is_no_record());
+                }
+            })
+        })
+        .collect();
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    assert_eq!(pool.idle(), 3);
+
+    drop(pool);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn guards_return_handles() {
+    let path = db_path("guards");
+    seed(&path);
+    let pool = GdbmOpener::new().read_pool(&path, 2).unwrap();
+
+    let a = pool.get();
+    let b = pool.try_get().unwrap();
+    assert!(pool.try_get().is_none());
+    assert_eq!(pool.idle(), 0);
+    assert_eq!(a.count().unwrap(), KEYS as usize);
+
+    drop(b);
+    assert_eq!(pool.idle(), 1);
+    let c = pool.try_get().unwrap();
+    assert_eq!(c.count().unwrap(), KEYS as usize);
+    drop(a);
+    drop(c);
+    assert_eq!(pool.idle(), 2);
+
+    drop(pool);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn get_waits_for_a_free_handle() {
+    let path = db_path("wait");
+    seed(&path);
+    let pool = Arc::new(GdbmOpener::new().read_pool(&path, 1).unwrap());
+
+    let held = pool.get();
+    let waiter = {
+        let pool = pool.clone();
+        thread::spawn(move || pool.get().count().unwrap())
+    };
+    thread::sleep(std::time::Duration::from_millis(50));
+    assert!(!waiter.is_finished());
+    drop(held);
+    assert_eq!(waiter.join().unwrap(), KEYS as usize);
+
+    drop(pool);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn open_errors_are_reported() {
+    let path = db_path("missing");
+    assert!(GdbmOpener::new().read_pool(&path, 2).is_err());
+}
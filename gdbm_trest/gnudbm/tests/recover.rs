@@ -0,0 +1,133 @@
+// `check` needs gdbm 1.20
+#![cfg(feature = "v1_20")]
+
+extern crate gnudbm;
+
+use std::fs::{self, remove_file, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use gnudbm::{Error, GdbmOpener, RecoverOptions, RwHandle};
+
+const KEYS: u32 = 2000;
+
+fn db_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("gnudbm-recover-{}-{}.db", name, std::process::id()));
+    let _ = remove_file(&path);
+    path
+}
+
+fn seed(path: &PathBuf) {
+    let mut db = GdbmOpener::new().create(true).readwrite(path).unwrap();
+    for i in 0..KEYS {
+        db.store(format!("{}", 1609430400 + i * 60), &vec![i; 16]).unwrap();
+    }
+}
+
+/// Overwrites part of the bucket area, as a torn write might.
+fn damage(path: &PathBuf) {
+    let len = fs::metadata(path).unwrap().len();
+    let mut file = OpenOptions::new().write(true).open(path).unwrap();
+    file.seek(SeekFrom::Start(len * 7 / 10)).unwrap();
+    file.write_all(&[0xff; 256]).unwrap();
+}
+
+fn open(path: &PathBuf) -> RwHandle {
+    GdbmOpener::new().key_index(true).readwrite(path).unwrap()
+}
+
+#[test]
+fn healthy_database_is_consistent() {
+    let path = db_path("healthy");
+    seed(&path);
+    let mut db = open(&path);
+
+    let report = db.check().unwrap();
+    assert!(report.is_consistent(), "{:?}", report);
+    assert!(report.buckets > 0);
+    assert_eq!(report.keys, KEYS as usize);
+    assert_eq!(report.expected_keys, KEYS as usize);
+    assert!(report.count_error.is_none());
+
+    // without `force`, there is nothing to do
+    let recovered = db.recover(&RecoverOptions::new()).unwrap();
+    assert_eq!(recovered.recovered_keys, 0);
+    assert_eq!(recovered.backup, None);
+    assert_eq!(db.count().unwrap(), KEYS as usize);
+
+    drop(db);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn check_finds_damage() {
+    let path = db_path("check");
+    seed(&path);
+    damage(&path);
+    let db = GdbmOpener::new().readwrite(&path).unwrap();
+
+    let report = db.check().unwrap();
+    assert!(!report.is_consistent());
+    assert!(report.needs_recovery);
+    assert!(report.walk_error.as_ref().unwrap().is_corruption());
+    assert!(report.keys < KEYS as usize);
+    assert!(db.count().unwrap_err().is_corruption());
+
+    // once flagged, there is nothing more to read
+    let report = db.check().unwrap();
+    assert!(report.needs_recovery);
+    assert_eq!(report.keys, 0);
+
+    drop(db);
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn damaged_database_is_recovered() {
+    let path = db_path("damaged");
+    seed(&path);
+    damage(&path);
+    let damaged = fs::read(&path).unwrap();
+    // building the key index walks into the damage
+    let mut db = open(&path);
+    assert!(!db.check().unwrap().is_consistent());
+
+    let recovered = db.recover(RecoverOptions::new().backup(true)).unwrap();
+    // keys whose bytes were overwritten can be lost without being noticed
+    assert!(recovered.recovered_keys > KEYS as usize / 2);
+    assert!(recovered.recovered_keys + recovered.failed_keys <= KEYS as usize);
+
+    // the damaged file is kept as it was
+    let backup = recovered.backup.expect("no backup");
+    assert_eq!(fs::read(&backup).unwrap(), damaged);
+
+    let report = db.check().unwrap();
+    assert!(report.is_consistent(), "{:?}", report);
+    assert_eq!(report.keys, recovered.recovered_keys);
+    assert_eq!(db.range::<str, _>(..).unwrap().count(), recovered.recovered_keys);
+    assert_eq!(db.fetch("1609430400").unwrap().deserialize::<Vec<u32>>().unwrap(), vec![0; 16]);
+
+    // and the handle can be written to again
+    db.store("max_epoch", &1609550340u32).unwrap();
+
+    drop(db);
+    remove_file(&backup).unwrap();
+    remove_file(&path).unwrap();
+}
+
+#[test]
+fn recovery_stops_at_failed_key_threshold() {
+    let path = db_path("threshold");
+    seed(&path);
+    damage(&path);
+    let damaged = fs::read(&path).unwrap();
+    let mut db = open(&path);
+
+    let err = db.recover(RecoverOptions::new().max_failed_keys(1)).unwrap_err();
+    assert!(matches!(err, Error::RecoveryLimit), "{:?}", err);
+    assert!(err.is_corruption());
+    drop(db);
+    assert_eq!(fs::read(&path).unwrap(), damaged);
+
+    remove_file(&path).unwrap();
+}
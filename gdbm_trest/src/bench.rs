@@ -0,0 +1,330 @@
+//! 各个后端在不同并发方式下的读取测试. 每次读取 (fetch + 反序列化)
+//! 记一个样本. sqlite 和 CSV 通过 [`KlineStore::get_range`] 读一天的 kline.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::io::FromRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use gdbm::{Gdbm, Open};
+use gdbm_my::GdbmOpener as GdbmOpenerMy;
+use gnudbm::{GdbmOpener, Json, ReadPool};
+use nix::{
+    libc,
+    sys::wait::waitpid,
+    unistd::{fork, pipe, ForkResult},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Serialize;
+
+use crate::fixture::{FixtureSpec, Kline, INTERVAL};
+use crate::store::{CsvStore, KlineStore, SqliteStore};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    /// gdbm crate
+    Gdbm,
+    /// gnudbm crate
+    Gnudbm,
+    /// gnudbm, 每次读取都重新打开数据库
+    GnudbmReopen,
+    /// gnudbm, 读同一个数据库的 worker 共用一个 ReadPool, 每次读取借一个句柄
+    GnudbmPool,
+    /// 本地的 gdbm_my opener
+    GdbmMy,
+    /// sqlite, 所有 symbol 在同一个文件里
+    Sqlite,
+    /// CSV, 每次读取都读整个文件
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Mode {
+    /// 串行
+    Serial,
+    /// 多线程
+    Threads,
+    /// 多进程
+    Processes,
+    /// tokio 异步
+    Async,
+}
+
+/// 一轮测试的参数
+#[derive(Debug, Clone, Serialize)]
+pub struct Plan {
+    pub workers: usize,
+    pub reads: usize,
+    pub seed: u64,
+}
+
+/// 一轮测试的结果: 每次读取的耗时, 以及整轮的墙钟时间
+pub struct Run {
+    pub samples: Vec<Duration>,
+    pub wall: Duration,
+}
+
+type ReadFn = Box<dyn FnMut(&str) -> usize>;
+
+const DAY: u64 = 86400;
+
+/// 打开第 `db` 个数据库, 返回读一个 key 的函数 (返回读到的 kline 条数).
+/// `pool` 是 `GnudbmPool` 时这个数据库共用的 ReadPool
+fn open_reader(
+    backend: Backend,
+    spec: &FixtureSpec,
+    db: usize,
+    pool: Option<Arc<ReadPool>>,
+) -> ReadFn {
+    let path = spec.db_path(db);
+    match backend {
+        Backend::Gdbm => {
+            let db = Gdbm::new(&path, 0, Open::READER, 0).expect("db open failed");
+            Box::new(move |key| {
+                db.fetch_with(key, |bytes| {
+                    serde_json::from_slice::<Vec<Kline>>(bytes).unwrap().len()
+                })
+                .unwrap()
+            })
+        }
+        Backend::Gnudbm => {
+            let db = GdbmOpener::new()
+                .preread(true)
+                .readonly(&path)
+                .expect("db open failed");
+            Box::new(move |key| {
+                let klines: Vec<Kline> = db.fetch(key).unwrap().decode(&Json).unwrap();
+                klines.len()
+            })
+        }
+        Backend::GnudbmReopen => {
+            // 不 preread, 否则每次打开都要读整个文件
+            Box::new(move |key| {
+                let db = GdbmOpener::new().readonly(&path).expect("db open failed");
+                let klines: Vec<Kline> = db.fetch(key).unwrap().decode(&Json).unwrap();
+                klines.len()
+            })
+        }
+        Backend::GnudbmPool => {
+            // 多进程时没有共用的 pool, 每个子进程自己开一个
+            let pool = pool.unwrap_or_else(|| Arc::new(open_pool(&path, 1)));
+            Box::new(move |key| {
+                let klines: Vec<Kline> = pool.get().fetch(key).unwrap().decode(&Json).unwrap();
+                klines.len()
+            })
+        }
+        Backend::GdbmMy => {
+            let db = GdbmOpenerMy::new()
+                .pre_read(true)
+                .readonly(&path)
+                .expect("db open failed");
+            Box::new(move |key| {
+                let entry = db.fetch(key).unwrap();
+                serde_json::from_slice::<Vec<Kline>>(entry.as_bytes())
+                    .unwrap()
+                    .len()
+            })
+        }
+        Backend::Sqlite => {
+            let store = SqliteStore::open(spec.sqlite_path()).expect("db open failed");
+            store_reader(store, spec.symbol(db))
+        }
+        Backend::Csv => store_reader(CsvStore::new(spec.csv_dir()), spec.symbol(db)),
+    }
+}
+
+/// key 是一天零点的 epoch, 读这一天的 kline
+fn store_reader(store: impl KlineStore + 'static, symbol: String) -> ReadFn {
+    Box::new(move |key| {
+        let day: u64 = key.parse().unwrap();
+        store
+            .get_range(&symbol, INTERVAL, day, day + DAY)
+            .unwrap()
+            .len()
+    })
+}
+
+fn open_pool(path: &Path, size: usize) -> ReadPool {
+    GdbmOpener::new()
+        .preread(true)
+        .read_pool(path, size)
+        .expect("db open failed")
+}
+
+/// `GnudbmPool` 时每个数据库一个 ReadPool, 大小是读它的 worker 数;
+/// 其他后端为空. fork 出的子进程不能共用句柄, 多进程时也为空
+fn open_pools(backend: Backend, mode: Mode, spec: &FixtureSpec, plan: &Plan) -> Vec<Arc<ReadPool>> {
+    if backend != Backend::GnudbmPool || mode == Mode::Processes {
+        return vec![];
+    }
+    (0..spec.dbs.min(plan.workers))
+        .map(|i| {
+            Arc::new(open_pool(
+                &spec.db_path(i),
+                (plan.workers - i).div_ceil(spec.dbs),
+            ))
+        })
+        .collect()
+}
+
+/// 第 `worker` 个 worker 要读的数据库序号和 key 序列
+fn worker_keys(spec: &FixtureSpec, plan: &Plan, worker: usize) -> (usize, Vec<String>) {
+    let mut rng = StdRng::seed_from_u64(plan.seed ^ ((worker as u64) << 32));
+    let keys = (0..plan.reads)
+        .map(|_| spec.key(rng.gen_range(0..spec.keys)))
+        .collect();
+    (worker % spec.dbs, keys)
+}
+
+fn read_all(read: &mut ReadFn, keys: &[String]) -> Vec<Duration> {
+    keys.iter()
+        .map(|key| {
+            let start = Instant::now();
+            let n = read(key);
+            let elapsed = start.elapsed();
+            assert!(n > 0, "key {} is empty", key);
+            elapsed
+        })
+        .collect()
+}
+
+fn run_worker(
+    backend: Backend,
+    spec: &FixtureSpec,
+    plan: &Plan,
+    pools: &[Arc<ReadPool>],
+    worker: usize,
+) -> Vec<Duration> {
+    let (db, keys) = worker_keys(spec, plan, worker);
+    let mut read = open_reader(backend, spec, db, pools.get(db).cloned());
+    read_all(&mut read, &keys)
+}
+
+pub fn run(backend: Backend, mode: Mode, spec: &FixtureSpec, plan: &Plan) -> Run {
+    let start = Instant::now();
+    let pools = open_pools(backend, mode, spec, plan);
+    let samples = match mode {
+        Mode::Serial => (0..plan.workers)
+            .flat_map(|w| run_worker(backend, spec, plan, &pools, w))
+            .collect(),
+        Mode::Threads => run_threads(backend, spec, plan, &pools),
+        Mode::Processes => run_processes(backend, spec, plan),
+        Mode::Async => run_async(backend, spec, plan, &pools),
+    };
+    Run {
+        samples,
+        wall: start.elapsed(),
+    }
+}
+
+fn run_threads(
+    backend: Backend,
+    spec: &FixtureSpec,
+    plan: &Plan,
+    pools: &[Arc<ReadPool>],
+) -> Vec<Duration> {
+    std::thread::scope(|s| {
+        let ts: Vec<_> = (0..plan.workers)
+            .map(|w| s.spawn(move || run_worker(backend, spec, plan, pools, w)))
+            .collect();
+        ts.into_iter().flat_map(|t| t.join().unwrap()).collect()
+    })
+}
+
+/// 每个 worker 一个子进程, 样本 (纳秒, u64 小端) 通过管道传回父进程
+fn run_processes(backend: Backend, spec: &FixtureSpec, plan: &Plan) -> Vec<Duration> {
+    let mut children = vec![];
+    for w in 0..plan.workers {
+        let (rx, tx) = pipe().expect("pipe failed");
+        match unsafe { fork().expect("fork failed") } {
+            ForkResult::Parent { child } => {
+                let _ = nix::unistd::close(tx);
+                children.push((child, unsafe { File::from_raw_fd(rx) }));
+            }
+            ForkResult::Child => {
+                let _ = nix::unistd::close(rx);
+                let mut out = unsafe { File::from_raw_fd(tx) };
+                let bytes: Vec<u8> = run_worker(backend, spec, plan, &[], w)
+                    .iter()
+                    .flat_map(|d| (d.as_nanos() as u64).to_le_bytes())
+                    .collect();
+                let code = if out.write_all(&bytes).is_ok() { 0 } else { 1 };
+                unsafe { libc::_exit(code) };
+            }
+        }
+    }
+
+    let mut samples = vec![];
+    for (pid, mut rx) in children {
+        let mut bytes = vec![];
+        rx.read_to_end(&mut bytes).expect("read samples failed");
+        waitpid(pid, None).expect("waitpid failed");
+        assert_eq!(bytes.len(), plan.reads * 8, "worker {} failed", pid);
+        samples.extend(
+            bytes
+                .chunks_exact(8)
+                .map(|b| Duration::from_nanos(u64::from_le_bytes(b.try_into().unwrap()))),
+        );
+    }
+    samples
+}
+
+/// gnudbm 用它自己的异步句柄; 其他后端没有异步接口, 在 tokio 的 worker
+/// 线程上用 `block_in_place` 读, 这也是在异步代码里调用它们的常见方式.
+fn run_async(
+    backend: Backend,
+    spec: &FixtureSpec,
+    plan: &Plan,
+    pools: &[Arc<ReadPool>],
+) -> Vec<Duration> {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("runtime build failed");
+    rt.block_on(async {
+        let ts: Vec<_> = (0..plan.workers)
+            .map(|w| {
+                let (db, keys) = worker_keys(spec, plan, w);
+                let pool = pools.get(db).cloned();
+                let spec = spec.clone();
+                tokio::spawn(async move {
+                    if backend == Backend::Gnudbm {
+                        read_gnudbm_async(spec.db_path(db), keys).await
+                    } else {
+                        tokio::task::block_in_place(|| {
+                            let mut read = open_reader(backend, &spec, db, pool);
+                            read_all(&mut read, &keys)
+                        })
+                    }
+                })
+            })
+            .collect();
+        let mut samples = vec![];
+        for t in ts {
+            samples.extend(t.await.unwrap());
+        }
+        samples
+    })
+}
+
+async fn read_gnudbm_async(path: PathBuf, keys: Vec<String>) -> Vec<Duration> {
+    let db = GdbmOpener::new()
+        .preread(true)
+        .readonly_async(&path)
+        .await
+        .expect("db open failed");
+    let mut samples = Vec::with_capacity(keys.len());
+    for key in keys {
+        let start = Instant::now();
+        let entry = db.fetch(key).await.unwrap();
+        let klines: Vec<Kline> = entry.decode(&Json).unwrap();
+        samples.push(start.elapsed());
+        assert!(!klines.is_empty());
+    }
+    samples
+}
@@ -0,0 +1,154 @@
+//! 生成测试用的 kline 数据库, 格式和线上的 `*usdt_1min` 文件一致:
+//! key 是每天零点的 epoch 字符串, value 是当天 kline 的 JSON 数组,
+//! 另外还有一个 `max_epoch` key. 同样的 kline 也写进 sqlite 和 CSV,
+//! 三种格式都通过 [`KlineStore`] 写入.
+
+use std::path::PathBuf;
+
+use clap::{builder::RangedU64ValueParser, Args};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::store::{CsvStore, GdbmStore, KlineStore, SqliteStore};
+
+pub const FIRST_EPOCH: u64 = 1609430400;
+pub const INTERVAL: &str = "1min";
+const DAY: u64 = 86400;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Kline {
+    pub id: u64,
+    pub open: f64,
+    pub close: f64,
+    pub high: f64,
+    pub low: f64,
+    pub count: f64,
+    pub amount: f64,
+    pub vol: f64,
+}
+
+#[derive(Debug, Clone, Args, Serialize)]
+pub struct FixtureSpec {
+    /// 生成的数据库个数, 并发时每个 worker 读其中一个
+    #[arg(long, default_value_t = 6)]
+    pub dbs: usize,
+    /// 每个数据库的 key (天) 数
+    #[arg(long, default_value_t = 30)]
+    pub keys: usize,
+    /// 每个 key 下的 kline 条数, 1 分钟线一天最多 1440 条
+    #[arg(long, default_value_t = 1440, value_parser = RangedU64ValueParser::<usize>::new().range(1..=1440))]
+    pub klines: usize,
+    /// 随机种子, 相同的参数和种子生成相同的文件
+    #[arg(long, default_value_t = 42)]
+    pub seed: u64,
+    /// 数据库存放的目录
+    #[arg(long, default_value = "bench_data")]
+    pub dir: PathBuf,
+}
+
+impl FixtureSpec {
+    pub fn symbol(&self, i: usize) -> String {
+        format!("synth{}usdt", i)
+    }
+
+    pub fn db_path(&self, i: usize) -> PathBuf {
+        self.dir.join(format!("{}_{}", self.symbol(i), INTERVAL))
+    }
+
+    /// 所有 symbol 共用的 sqlite 文件
+    pub fn sqlite_path(&self) -> PathBuf {
+        self.dir.join("klines.sqlite")
+    }
+
+    pub fn csv_dir(&self) -> PathBuf {
+        self.dir.join("csv")
+    }
+
+    pub fn key(&self, i: usize) -> String {
+        (FIRST_EPOCH + i as u64 * DAY).to_string()
+    }
+
+    /// gdbm, sqlite 和 CSV 三个 store
+    fn stores(&self) -> Vec<Box<dyn KlineStore>> {
+        std::fs::create_dir_all(&self.dir).expect("create fixture dir failed");
+        let sqlite = SqliteStore::open(self.sqlite_path()).expect("sqlite open failed");
+        vec![
+            Box::new(GdbmStore::new(&self.dir)),
+            Box::new(sqlite),
+            Box::new(CsvStore::new(self.csv_dir())),
+        ]
+    }
+
+    /// 生成全部数据库, 已有的同名数据会被覆盖
+    pub fn generate(&self) -> Vec<PathBuf> {
+        // gdbm 删掉的空间不会还给文件系统, 直接删文件保证每次生成的文件一样
+        for i in 0..self.dbs {
+            let path = self.db_path(i);
+            if path.exists() {
+                std::fs::remove_file(&path).expect("remove old fixture failed");
+            }
+        }
+        let mut stores = self.stores();
+        for i in 0..self.dbs {
+            let mut rng = StdRng::seed_from_u64(self.seed.wrapping_add(i as u64));
+            let klines = self.klines(&mut rng);
+            for store in &mut stores {
+                store
+                    .delete_before(&self.symbol(i), INTERVAL, u64::MAX)
+                    .expect("delete old klines failed");
+                store
+                    .put_range(&self.symbol(i), INTERVAL, &klines)
+                    .expect("store klines failed");
+            }
+        }
+
+        let mut paths: Vec<PathBuf> = (0..self.dbs).map(|i| self.db_path(i)).collect();
+        paths.push(self.sqlite_path());
+        paths.push(self.csv_dir());
+        paths
+    }
+
+    /// `--reuse` 时确认三种格式的数据都已经按这些参数生成过
+    pub fn check(&self) {
+        let last = FIRST_EPOCH + (self.keys - 1) as u64 * DAY + (self.klines - 1) as u64 * 60;
+        for store in self.stores() {
+            for i in 0..self.dbs {
+                let max = store
+                    .max_epoch(&self.symbol(i), INTERVAL)
+                    .expect("read fixture failed");
+                assert_eq!(
+                    max,
+                    Some(last),
+                    "fixture {} is missing or stale",
+                    self.symbol(i)
+                );
+            }
+        }
+    }
+
+    /// 连续 `keys` 天, 每天从零点开始 `klines` 条 1 分钟线
+    fn klines(&self, rng: &mut StdRng) -> Vec<Kline> {
+        let mut price: f64 = rng.gen_range(0.1..50000.0);
+        let mut klines = Vec::with_capacity(self.keys * self.klines);
+        for k in 0..self.keys {
+            let day = FIRST_EPOCH + k as u64 * DAY;
+            klines.extend((0..self.klines).map(|m| {
+                let open = price;
+                price *= 1.0 + rng.gen_range(-0.002..0.002);
+                let (high, low) = (open.max(price), open.min(price));
+                let vol = rng.gen_range(0.0..1000.0);
+                Kline {
+                    id: day + m as u64 * 60,
+                    open,
+                    close: price,
+                    high: high * (1.0 + rng.gen_range(0.0..0.001)),
+                    low: low * (1.0 - rng.gen_range(0.0..0.001)),
+                    count: rng.gen_range(0..500) as f64,
+                    amount: vol * price,
+                    vol,
+                }
+            }));
+        }
+        klines
+    }
+}
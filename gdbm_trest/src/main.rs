@@ -1,168 +1,105 @@
-#![allow(dead_code)]
-#![allow(unused_imports)]
+//! gdbm 读取性能测试.
+//!
+//! ```text
+//! gdbm_trest generate --dbs 6 --keys 30 --klines 1440
+//! gdbm_trest run --workers 6 --reads 200 --format csv --output bench.csv
+//! gdbm_trest run --reuse --backends gnudbm,sqlite,csv --modes serial,threads
+//! ```
 
-use gdbm::{Gdbm, Open};
-use gdbm_my::GdbmOpener as GdbmOpenerMy;
-use gnudbm::GdbmOpener;
-use nix::{libc, sys::wait::waitpid, unistd::Pid};
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use std::{env::args, path::PathBuf, time::Instant};
+mod bench;
+mod fixture;
+mod report;
+mod store;
 
-#[derive(Debug, Deserialize, Serialize)]
-struct Keys(Vec<u32>);
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
 
-#[derive(Debug, Deserialize, Serialize)]
-struct Kline {
-    id: u64,
-    open: f64,
-    close: f64,
-    high: f64,
-    low: f64,
-    count: f64,
-    amount: f64,
-    vol: f64,
-}
-
-#[tokio::main]
-async fn main() {
-    // let sym = "ankrusdt_1min";
-    // let key = "1609430400";
-    // read_gdbm::<Vec<Kline>>(sym, key);
-    // read_gnudbm::<Vec<Kline>>(sym, key);
-    // read_gdbm_my::<Vec<Kline>>(sym, key);
-    // let key = "max_epoch";
-    // read_gdbm::<u64>(sym, key);
-    // read_gnudbm::<u64>(sym, key);
-    // read_gdbm_my::<u64>(sym, key);
+use clap::{builder::RangedU64ValueParser, Args, Parser, Subcommand};
 
-    let syms = vec![
-        "ankrusdt_1min",
-        "btcusdt_1min",
-        "aaveusdt_1min",
-        "ankrusdt_1min",
-        "btcusdt_1min",
-        "aaveusdt_1min",
-    ];
-    chuan_test(syms.clone());
-    multi_process(syms.clone());
-    multi_thread(syms.clone());
-    async_test(syms.clone()).await;
-    // let x = args().skip(1).take(1).collect::<String>();
-    // let f = format!("{}usdt_1min", x);
-    // read_gdbm_my::<Vec<Kline>>(&f, "1609430400");
-}
+use bench::{Backend, Mode, Plan};
+use fixture::FixtureSpec;
+use report::{Format, Summary};
 
-async fn async_test(syms: Vec<&str>) {
-    println!("--------异步多线程----------");
-    let mut ts = vec![];
-    for sym in syms {
-        let sym = sym.to_string();
-        let t = tokio::spawn(async move {
-            read_gdbm_my::<Vec<Kline>>(&sym, "1609430400");
-        });
-        ts.push(t);
-    }
-    for t in ts {
-        t.await.unwrap();
-    }
+#[derive(Parser)]
+#[command(about = "gdbm 读取性能测试")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
 }
 
-fn chuan_test(syms: Vec<&str>) {
-    println!("-------串行-----------");
-    for sym in syms {
-        read_gdbm_my::<Vec<Kline>>(sym, "1609430400");
-    }
+#[derive(Subcommand)]
+enum Command {
+    /// 只生成测试数据库
+    Generate(FixtureSpec),
+    /// 生成测试数据库并运行全部测试
+    Run(RunArgs),
 }
 
-fn multi_thread(syms: Vec<&str>) {
-    println!("--------多线程----------");
-    let mut ts = vec![];
-    for sym in syms {
-        let sym = sym.to_string();
-        let t = std::thread::spawn(move || {
-            read_gdbm_my::<Vec<Kline>>(&sym, "1609430400");
-        });
-        ts.push(t);
-    }
-    for t in ts {
-        t.join().unwrap();
-    }
+#[derive(Args)]
+struct RunArgs {
+    #[command(flatten)]
+    fixture: FixtureSpec,
+    /// 不重新生成, 直接用 --dir 里已有的数据库
+    #[arg(long)]
+    reuse: bool,
+    /// 并发的 worker 数 (串行模式下依次运行)
+    #[arg(long, default_value_t = 6, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    workers: usize,
+    /// 每个 worker 的读取次数
+    #[arg(long, default_value_t = 200, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    reads: usize,
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = [Backend::Gdbm, Backend::Gnudbm, Backend::GnudbmReopen, Backend::GnudbmPool, Backend::GdbmMy])]
+    backends: Vec<Backend>,
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = [Mode::Serial, Mode::Threads, Mode::Processes, Mode::Async])]
+    modes: Vec<Mode>,
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+    /// 结果写到文件, 默认输出到 stdout
+    #[arg(long)]
+    output: Option<PathBuf>,
 }
 
-fn multi_process(syms: Vec<&str>) {
-    println!("--------多进程----------");
-    let mut children = vec![];
-    for f in syms {
-        let pid = match unsafe { nix::unistd::fork().unwrap() } {
-            nix::unistd::ForkResult::Parent { child } => child,
-            nix::unistd::ForkResult::Child => {
-                read_gdbm_my::<Vec<Kline>>(f, "1609430400");
-                unsafe { libc::_exit(0) };
+fn main() {
+    match Cli::parse().command {
+        Command::Generate(spec) => {
+            for path in spec.generate() {
+                eprintln!("generated {}", path.display());
             }
-        };
-        children.push(pid);
+        }
+        Command::Run(args) => run(args),
     }
-    for pid in children {
-        waitpid(pid, None).unwrap();
-    }
-}
-
-fn read_gdbm<T>(sym: &str, key: &str)
-where
-    T: DeserializeOwned,
-{
-    let path = PathBuf::from(sym);
-    let db = Gdbm::new(&path, 0, Open::READER, 0o666).unwrap();
-    let start = Instant::now();
-    let content = db.fetch(key).unwrap();
-    let read_time = start.elapsed().as_micros();
-    let _res = serde_json::from_str::<T>(&content).unwrap();
-    let de_time = start.elapsed().as_micros() - read_time;
-    println!(
-        "GDBM: key: {}, read_time: {}, de_time: {}, ",
-        key, read_time, de_time,
-    );
 }
 
-fn read_gnudbm<T>(sym: &str, key: &str)
-where
-    T: DeserializeOwned,
-{
-    let path = PathBuf::from(sym);
-    let db = GdbmOpener::new()
-        .create(true)
-        .readonly(&path)
-        .expect("db creation failed");
-    let start = Instant::now();
-    let entry = db.fetch(key).unwrap();
-    let read_time = start.elapsed().as_micros();
-    let _res = serde_json::from_slice::<T>(entry.as_bytes()).unwrap();
-    let de_time = start.elapsed().as_micros() - read_time;
-    println!(
-        "GnuDBM: key: {}, read_time: {}, de_time: {}",
-        key, read_time, de_time
+fn run(args: RunArgs) {
+    let spec = args.fixture;
+    assert!(
+        spec.dbs > 0 && spec.keys > 0 && spec.klines > 0,
+        "fixture is empty"
     );
-}
+    if args.reuse {
+        spec.check();
+    } else {
+        spec.generate();
+    }
+    let plan = Plan {
+        workers: args.workers,
+        reads: args.reads,
+        seed: spec.seed,
+    };
 
-fn read_gdbm_my<T>(sym: &str, key: &str)
-where
-    T: DeserializeOwned,
-{
-    let path = PathBuf::from(sym);
-    let db = GdbmOpenerMy::new()
-        .create(true)
-        .pre_read(true)
-        .readonly(&path)
-        .expect("db creation failed");
+    let mut results = vec![];
+    for &backend in &args.backends {
+        for &mode in &args.modes {
+            eprintln!("{:?} / {:?} ...", backend, mode);
+            let run = bench::run(backend, mode, &spec, &plan);
+            results.push(Summary::new(backend, mode, run));
+        }
+    }
 
-    let start = Instant::now();
-    let entry = db.fetch(key).unwrap();
-    let read_time = start.elapsed().as_micros();
-    let str = String::from_utf8(entry.as_bytes().to_vec()).unwrap();
-    let _res = serde_json::from_str::<T>(&str).unwrap();
-    let de_time = start.elapsed().as_micros() - read_time;
-    println!(
-        "GDBM_My: key: {}, read_time: {}, de_time: {}",
-        key, read_time, de_time
-    );
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(File::create(path).expect("create output failed")),
+        None => Box::new(io::stdout()),
+    };
+    report::write(&mut out, args.format, &spec, &plan, &results).expect("write report failed");
 }
@@ -0,0 +1,127 @@
+//! 把样本汇总成 p50/p99/吞吐量, 输出 JSON 或 CSV.
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::bench::{Backend, Mode, Plan, Run};
+use crate::fixture::FixtureSpec;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub backend: Backend,
+    pub mode: Mode,
+    pub reads: usize,
+    pub mean_us: f64,
+    pub p50_us: f64,
+    pub p99_us: f64,
+    pub max_us: f64,
+    pub wall_ms: f64,
+    /// 每秒读取次数, 按整轮的墙钟时间算
+    pub throughput: f64,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    fixture: &'a FixtureSpec,
+    plan: &'a Plan,
+    results: &'a [Summary],
+}
+
+/// 和命令行参数里一样的名字
+fn name<T: ValueEnum>(value: &T) -> String {
+    value.to_possible_value().unwrap().get_name().to_string()
+}
+
+fn micros(d: Duration) -> f64 {
+    d.as_nanos() as f64 / 1000.0
+}
+
+/// 最近秩法求百分位, `sorted` 必须已排序且非空
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+impl Summary {
+    pub fn new(backend: Backend, mode: Mode, run: Run) -> Summary {
+        let mut samples = run.samples;
+        samples.sort();
+        let total: Duration = samples.iter().sum();
+        let reads = samples.len();
+        Summary {
+            backend,
+            mode,
+            reads,
+            mean_us: micros(total) / reads as f64,
+            p50_us: micros(percentile(&samples, 50.0)),
+            p99_us: micros(percentile(&samples, 99.0)),
+            max_us: micros(samples[reads - 1]),
+            wall_ms: micros(run.wall) / 1000.0,
+            throughput: reads as f64 / run.wall.as_secs_f64(),
+        }
+    }
+}
+
+pub fn write(
+    out: &mut dyn Write,
+    format: Format,
+    fixture: &FixtureSpec,
+    plan: &Plan,
+    results: &[Summary],
+) -> io::Result<()> {
+    match format {
+        Format::Json => {
+            let report = Report {
+                fixture,
+                plan,
+                results,
+            };
+            serde_json::to_writer_pretty(&mut *out, &report)?;
+            writeln!(out)
+        }
+        Format::Csv => {
+            writeln!(
+                out,
+                "backend,mode,reads,mean_us,p50_us,p99_us,max_us,wall_ms,throughput"
+            )?;
+            for s in results {
+                writeln!(
+                    out,
+                    "{},{},{},{:.1},{:.1},{:.1},{:.1},{:.1},{:.1}",
+                    name(&s.backend),
+                    name(&s.mode),
+                    s.reads,
+                    s.mean_us,
+                    s.p50_us,
+                    s.p99_us,
+                    s.max_us,
+                    s.wall_ms,
+                    s.throughput
+                )?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn percentiles() {
+        let samples: Vec<_> = (1..=100).map(Duration::from_micros).collect();
+        assert_eq!(percentile(&samples, 50.0), Duration::from_micros(50));
+        assert_eq!(percentile(&samples, 99.0), Duration::from_micros(99));
+        assert_eq!(percentile(&samples[..1], 99.0), Duration::from_micros(1));
+    }
+}
@@ -0,0 +1,182 @@
+//! 每个后端都要通过的测试. 各个用例用不同的 symbol, 共用同一个 store.
+
+use super::KlineStore;
+use crate::fixture::{Kline, FIRST_EPOCH};
+
+const DAY: u64 = 86400;
+
+/// 从 `start` 开始每分钟一条, 数值取简单的小数, 各后端都能原样读回
+fn klines(start: u64, n: usize) -> Vec<Kline> {
+    (0..n)
+        .map(|i| {
+            let x = i as f64;
+            Kline {
+                id: start + i as u64 * 60,
+                open: 100.0 + x,
+                close: 100.5 + x,
+                high: 101.0 + x,
+                low: 99.75 + x,
+                count: x,
+                amount: 1000.25 * x,
+                vol: 10.0 * x,
+            }
+        })
+        .collect()
+}
+
+fn ids(klines: &[Kline]) -> Vec<u64> {
+    klines.iter().map(|k| k.id).collect()
+}
+
+pub fn check<S: KlineStore>(mut store: S) {
+    empty(&mut store);
+    round_trip(&mut store);
+    range_bounds(&mut store);
+    across_days(&mut store);
+    replace_existing(&mut store);
+    max_epoch(&mut store);
+    delete_before(&mut store);
+    isolated(&mut store);
+}
+
+fn empty<S: KlineStore>(store: &mut S) {
+    assert_eq!(store.max_epoch("empty", "1min").unwrap(), None);
+    assert!(store
+        .get_range("empty", "1min", 0, u64::MAX)
+        .unwrap()
+        .is_empty());
+    assert_eq!(store.delete_before("empty", "1min", u64::MAX).unwrap(), 0);
+    store.put_range("empty", "1min", &[]).unwrap();
+    assert_eq!(store.max_epoch("empty", "1min").unwrap(), None);
+}
+
+fn round_trip<S: KlineStore>(store: &mut S) {
+    let data = klines(FIRST_EPOCH, 10);
+    store.put_range("roundtrip", "1min", &data).unwrap();
+    assert_eq!(
+        store.get_range("roundtrip", "1min", 0, u64::MAX).unwrap(),
+        data
+    );
+}
+
+fn range_bounds<S: KlineStore>(store: &mut S) {
+    let data = klines(FIRST_EPOCH, 10);
+    store.put_range("bounds", "1min", &data).unwrap();
+    // from 包含, to 不包含
+    let got = store
+        .get_range("bounds", "1min", data[2].id, data[5].id)
+        .unwrap();
+    assert_eq!(got, data[2..5]);
+    // 不在整分钟上的边界
+    let got = store
+        .get_range("bounds", "1min", data[2].id + 1, data[5].id + 1)
+        .unwrap();
+    assert_eq!(got, data[3..6]);
+    assert!(store
+        .get_range("bounds", "1min", data[5].id, data[5].id)
+        .unwrap()
+        .is_empty());
+    assert!(store
+        .get_range("bounds", "1min", data[9].id + 1, u64::MAX)
+        .unwrap()
+        .is_empty());
+}
+
+fn across_days<S: KlineStore>(store: &mut S) {
+    // 前一天的最后 5 分钟到后一天的前 5 分钟, 乱序写入
+    let data = klines(FIRST_EPOCH + DAY - 5 * 60, 10);
+    let mut shuffled = data.clone();
+    shuffled.reverse();
+    store.put_range("days", "1min", &shuffled).unwrap();
+    assert_eq!(store.get_range("days", "1min", 0, u64::MAX).unwrap(), data);
+    let got = store
+        .get_range("days", "1min", FIRST_EPOCH + DAY, u64::MAX)
+        .unwrap();
+    assert_eq!(got, data[5..]);
+}
+
+fn replace_existing<S: KlineStore>(store: &mut S) {
+    store
+        .put_range("replace", "1min", &klines(FIRST_EPOCH, 10))
+        .unwrap();
+    let mut update = klines(FIRST_EPOCH + 5 * 60, 10);
+    for k in &mut update {
+        k.close = 1.5;
+    }
+    store.put_range("replace", "1min", &update).unwrap();
+
+    let got = store.get_range("replace", "1min", 0, u64::MAX).unwrap();
+    assert_eq!(got.len(), 15);
+    assert_eq!(got[..5], klines(FIRST_EPOCH, 5)[..]);
+    assert_eq!(got[5..], update[..]);
+}
+
+fn max_epoch<S: KlineStore>(store: &mut S) {
+    let data = klines(FIRST_EPOCH + DAY, 10);
+    store.put_range("max", "1min", &data).unwrap();
+    assert_eq!(store.max_epoch("max", "1min").unwrap(), Some(data[9].id));
+    // 补写更早的数据不影响 max_epoch
+    store
+        .put_range("max", "1min", &klines(FIRST_EPOCH, 10))
+        .unwrap();
+    assert_eq!(store.max_epoch("max", "1min").unwrap(), Some(data[9].id));
+    let later = klines(data[9].id + 60, 1);
+    store.put_range("max", "1min", &later).unwrap();
+    assert_eq!(store.max_epoch("max", "1min").unwrap(), Some(later[0].id));
+}
+
+fn delete_before<S: KlineStore>(store: &mut S) {
+    let data = klines(FIRST_EPOCH + DAY - 5 * 60, 10);
+    store.put_range("delete", "1min", &data).unwrap();
+
+    assert_eq!(
+        store.delete_before("delete", "1min", data[0].id).unwrap(),
+        0
+    );
+    // 跨过一天的边界
+    assert_eq!(
+        store.delete_before("delete", "1min", data[7].id).unwrap(),
+        7
+    );
+    assert_eq!(
+        ids(&store.get_range("delete", "1min", 0, u64::MAX).unwrap()),
+        ids(&data[7..])
+    );
+    assert_eq!(store.max_epoch("delete", "1min").unwrap(), Some(data[9].id));
+
+    assert_eq!(store.delete_before("delete", "1min", u64::MAX).unwrap(), 3);
+    assert!(store
+        .get_range("delete", "1min", 0, u64::MAX)
+        .unwrap()
+        .is_empty());
+    assert_eq!(store.max_epoch("delete", "1min").unwrap(), None);
+}
+
+fn isolated<S: KlineStore>(store: &mut S) {
+    store
+        .put_range("iso", "1min", &klines(FIRST_EPOCH, 3))
+        .unwrap();
+    store
+        .put_range("iso", "5min", &klines(FIRST_EPOCH, 5))
+        .unwrap();
+    store
+        .put_range("other", "1min", &klines(FIRST_EPOCH, 7))
+        .unwrap();
+    assert_eq!(
+        store.get_range("iso", "1min", 0, u64::MAX).unwrap().len(),
+        3
+    );
+    assert_eq!(
+        store.get_range("iso", "5min", 0, u64::MAX).unwrap().len(),
+        5
+    );
+    store.delete_before("iso", "5min", u64::MAX).unwrap();
+    assert_eq!(
+        store.get_range("iso", "1min", 0, u64::MAX).unwrap().len(),
+        3
+    );
+    assert_eq!(
+        store.get_range("other", "1min", 0, u64::MAX).unwrap().len(),
+        7
+    );
+}
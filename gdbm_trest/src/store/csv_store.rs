@@ -0,0 +1,115 @@
+//! CSV 后端, 一个 symbol_interval 一个带表头的 `.csv` 文件, 按 `id` 升序.
+//! 写入时整个文件读出来合并后重写, 先写临时文件再 rename, 中途失败不会
+//! 留下半个文件.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{table_name, KlineStore, StoreResult};
+use crate::fixture::Kline;
+
+pub struct CsvStore {
+    dir: PathBuf,
+}
+
+/// 文件不存在时为空
+fn read_all(path: &Path) -> StoreResult<BTreeMap<u64, Kline>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let mut csv_rd = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .from_path(path)?;
+    let mut klines = BTreeMap::new();
+    for k in csv_rd.deserialize::<Kline>() {
+        let k = k?;
+        klines.insert(k.id, k);
+    }
+    Ok(klines)
+}
+
+fn write_all(path: &Path, klines: &BTreeMap<u64, Kline>) -> StoreResult<()> {
+    let tmp = path.with_extension("csv.tmp");
+    {
+        let mut csv_wr = csv::WriterBuilder::new()
+            .has_headers(true)
+            .from_path(&tmp)?;
+        for k in klines.values() {
+            csv_wr.serialize(k)?;
+        }
+        csv_wr.flush()?;
+    }
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+impl CsvStore {
+    pub fn new(dir: impl Into<PathBuf>) -> CsvStore {
+        CsvStore { dir: dir.into() }
+    }
+
+    pub fn path(&self, symbol: &str, interval: &str) -> StoreResult<PathBuf> {
+        Ok(self
+            .dir
+            .join(format!("{}.csv", table_name(symbol, interval)?)))
+    }
+}
+
+impl KlineStore for CsvStore {
+    fn put_range(&mut self, symbol: &str, interval: &str, klines: &[Kline]) -> StoreResult<()> {
+        if klines.is_empty() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(symbol, interval)?;
+        let mut all = read_all(&path)?;
+        for k in klines {
+            all.insert(k.id, k.clone());
+        }
+        write_all(&path, &all)
+    }
+
+    fn get_range(
+        &self,
+        symbol: &str,
+        interval: &str,
+        from: u64,
+        to: u64,
+    ) -> StoreResult<Vec<Kline>> {
+        if from >= to {
+            return Ok(vec![]);
+        }
+        let all = read_all(&self.path(symbol, interval)?)?;
+        Ok(all.range(from..to).map(|(_, k)| k.clone()).collect())
+    }
+
+    fn max_epoch(&self, symbol: &str, interval: &str) -> StoreResult<Option<u64>> {
+        let all = read_all(&self.path(symbol, interval)?)?;
+        Ok(all.keys().next_back().copied())
+    }
+
+    fn delete_before(&mut self, symbol: &str, interval: &str, epoch: u64) -> StoreResult<usize> {
+        let path = self.path(symbol, interval)?;
+        let mut all = read_all(&path)?;
+        let kept = all.split_off(&epoch);
+        let removed = all.len();
+        if removed > 0 {
+            write_all(&path, &kept)?;
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conformance() {
+        let dir = std::env::temp_dir().join(format!("gdbm_trest-store-csv-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        super::super::conformance::check(CsvStore::new(&dir));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,203 @@
+//! gdbm 后端, 和线上的 `{symbol}_{interval}` 文件格式一致: 一个 symbol 一个
+//! 文件, key 是每天零点 (北京时间) 的 epoch 字符串, value 是当天 kline 的 JSON
+//! 数组, 另外 `max_epoch` key 记录最新一条 kline 的 `id`.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use gnudbm::{Entry, GdbmOpener, GdbmResult, Json, ReadHandle, RwHandle};
+
+use super::{table_name, KlineStore, StoreResult};
+use crate::fixture::Kline;
+
+const DAY: u64 = 86400;
+/// 北京时间零点是 UTC 的前一天 16 点
+const DAY_OFFSET: u64 = 8 * 3600;
+/// 第一个完整的 (北京时间) 天, 更早的 epoch 放不进按天分的 key
+const FIRST_DAY: u64 = DAY - DAY_OFFSET;
+const MAX_EPOCH: &str = "max_epoch";
+
+pub struct GdbmStore {
+    dir: PathBuf,
+}
+
+/// `epoch` 所在那天的 key, `epoch` 不能早于 [`FIRST_DAY`]
+fn day_of(epoch: u64) -> u64 {
+    epoch - (epoch % DAY + DAY_OFFSET) % DAY
+}
+
+/// 一天的 kline, key 不存在时为空
+fn decode_day(entry: GdbmResult<Entry>) -> StoreResult<Vec<Kline>> {
+    match entry {
+        Ok(entry) => Ok(entry.decode(&Json)?),
+        Err(e) if e.is_no_record() => Ok(vec![]),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn decode_max(entry: GdbmResult<Entry>) -> StoreResult<Option<u64>> {
+    match entry {
+        Ok(entry) => Ok(Some(entry.decode(&Json)?)),
+        Err(e) if e.is_no_record() => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+impl GdbmStore {
+    pub fn new(dir: impl Into<PathBuf>) -> GdbmStore {
+        GdbmStore { dir: dir.into() }
+    }
+
+    pub fn path(&self, symbol: &str, interval: &str) -> StoreResult<PathBuf> {
+        Ok(self.dir.join(table_name(symbol, interval)?))
+    }
+
+    /// 文件不存在时返回 `None`
+    fn reader(&self, symbol: &str, interval: &str) -> StoreResult<Option<ReadHandle>> {
+        let path = self.path(symbol, interval)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(GdbmOpener::new().readonly(&path)?))
+    }
+
+    fn writer(&self, symbol: &str, interval: &str) -> StoreResult<RwHandle> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.path(symbol, interval)?;
+        Ok(GdbmOpener::new().create(true).readwrite(path)?)
+    }
+}
+
+impl KlineStore for GdbmStore {
+    fn put_range(&mut self, symbol: &str, interval: &str, klines: &[Kline]) -> StoreResult<()> {
+        let max = match klines.iter().map(|k| k.id).max() {
+            Some(max) => max,
+            None => return Ok(()),
+        };
+        if let Some(k) = klines.iter().find(|k| k.id < FIRST_DAY) {
+            return Err(format!("kline {} is before the first day", k.id).into());
+        }
+        let mut db = self.writer(symbol, interval)?;
+
+        let mut days: BTreeMap<u64, Vec<&Kline>> = BTreeMap::new();
+        for k in klines {
+            days.entry(day_of(k.id)).or_default().push(k);
+        }
+        for (day, new) in days {
+            let key = day.to_string();
+            let mut merged: BTreeMap<u64, Kline> = decode_day(db.fetch(&key))?
+                .into_iter()
+                .map(|k| (k.id, k))
+                .collect();
+            for k in new {
+                merged.insert(k.id, k.clone());
+            }
+            let merged: Vec<Kline> = merged.into_values().collect();
+            db.store_with(&Json, &key, &merged)?;
+        }
+
+        if decode_max(db.fetch(MAX_EPOCH))?.is_none_or(|old| max > old) {
+            db.store_with(&Json, MAX_EPOCH, &max)?;
+        }
+        Ok(())
+    }
+
+    fn get_range(
+        &self,
+        symbol: &str,
+        interval: &str,
+        from: u64,
+        to: u64,
+    ) -> StoreResult<Vec<Kline>> {
+        let db = match self.reader(symbol, interval)? {
+            Some(db) => db,
+            None => return Ok(vec![]),
+        };
+        // 只查到最新一天为止, 避免 `to` 很大时逐天空查
+        let to = match decode_max(db.fetch(MAX_EPOCH))? {
+            Some(max) => to.min(max.saturating_add(1)),
+            None => return Ok(vec![]),
+        };
+        let mut klines = vec![];
+        let mut day = day_of(from.max(FIRST_DAY));
+        while day < to {
+            let in_range = decode_day(db.fetch(day.to_string()))?
+                .into_iter()
+                .filter(|k| from <= k.id && k.id < to);
+            klines.extend(in_range);
+            day += DAY;
+        }
+        Ok(klines)
+    }
+
+    fn max_epoch(&self, symbol: &str, interval: &str) -> StoreResult<Option<u64>> {
+        match self.reader(symbol, interval)? {
+            Some(db) => decode_max(db.fetch(MAX_EPOCH)),
+            None => Ok(None),
+        }
+    }
+
+    fn delete_before(&mut self, symbol: &str, interval: &str, epoch: u64) -> StoreResult<usize> {
+        if !self.path(symbol, interval)?.exists() {
+            return Ok(0);
+        }
+        let mut db = self.writer(symbol, interval)?;
+        let cutoff = day_of(epoch.max(FIRST_DAY));
+
+        // `cutoff` 之前的天整个删掉; `max_epoch` 和 `cutoff` 当天留给下面处理
+        let mut removed = 0;
+        let mut bad = None;
+        db.retain(|key, entry| {
+            let day = std::str::from_utf8(key)
+                .ok()
+                .and_then(|k| k.parse::<u64>().ok());
+            if day.is_none_or(|day| day >= cutoff) {
+                return true;
+            }
+            match entry.decode::<Vec<Kline>, _>(&Json) {
+                Ok(klines) => {
+                    removed += klines.len();
+                    false
+                }
+                Err(e) => {
+                    bad.get_or_insert(e);
+                    true
+                }
+            }
+        })?;
+        if let Some(e) = bad {
+            return Err(e.into());
+        }
+
+        let key = cutoff.to_string();
+        let klines = decode_day(db.fetch(&key))?;
+        let before = klines.len();
+        let kept: Vec<Kline> = klines.into_iter().filter(|k| k.id >= epoch).collect();
+        removed += before - kept.len();
+        if kept.is_empty() && before > 0 {
+            db.remove(&key)?;
+        } else if kept.len() < before {
+            db.store_with(&Json, &key, &kept)?;
+        }
+
+        // 全部删光时去掉 `max_epoch`
+        if decode_max(db.fetch(MAX_EPOCH))?.is_some_and(|max| max < epoch) {
+            db.remove(MAX_EPOCH)?;
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conformance() {
+        let dir =
+            std::env::temp_dir().join(format!("gdbm_trest-store-gdbm-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        super::super::conformance::check(GdbmStore::new(&dir));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
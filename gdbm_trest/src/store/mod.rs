@@ -0,0 +1,51 @@
+//! 同一种 kline 数据在 gdbm / sqlite / CSV 里的存取接口.
+//!
+//! 所有后端的约定:
+//! - kline 以开盘时间 `id` 为主键, 重复写入同一个 `id` 会覆盖;
+//! - `get_range` 返回 `from <= id < to` 的 kline, 按 `id` 升序;
+//! - 不同的 `symbol` / `interval` 互不影响.
+
+mod csv_store;
+mod gdbm_store;
+mod sqlite_store;
+
+#[cfg(test)]
+mod conformance;
+
+pub use csv_store::CsvStore;
+pub use gdbm_store::GdbmStore;
+pub use sqlite_store::SqliteStore;
+
+use crate::fixture::Kline;
+
+pub type StoreResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+pub trait KlineStore {
+    /// 写入一批 kline, 已有的同 `id` kline 会被替换
+    fn put_range(&mut self, symbol: &str, interval: &str, klines: &[Kline]) -> StoreResult<()>;
+
+    /// 读取 `from <= id < to` 的 kline, 按 `id` 升序
+    fn get_range(
+        &self,
+        symbol: &str,
+        interval: &str,
+        from: u64,
+        to: u64,
+    ) -> StoreResult<Vec<Kline>>;
+
+    /// 最新一条 kline 的 `id`, 没有数据时为 `None`
+    fn max_epoch(&self, symbol: &str, interval: &str) -> StoreResult<Option<u64>>;
+
+    /// 删除 `id < epoch` 的 kline, 返回删除的条数
+    fn delete_before(&mut self, symbol: &str, interval: &str, epoch: u64) -> StoreResult<usize>;
+}
+
+/// sqlite 表名和文件名都由 symbol 和 interval 拼成, 只允许字母, 数字和下划线
+fn table_name(symbol: &str, interval: &str) -> StoreResult<String> {
+    let name = format!("{}_{}", symbol, interval).to_lowercase();
+    if name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        Ok(name)
+    } else {
+        Err(format!("invalid symbol or interval: {:?} {:?}", symbol, interval).into())
+    }
+}
@@ -0,0 +1,165 @@
+//! sqlite 后端, 一个 symbol_interval 一张表, `id` 是主键.
+
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::{table_name, KlineStore, StoreResult};
+use crate::fixture::Kline;
+
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+/// sqlite 的整数是 i64, 超出范围的边界按最大值算
+fn to_sql_epoch(epoch: u64) -> i64 {
+    i64::try_from(epoch).unwrap_or(i64::MAX)
+}
+
+impl SqliteStore {
+    pub fn open(path: impl AsRef<Path>) -> StoreResult<SqliteStore> {
+        Ok(SqliteStore {
+            conn: Connection::open(path)?,
+        })
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> StoreResult<SqliteStore> {
+        Ok(SqliteStore {
+            conn: Connection::open_in_memory()?,
+        })
+    }
+
+    fn create_table(&self, table: &str) -> StoreResult<()> {
+        self.conn.execute(
+            &format!(
+                "
+                  create table if not exists {} (
+                    id int primary key not null,
+                    open numeric not null,
+                    close numeric not null,
+                    high numeric not null,
+                    low numeric not null,
+                    count numeric not null,
+                    amount numeric not null,
+                    vol numeric not null
+                  );
+                ",
+                table
+            ),
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// 表不存在时返回 `None`, 只读的方法不建表
+    fn existing_table(&self, symbol: &str, interval: &str) -> StoreResult<Option<String>> {
+        let table = table_name(symbol, interval)?;
+        let found = self
+            .conn
+            .prepare_cached("select 1 from sqlite_master where type = 'table' and name = ?")?
+            .query_row([&table], |_| Ok(()))
+            .optional()?;
+        Ok(found.map(|_| table))
+    }
+}
+
+impl KlineStore for SqliteStore {
+    fn put_range(&mut self, symbol: &str, interval: &str, klines: &[Kline]) -> StoreResult<()> {
+        let table = table_name(symbol, interval)?;
+        self.create_table(&table)?;
+        let tx = self.conn.transaction()?;
+        {
+            let mut insert_stmt = tx.prepare_cached(&format!(
+                "
+                  insert or replace into {}(id, open, close, high, low, count, amount, vol)
+                  values (?, ?, ?, ?, ?, ?, ?, ?);
+                ",
+                table
+            ))?;
+            for k in klines {
+                insert_stmt.execute(params![
+                    i64::try_from(k.id)?,
+                    k.open,
+                    k.close,
+                    k.high,
+                    k.low,
+                    k.count,
+                    k.amount,
+                    k.vol
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_range(
+        &self,
+        symbol: &str,
+        interval: &str,
+        from: u64,
+        to: u64,
+    ) -> StoreResult<Vec<Kline>> {
+        let table = match self.existing_table(symbol, interval)? {
+            Some(table) => table,
+            None => return Ok(vec![]),
+        };
+        let mut select_stmt = self.conn.prepare_cached(&format!(
+            "
+              select id, open, close, high, low, count, amount, vol from {}
+              where id >= ? and id < ? order by id;
+            ",
+            table
+        ))?;
+        let klines = select_stmt
+            .query(params![to_sql_epoch(from), to_sql_epoch(to)])?
+            .mapped(|r| {
+                Ok(Kline {
+                    id: r.get::<_, i64>(0)? as u64,
+                    open: r.get(1)?,
+                    close: r.get(2)?,
+                    high: r.get(3)?,
+                    low: r.get(4)?,
+                    count: r.get(5)?,
+                    amount: r.get(6)?,
+                    vol: r.get(7)?,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(klines)
+    }
+
+    fn max_epoch(&self, symbol: &str, interval: &str) -> StoreResult<Option<u64>> {
+        let table = match self.existing_table(symbol, interval)? {
+            Some(table) => table,
+            None => return Ok(None),
+        };
+        let max: Option<i64> =
+            self.conn
+                .query_row(&format!("select max(id) from {};", table), [], |r| r.get(0))?;
+        Ok(max.map(|id| id as u64))
+    }
+
+    fn delete_before(&mut self, symbol: &str, interval: &str, epoch: u64) -> StoreResult<usize> {
+        let table = match self.existing_table(symbol, interval)? {
+            Some(table) => table,
+            None => return Ok(0),
+        };
+        let removed = self.conn.execute(
+            &format!("delete from {} where id < ?;", table),
+            [to_sql_epoch(epoch)],
+        )?;
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conformance() {
+        super::super::conformance::check(SqliteStore::open_in_memory().unwrap());
+    }
+}